@@ -53,8 +53,15 @@ cat > "${CONTENTS_DIR}/Info.plist" << EOF
 </plist>
 EOF
 
-echo "✅ VST3 plugin built successfully!"
-echo "📁 Plugin location: ${VST3_DIR}"
+# Create CLAP bundle - on Linux a CLAP plugin is the shared library with a .clap extension
+CLAP_FILE="target/release/${PLUGIN_NAME}.clap"
+
+echo "📦 Creating CLAP bundle..."
+cp "target/release/libbias_fx_rust.so" "${CLAP_FILE}"
+
+echo "✅ VST3 and CLAP plugins built successfully!"
+echo "📁 VST3 location: ${VST3_DIR}"
+echo "📁 CLAP location: ${CLAP_FILE}"
 echo ""
 echo "🔧 Installation instructions:"
 echo "   For system-wide installation:"
@@ -64,7 +71,11 @@ echo "   For user installation:"
 echo "   mkdir -p ~/.vst3"
 echo "   cp -r \"${VST3_DIR}\" ~/.vst3/"
 echo ""
-echo "🎸 Ready to use in REAPER and other Linux DAWs!"
+echo "   For CLAP hosts (Bitwig, REAPER):"
+echo "   mkdir -p ~/.clap"
+echo "   cp \"${CLAP_FILE}\" ~/.clap/"
+echo ""
+echo "🎸 Ready to use in REAPER, Bitwig and other Linux DAWs!"
 echo "⚡ Features:"
 echo "   • O(1) functional DSP processing for professional performance"
 echo "   • Partitioned FFT convolution for cabinet simulation (256-sample blocks)"
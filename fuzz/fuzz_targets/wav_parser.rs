@@ -0,0 +1,13 @@
+#![no_main]
+
+use bias_fx_rust::IrLoader;
+use libfuzzer_sys::fuzz_target;
+
+// Arbitrary bytes must either parse or return an `IrLoadError` - never panic
+fuzz_target!(|data: &[u8]| {
+    if let Ok(impulse_response) = IrLoader::parse_wav(data) {
+        assert!(impulse_response.sample_rate > 0.0);
+        assert!(!impulse_response.is_empty());
+        assert!(impulse_response.channels.iter().all(|c| c.len() == impulse_response.len()));
+    }
+});
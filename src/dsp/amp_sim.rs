@@ -1,5 +1,7 @@
-use super::distortion::{AsymmetricClipper, TubeSaturation};
-use super::filters::BiquadFilter;
+use super::distortion::{AsymmetricClipper, TubeSaturation, WaveshaperMode};
+use super::filters::{BiquadFilter, ToneStack};
+use super::{BlockParams, Prepare, CONTROL_RATE};
+use super::tone_stack::ToneStackModel;
 
 /// Complete tube amplifier stage simulation - O(1) processing complexity
 /// Combines preamp, tonestack, and power amp stages in functional pipeline
@@ -33,6 +35,12 @@ impl TubeStage {
         stage
     }
     
+    /// Select plain or ADAA evaluation for both waveshapers - O(1), no allocation
+    pub fn set_waveshaper_mode(&mut self, mode: WaveshaperMode) {
+        self.input_tube.set_mode(mode);
+        self.clipper.set_mode(mode);
+    }
+    
     /// Initialize filters with sample rate - O(1) coefficient calculation
     fn initialize_filters(&mut self, sample_rate: f32) {
         // High-frequency rolloff at 8kHz for tube warmth - O(1) setup
@@ -45,14 +53,56 @@ impl TubeStage {
     /// Process sample through complete tube stage - O(1) complexity
     /// Functional pipeline: input -> tube -> clip -> filter -> output
     pub fn process(&mut self, input: f32, drive: f32) -> f32 {
+        self.process_keyed(input, input, drive)
+    }
+    
+    /// Process sample with tube bias tracking keyed from an external detector signal
+    /// Used by linked stereo processing so both channels share the same dynamics
+    pub fn process_keyed(&mut self, input: f32, key: f32, drive: f32) -> f32 {
         input
-            .pipe(|x| self.input_tube.process(x, drive))      // O(1) tube saturation
+            .pipe(|x| self.input_tube.process_keyed(x, key, drive)) // O(1) tube saturation
             .pipe(|x| self.clipper.process(x, drive * 0.5))   // O(1) asymmetric clipping
             .pipe(|x| self.hf_rolloff.process(x))             // O(1) high-frequency rolloff
             .pipe(|x| self.dc_blocker.process(x))             // O(1) DC blocking
     }
+    
+    /// Process a block in place with a per-sample detector key and drive - O(N) complexity
+    /// Nonlinear stages run sample by sample, the linear filters then sweep the whole block
+    pub fn process_block_keyed(&mut self, samples: &mut [f32], key: &[f32], drive: &[f32]) {
+        for ((sample, &key), &drive) in samples.iter_mut().zip(key).zip(drive) {
+            let saturated = self.input_tube.process_keyed(*sample, key, drive);
+            *sample = self.clipper.process(saturated, drive * 0.5);
+        }
+        for sample in samples.iter_mut() {
+            *sample = self.hf_rolloff.process(*sample);
+        }
+        for sample in samples.iter_mut() {
+            *sample = self.dc_blocker.process(*sample);
+        }
+    }
+}
+
+impl Prepare for TubeStage {
+    /// Retune filters and bias tracking for the rate the stage runs at - O(1) complexity
+    fn prepare(&mut self, sample_rate: f32, max_block: usize) {
+        self.initialize_filters(sample_rate);
+        self.input_tube.prepare(sample_rate, max_block);
+        self.clipper.prepare(sample_rate, max_block);
+        self.hf_rolloff.reset();
+        self.dc_blocker.reset();
+    }
 }
 
+/// Sag depth matching the power amp's original fixed compression amount
+pub const DEFAULT_SAG: f32 = 0.3;
+
+/// Presence shelf corner - the band where reduced negative feedback lets the highs through
+const PRESENCE_FREQUENCY: f32 = 3500.0;
+
+/// Resonance peak - the speaker's low-frequency resonance seen through the feedback loop
+const RESONANCE_FREQUENCY: f32 = 100.0;
+const RESONANCE_Q: f32 = 1.4;
+
 /// Power amplifier simulation with compression and saturation - O(1) complexity
 /// Models output transformer saturation and speaker loading effects
 pub struct PowerAmp {
@@ -62,28 +112,61 @@ pub struct PowerAmp {
     /// Compression time constant for realistic power amp response
     compression_coeff: f32,
     
-    /// Output transformer saturation model - O(1) processing
-    transformer_sat: f32,
+    /// Supply sag depth - how far the gain droops under load, 0 keeps the rail stiff
+    sag: f32,
+    
+    /// Presence high shelf ahead of the output stage - O(1) filtering
+    presence_filter: BiquadFilter,
+    
+    /// Resonance low-end peak ahead of the output stage - O(1) filtering
+    resonance_filter: BiquadFilter,
+    
+    sample_rate: f32,
+    
+    /// Presence and resonance values the current coefficients were computed for
+    current_controls: Option<(f32, f32)>,
 }
 
 impl PowerAmp {
     /// Create new power amp simulation - O(1) initialization
     pub fn new() -> Self {
-        Self {
+        let mut power_amp = Self {
             compression_level: 0.0,
             compression_coeff: 0.9995, // Slow compression for power amp feel
-            transformer_sat: 0.0,
+            sag: DEFAULT_SAG,
+            presence_filter: BiquadFilter::new(),
+            resonance_filter: BiquadFilter::new(),
+            sample_rate: 44100.0,
+            current_controls: None,
+        };
+        power_amp.update_controls(0.0, 0.0, DEFAULT_SAG);
+        power_amp
+    }
+    
+    /// Update presence (dB), resonance (dB) and sag depth - O(1)
+    /// Filter coefficients are only recalculated when presence or resonance move
+    pub fn update_controls(&mut self, presence_db: f32, resonance_db: f32, sag: f32) {
+        self.sag = sag;
+        if self.current_controls == Some((presence_db, resonance_db)) {
+            return;
         }
+        self.current_controls = Some((presence_db, resonance_db));
+        
+        self.presence_filter.high_shelf(PRESENCE_FREQUENCY, presence_db, self.sample_rate);
+        self.resonance_filter.peaking_eq(RESONANCE_FREQUENCY, resonance_db, RESONANCE_Q, self.sample_rate);
     }
     
     /// Process sample through power amp stage - O(1) complexity
     /// Models output compression and transformer saturation
     pub fn process(&mut self, input: f32, volume: f32) -> f32 {
+        // Presence and resonance shape what drives the output stage - O(1)
+        let input = self.resonance_filter.process(self.presence_filter.process(input));
+        
         // Calculate output level for compression - O(1)
         let output_level = (input * volume).abs();
         
         // Update compression with exponential smoothing - O(1)
-        let target_compression = (output_level - 0.7).max(0.0) * 0.3;
+        let target_compression = (output_level - 0.7).max(0.0) * self.sag;
         self.compression_level = self.compression_level * self.compression_coeff 
             + target_compression * (1.0 - self.compression_coeff);
         
@@ -96,6 +179,13 @@ impl PowerAmp {
         Self::transformer_saturation(driven_signal)
     }
     
+    /// Process a block in place with per-sample master volume - O(N) complexity
+    pub fn process_block(&mut self, samples: &mut [f32], volume: &[f32]) {
+        for (sample, &volume) in samples.iter_mut().zip(volume) {
+            *sample = self.process(*sample, volume);
+        }
+    }
+    
     /// Output transformer saturation model - O(1) complexity
     /// Simulates magnetic core saturation for warm power amp distortion
     fn transformer_saturation(input: f32) -> f32 {
@@ -105,10 +195,40 @@ impl PowerAmp {
     }
 }
 
+impl Prepare for PowerAmp {
+    /// Retune filters and the sag time constant for the rate the stage runs at - O(1)
+    /// The 0.9995 per-sample coefficient is tuned for 44.1 kHz
+    fn prepare(&mut self, sample_rate: f32, _max_block: usize) {
+        self.sample_rate = sample_rate;
+        self.compression_coeff = 0.9995f32.powf(44100.0 / sample_rate);
+        self.compression_level = 0.0;
+        self.presence_filter.reset();
+        self.resonance_filter.reset();
+        
+        let (presence_db, resonance_db) = self.current_controls.take().unwrap_or((0.0, 0.0));
+        self.update_controls(presence_db, resonance_db, self.sag);
+    }
+}
+
+/// Per-sample inputs of one amp head block, one value per sample at the head's rate
+pub struct AmpSignals<'a> {
+    /// Detector signal for the preamp bias tracking
+    pub key: &'a [f32],
+    pub drive: &'a [f32],
+    /// Power amp drive as linear gain
+    pub master: &'a [f32],
+    /// Noise gate gain, applied between the clipper and the power amp
+    pub gate: &'a [f32],
+}
+
 /// Complete amplifier head simulation - O(1) processing complexity
-/// Combines preamp, tonestack, and power amp in single functional unit
+/// Combines preamp, tonestack, and power amp in single functional unit:
+/// preamp tube stage -> tone stack -> preamp clipper -> power amp
 pub struct AmpHead {
     preamp: TubeStage,
+    tonestack: ToneStack,
+    /// Last preamp gain stage, driven after the tone stack
+    clipper: AsymmetricClipper,
     power_amp: PowerAmp,
 }
 
@@ -117,16 +237,89 @@ impl AmpHead {
     pub fn new() -> Self {
         Self {
             preamp: TubeStage::new(),
+            tonestack: ToneStack::new(44100.0),
+            clipper: AsymmetricClipper::new(),
             power_amp: PowerAmp::new(),
         }
     }
     
+    /// Select plain or ADAA evaluation for the preamp waveshapers - O(1), no allocation
+    pub fn set_waveshaper_mode(&mut self, mode: WaveshaperMode) {
+        self.preamp.set_waveshaper_mode(mode);
+        self.clipper.set_mode(mode);
+    }
+    
+    /// Select the tone stack circuit - rebuilds its filters, no allocation
+    pub fn set_tone_stack_model(&mut self, model: ToneStackModel) {
+        self.tonestack.set_model(model);
+    }
+    
+    /// Update tone controls - O(1) parameter updates
+    pub fn update_tone_controls(&mut self, bass_db: f32, mid_db: f32, treble_db: f32) {
+        self.tonestack.update_controls(bass_db, mid_db, treble_db);
+    }
+    
+    /// Update power amp presence, resonance and sag - O(1) parameter updates
+    pub fn update_power_amp_controls(&mut self, presence_db: f32, resonance_db: f32, sag: f32) {
+        self.power_amp.update_controls(presence_db, resonance_db, sag);
+    }
+    
     /// Process sample through complete amp - O(1) complexity
-    /// Full signal chain: preamp -> power amp processing
+    /// Full signal chain: preamp -> tone -> clipper -> power amp processing
     pub fn process(&mut self, input: f32, drive: f32, volume: f32) -> f32 {
+        self.process_keyed(input, input, drive, volume)
+    }
+    
+    /// Process sample with the preamp bias tracking keyed from a detector signal - O(1) complexity
+    pub fn process_keyed(&mut self, input: f32, key: f32, drive: f32, volume: f32) -> f32 {
         input
-            .pipe(|x| self.preamp.process(x, drive))   // O(1) preamp processing
-            .pipe(|x| self.power_amp.process(x, volume)) // O(1) power amp processing
+            .pipe(|x| self.preamp.process_keyed(x, key, drive)) // O(1) preamp processing
+            .pipe(|x| self.tonestack.process(x))                // O(1) filter processing
+            .pipe(|x| self.clipper.process(x, drive))           // O(1) waveshaping
+            .pipe(|x| self.power_amp.process(x, volume))        // O(1) power amp processing
+    }
+    
+    /// Process a block in place at `ratio` times the host rate - O(N) complexity
+    /// Tone and power amp controls follow `controls` at control rate, or stay where
+    /// they are with `None`
+    pub fn process_block(
+        &mut self,
+        samples: &mut [f32],
+        signals: &AmpSignals,
+        controls: Option<&BlockParams>,
+        ratio: usize,
+    ) {
+        self.preamp.process_block_keyed(samples, signals.key, signals.drive);
+        
+        // Coefficients follow the value at the start of each control-rate chunk
+        let chunk_len = CONTROL_RATE * ratio;
+        for (chunk_index, chunk) in samples.chunks_mut(chunk_len).enumerate() {
+            if let Some(params) = controls {
+                let i = chunk_index * CONTROL_RATE;
+                self.tonestack.update_controls(params.bass[i], params.mid[i], params.treble[i]);
+                self.power_amp.update_controls(params.presence[i], params.resonance[i], params.sag[i]);
+            }
+            let start = chunk_index * chunk_len;
+            let end = start + chunk.len();
+            self.tonestack.process_block(chunk);
+            self.clipper.process_block(chunk, &signals.drive[start..end]);
+            for (sample, &gain) in chunk.iter_mut().zip(&signals.gate[start..end]) {
+                *sample *= gain;
+            }
+            self.power_amp.process_block(chunk, &signals.master[start..end]);
+        }
+    }
+}
+
+impl Prepare for AmpHead {
+    /// Retune every stage for the rate the head runs at - O(1), no allocation
+    /// Re-run on the audio thread when the oversampling ratio changes; the tone stack
+    /// keeps its model and control settings
+    fn prepare(&mut self, sample_rate: f32, max_block: usize) {
+        self.preamp.prepare(sample_rate, max_block);
+        self.tonestack.prepare(sample_rate, max_block);
+        self.clipper.prepare(sample_rate, max_block);
+        self.power_amp.prepare(sample_rate, max_block);
     }
 }
 
@@ -177,4 +370,134 @@ impl BiquadFilter {
         self.set_coefficients(b0/a0, b1/a0, b2/a0, a1/a0, a2/a0);
     }
     
-}
\ No newline at end of file
+    /// Configure as high-shelf filter with unity slope - O(1) coefficient calculation
+    pub fn high_shelf(&mut self, freq: f32, gain_db: f32, sample_rate: f32) {
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        let w = 2.0 * std::f32::consts::PI * freq / sample_rate;
+        let cos_w = w.cos();
+        let alpha = w.sin() / std::f32::consts::SQRT_2;
+        let two_sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+        
+        let b0 = a * ((a + 1.0) + (a - 1.0) * cos_w + two_sqrt_a_alpha);
+        let b1 = -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w);
+        let b2 = a * ((a + 1.0) + (a - 1.0) * cos_w - two_sqrt_a_alpha);
+        let a0 = (a + 1.0) - (a - 1.0) * cos_w + two_sqrt_a_alpha;
+        let a1 = 2.0 * ((a - 1.0) - (a + 1.0) * cos_w);
+        let a2 = (a + 1.0) - (a - 1.0) * cos_w - two_sqrt_a_alpha;
+        
+        self.set_coefficients(b0/a0, b1/a0, b2/a0, a1/a0, a2/a0);
+    }
+
+    /// Configure as first-order low-pass filter, bilinear transform - O(1) coefficient calculation
+    pub fn one_pole_low_pass(&mut self, freq: f32, sample_rate: f32) {
+        let k = (std::f32::consts::PI * freq / sample_rate).tan();
+        let b0 = k / (1.0 + k);
+        self.set_coefficients(b0, b0, 0.0, (k - 1.0) / (k + 1.0), 0.0);
+    }
+
+    /// Configure as first-order high-pass filter, bilinear transform - O(1) coefficient calculation
+    pub fn one_pole_high_pass(&mut self, freq: f32, sample_rate: f32) {
+        let k = (std::f32::consts::PI * freq / sample_rate).tan();
+        let b0 = 1.0 / (1.0 + k);
+        self.set_coefficients(b0, -b0, 0.0, (k - 1.0) / (k + 1.0), 0.0);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::MAX_BLOCK_SIZE;
+
+    /// Steady-state peak and RMS of a sine through the power amp
+    fn sine_response(power_amp: &mut PowerAmp, frequency: f32, amplitude: f32, volume: f32) -> (f32, f32) {
+        let output: Vec<f32> = (0..44100)
+            .map(|n| {
+                let input = amplitude * (2.0 * std::f32::consts::PI * frequency * n as f32 / 44100.0).sin();
+                power_amp.process(input, volume)
+            })
+            .collect();
+        let tail = &output[22050..];
+        let peak = tail.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+        let rms = (tail.iter().map(|x| x * x).sum::<f32>() / tail.len() as f32).sqrt();
+        (peak, rms)
+    }
+
+    #[test]
+    fn test_master_and_sag_shape_power_amp_distortion() {
+        // Quiet master stays close to a clean sine, cranked master squares it off
+        let (peak, rms) = sine_response(&mut PowerAmp::new(), 220.0, 0.2, 1.0);
+        let clean_crest = peak / rms;
+        assert!((clean_crest - std::f32::consts::SQRT_2).abs() < 0.05);
+
+        let (peak, rms) = sine_response(&mut PowerAmp::new(), 220.0, 0.2, 16.0);
+        assert!(peak / rms < clean_crest - 0.15, "crest factor {} at full master", peak / rms);
+
+        // More sag pulls the level down harder once the output stage is loaded
+        let mut stiff = PowerAmp::new();
+        stiff.update_controls(0.0, 0.0, 0.0);
+        let mut saggy = PowerAmp::new();
+        saggy.update_controls(0.0, 0.0, 1.0);
+        let (_, stiff_rms) = sine_response(&mut stiff, 220.0, 0.5, 4.0);
+        let (_, saggy_rms) = sine_response(&mut saggy, 220.0, 0.5, 4.0);
+        assert!(saggy_rms < stiff_rms * 0.9, "sag {} vs stiff {}", saggy_rms, stiff_rms);
+    }
+
+    #[test]
+    fn test_presence_and_resonance_voice_the_output_stage() {
+        let level = |presence_db: f32, resonance_db: f32, frequency: f32| {
+            let mut power_amp = PowerAmp::new();
+            power_amp.update_controls(presence_db, resonance_db, 0.0);
+            sine_response(&mut power_amp, frequency, 0.05, 1.0).1
+        };
+
+        let flat_high = level(0.0, 0.0, 8000.0);
+        let flat_low = level(0.0, 0.0, 100.0);
+        assert!(level(12.0, 0.0, 8000.0) > flat_high * 2.5);
+        assert!(level(-12.0, 0.0, 8000.0) < flat_high * 0.4);
+        assert!((level(12.0, 0.0, 100.0) / flat_low - 1.0).abs() < 0.05);
+        assert!(level(0.0, 12.0, 100.0) > flat_low * 2.5);
+        assert!((level(0.0, 12.0, 8000.0) / flat_high - 1.0).abs() < 0.05);
+    }
+
+    /// Steady-state gain of a filter for a sine at `frequency`, measured over the second half of one second
+    fn measured_gain(filter: &BiquadFilter, frequency: f32, sample_rate: f32) -> f32 {
+        let mut filter = filter.clone();
+        let length = sample_rate as usize;
+        let mut peak = 0.0f32;
+        for n in 0..length {
+            let phase = (frequency as f64 * n as f64 / sample_rate as f64).fract();
+            let output = filter.process((2.0 * std::f64::consts::PI * phase).sin() as f32);
+            if n >= length / 2 {
+                peak = peak.max(output.abs());
+            }
+        }
+        peak
+    }
+
+    /// Frequency between `low` and `high` where the measured gain crosses -3 dB, by bisection
+    fn measured_corner(filter: &BiquadFilter, sample_rate: f32, mut low: f32, mut high: f32) -> f32 {
+        let rising = measured_gain(filter, high, sample_rate) > measured_gain(filter, low, sample_rate);
+        for _ in 0..12 {
+            let middle = (low * high).sqrt();
+            let passes = measured_gain(filter, middle, sample_rate) > std::f32::consts::FRAC_1_SQRT_2;
+            if passes == rising {
+                high = middle;
+            } else {
+                low = middle;
+            }
+        }
+        (low * high).sqrt()
+    }
+
+    #[test]
+    fn test_prepare_keeps_tube_stage_corners_across_sample_rates() {
+        for sample_rate in [44100.0, 48000.0, 88200.0, 96000.0, 176400.0, 192000.0] {
+            let mut head = AmpHead::new();
+            head.prepare(sample_rate, MAX_BLOCK_SIZE);
+
+            let rolloff = measured_corner(&head.preamp.hf_rolloff, sample_rate, 2000.0, 20000.0);
+            let dc_blocker = measured_corner(&head.preamp.dc_blocker, sample_rate, 5.0, 80.0);
+            assert!((rolloff / 8000.0 - 1.0).abs() < 0.02, "{} Hz rolloff at {} Hz", rolloff, sample_rate);
+            assert!((dc_blocker / 20.0 - 1.0).abs() < 0.02, "{} Hz DC blocker at {} Hz", dc_blocker, sample_rate);
+        }
+    }
+}
@@ -1,20 +1,68 @@
-use super::convolution::{PartitionedConvolution, ConvolutionError};
+use super::convolution::{ConvolutionEngine, ConvolutionError, ConvolutionMode};
+use super::ir_loader::{ImpulseResponse, IrLoader};
+use super::user_ir::{PreparedChannelIr, PreparedEngines};
+use super::{Prepare, MAX_BLOCK_SIZE};
 use std::collections::HashMap;
 
+/// Sample rate the built-in cabinet IRs were designed at
+pub(super) const BUILTIN_IR_SAMPLE_RATE: f32 = 44100.0;
+
+/// Longest time-alignment delay of a blend slot
+pub const MAX_SLOT_DELAY_MS: f32 = 1.0;
+
+/// Unit impulse loaded for Direct slots, so a dry slot stays time-aligned with an IR slot
+pub(super) static UNIT_IMPULSE: [f32; 1] = [1.0];
+
 /// Professional cabinet simulation using impulse responses
 /// Provides authentic speaker cabinet modeling with multiple cabinet types
 /// Achieves O(1) per-sample processing through partitioned FFT convolution
+///
+/// Two IR slots can be blended like two mics on one speaker: slot 0 is always
+/// active, slot 1 is the optional blend slot. Each has its own engines, level,
+/// pan, polarity and time-alignment delay.
+///
+/// Cabinets prepared off the audio thread are installed with `install_cabinet`,
+/// which crossfades from the old IR to the new one instead of cutting over.
 pub struct CabinetSimulator {
-    /// High-performance convolution engine for IR processing
-    convolution_engine: PartitionedConvolution,
+    /// Blend slots - index 0 is the main cabinet, index 1 the optional blend
+    slots: [CabinetSlot; 2],
+    
+    /// Whether the blend slot contributes to the output
+    blend_enabled: bool,
+    
+    /// Block size used when (re)creating the low-CPU uniform engine
+    block_size: usize,
+    
+    /// Engine type the cabinet runs - slots still holding engines of another type
+    /// keep playing until engines prepared off the audio thread are installed
+    convolution_mode: ConvolutionMode,
+    
+    /// Length of the equal-power crossfade when a slot's IR changes - 0 switches instantly
+    crossfade_ms: f32,
     
-    /// Currently loaded cabinet type for O(1) identification
-    current_cabinet: CabinetType,
+    /// Set by switches of a playing slot: passthrough slots keep running their engines
+    /// so the output delay and reported latency stay put. Only loading or resetting
+    /// off the audio thread lets the cabinet drop into bypass again.
+    hold_latency: bool,
     
-    /// Pre-loaded impulse responses mapped by cabinet type
+    /// Pre-loaded impulse responses mapped by cabinet type, resampled to `sample_rate`
     /// IRs are loaded from files or use fallbacks for zero loading latency
     cabinet_impulses: HashMap<CabinetType, Vec<f32>>,
     
+    /// User-supplied impulse response at `sample_rate` - empty until one is installed
+    user_impulse: Vec<f32>,
+    
+    /// Opposite-input half of a true-stereo user IR - empty for mono and stereo IRs
+    user_cross_impulse: Vec<f32>,
+    
+    /// This output's row of the user IR as read from disk, kept for re-preparing
+    /// at a new sample rate (see `ImpulseResponse::output_row`)
+    user_source: ImpulseResponse,
+    
+    /// Output channel this cabinet feeds (0 = left, 1 = right) for slot panning
+    /// `None` for mono output, where pan has no effect
+    output_channel: Option<usize>,
+    
     /// Wet/dry mix control for cabinet effect intensity
     /// 0.0 = completely dry, 1.0 = completely wet (cabinet processed)
     mix: f32,
@@ -23,6 +71,218 @@ pub struct CabinetSimulator {
     sample_rate: f32,
 }
 
+/// Per-block settings of one blend slot - smoothed values hold one value per sample
+#[derive(Clone)]
+pub struct CabinetSlotParams {
+    /// Linear slot gain
+    pub level: [f32; MAX_BLOCK_SIZE],
+    
+    /// -1.0 = hard left, 0.0 = centre, 1.0 = hard right
+    pub pan: [f32; MAX_BLOCK_SIZE],
+    
+    /// Time-alignment delay in milliseconds, 0 to `MAX_SLOT_DELAY_MS`
+    pub delay_ms: [f32; MAX_BLOCK_SIZE],
+    
+    /// Flip the slot's polarity
+    pub invert: bool,
+}
+
+impl Default for CabinetSlotParams {
+    fn default() -> Self {
+        Self {
+            level: [1.0; MAX_BLOCK_SIZE],
+            pan: [0.0; MAX_BLOCK_SIZE],
+            delay_ms: [0.0; MAX_BLOCK_SIZE],
+            invert: false,
+        }
+    }
+}
+
+/// One IR slot of the cabinet blender
+struct CabinetSlot {
+    /// Convolution engine for the slot's IR
+    engine: ConvolutionEngine,
+    
+    /// Second engine for true-stereo IRs - convolves the opposite channel's input
+    cross_engine: ConvolutionEngine,
+    
+    /// Cabinet loaded into this slot
+    cabinet_type: CabinetType,
+    
+    /// Slot passes its input through - Direct, or the user IR slot without a file
+    passthrough: bool,
+    
+    /// `cross_engine` holds the opposite-input half of a true-stereo IR
+    true_stereo: bool,
+    
+    /// Previous IR's engines, faded out after a switch - idle otherwise
+    fade_engine: ConvolutionEngine,
+    fade_cross_engine: ConvolutionEngine,
+    fade_true_stereo: bool,
+    
+    /// The crossfade starts from the slot's input rather than `fade_engine` - the
+    /// cabinet was bypassed, so the input is what was playing
+    fade_from_input: bool,
+    
+    /// Samples left in the running crossfade, out of `fade_length`
+    fade_remaining: usize,
+    fade_length: usize,
+    
+    level: f32,
+    pan: f32,
+    invert: bool,
+    
+    /// Level, polarity and pan combined for the cabinet's output channel
+    gain: f32,
+    
+    /// Mic time alignment
+    delay: FractionalDelay,
+}
+
+impl CabinetSlot {
+    fn new(mode: ConvolutionMode, block_size: usize, sample_rate: f32) -> Self {
+        Self {
+            engine: ConvolutionEngine::new(mode, block_size),
+            cross_engine: ConvolutionEngine::new(mode, block_size),
+            cabinet_type: CabinetType::Direct,
+            passthrough: true,
+            true_stereo: false,
+            fade_engine: ConvolutionEngine::new(mode, block_size),
+            fade_cross_engine: ConvolutionEngine::new(mode, block_size),
+            fade_true_stereo: false,
+            fade_from_input: false,
+            fade_remaining: 0,
+            fade_length: 0,
+            level: 1.0,
+            pan: 0.0,
+            invert: false,
+            gain: 1.0,
+            delay: FractionalDelay::new((MAX_SLOT_DELAY_MS * 0.001 * sample_rate).ceil() as usize),
+        }
+    }
+    
+    /// Update level, pan and polarity - O(1), gain is only recomputed on change
+    fn set_params(&mut self, level: f32, pan: f32, invert: bool, output_channel: Option<usize>) {
+        if level != self.level || pan != self.pan || invert != self.invert {
+            self.level = level;
+            self.pan = pan.clamp(-1.0, 1.0);
+            self.invert = invert;
+            self.update_gain(output_channel);
+        }
+    }
+    
+    /// Balance-style pan law: centre is unity on both sides and panning fades the
+    /// opposite side out, so a centred slot sounds exactly like the unblended cabinet
+    fn update_gain(&mut self, output_channel: Option<usize>) {
+        let polarity = if self.invert { -1.0 } else { 1.0 };
+        let pan_gain = match output_channel {
+            Some(0) if self.pan > 0.0 => (self.pan * std::f32::consts::FRAC_PI_2).cos(),
+            Some(1) if self.pan < 0.0 => (-self.pan * std::f32::consts::FRAC_PI_2).cos(),
+            _ => 1.0,
+        };
+        self.gain = self.level * polarity * pan_gain;
+    }
+    
+    /// Convolve, time-align and scale one sample - O(1) amortized
+    /// While a crossfade runs both IRs are convolved and blended with equal power
+    fn process(&mut self, input: f32, cross_input: f32) -> f32 {
+        let mut wet = self.engine.process_sample(input);
+        if self.true_stereo {
+            wet += self.cross_engine.process_sample(cross_input);
+        }
+        
+        if self.fade_remaining > 0 {
+            let mut old = if self.fade_from_input { input } else { self.fade_engine.process_sample(input) };
+            if self.fade_true_stereo && !self.fade_from_input {
+                old += self.fade_cross_engine.process_sample(cross_input);
+            }
+            let position = 1.0 - self.fade_remaining as f32 / self.fade_length as f32;
+            let angle = position * std::f32::consts::FRAC_PI_2;
+            wet = wet * angle.sin() + old * angle.cos();
+            self.fade_remaining -= 1;
+        }
+        
+        self.delay.process(wet) * self.gain
+    }
+    
+    /// Move the current engines out to fade and put `engine`/`cross_engine` in their
+    /// place - O(1), no allocation. The idle fade engines end up in the arguments.
+    fn begin_crossfade(
+        &mut self,
+        engine: &mut ConvolutionEngine,
+        cross_engine: &mut ConvolutionEngine,
+        true_stereo: bool,
+        fade_length: usize,
+        fade_from_input: bool,
+    ) {
+        std::mem::swap(&mut self.fade_engine, &mut self.engine);
+        std::mem::swap(&mut self.fade_cross_engine, &mut self.cross_engine);
+        std::mem::swap(&mut self.engine, engine);
+        std::mem::swap(&mut self.cross_engine, cross_engine);
+        self.fade_true_stereo = self.true_stereo;
+        self.true_stereo = true_stereo;
+        self.fade_from_input = fade_from_input;
+        self.fade_length = fade_length;
+        self.fade_remaining = fade_length;
+    }
+    
+    fn reset(&mut self) {
+        self.engine.reset();
+        self.cross_engine.reset();
+        self.delay.reset();
+        self.fade_remaining = 0;
+    }
+}
+
+/// Short fractional delay line - 4-point Lagrange interpolation
+struct FractionalDelay {
+    /// Circular buffer, power-of-two length
+    buffer: Vec<f32>,
+    write_pos: usize,
+    delay_samples: f32,
+}
+
+impl FractionalDelay {
+    /// Allocate for delays up to `max_delay_samples` - done once, off the audio thread
+    fn new(max_delay_samples: usize) -> Self {
+        Self {
+            buffer: vec![0.0; (max_delay_samples + 4).next_power_of_two()],
+            write_pos: 0,
+            delay_samples: 0.0,
+        }
+    }
+    
+    fn set_delay(&mut self, delay_samples: f32) {
+        self.delay_samples = delay_samples.clamp(0.0, (self.buffer.len() - 4) as f32);
+    }
+    
+    /// Write one sample and read it back delayed - O(1)
+    fn process(&mut self, input: f32) -> f32 {
+        let mask = self.buffer.len() - 1;
+        self.buffer[self.write_pos] = input;
+        
+        // Taps centred around the read position; zero delay has no earlier tap,
+        // so it interpolates from the newest four samples instead
+        let whole = self.delay_samples as usize;
+        let first_tap = whole.max(1) - 1;
+        let x = self.delay_samples - first_tap as f32;
+        let tap = |offset: usize| self.buffer[self.write_pos.wrapping_sub(first_tap + offset) & mask];
+        
+        let output = -(x - 1.0) * (x - 2.0) * (x - 3.0) / 6.0 * tap(0)
+            + x * (x - 2.0) * (x - 3.0) / 2.0 * tap(1)
+            - x * (x - 1.0) * (x - 3.0) / 2.0 * tap(2)
+            + x * (x - 1.0) * (x - 2.0) / 6.0 * tap(3);
+        
+        self.write_pos = (self.write_pos + 1) & mask;
+        output
+    }
+    
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.write_pos = 0;
+    }
+}
+
 /// Professional cabinet types modeling industry-standard speakers
 /// Each represents a different frequency response and harmonic characteristic
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -46,6 +306,10 @@ pub enum CabinetType {
     /// Direct input (no cabinet simulation) - clean signal path
     /// Used when external cabinet simulation or direct recording is preferred
     Direct,
+    
+    /// User-loaded impulse response file
+    /// Passes the signal through unprocessed until an IR has been installed
+    UserIr,
 }
 
 impl nih_plug::prelude::Enum for CabinetType {
@@ -56,6 +320,7 @@ impl nih_plug::prelude::Enum for CabinetType {
             "Vox AC30 Blue",
             "Mesa 4x12 Recto",
             "Direct",
+            "User IR",
         ]
     }
 
@@ -66,6 +331,7 @@ impl nih_plug::prelude::Enum for CabinetType {
             "vox_ac30_blue", 
             "mesa_4x12_recto",
             "direct",
+            "user_ir",
         ])
     }
 
@@ -76,6 +342,7 @@ impl nih_plug::prelude::Enum for CabinetType {
             CabinetType::VoxAC30Blue => 2,
             CabinetType::Mesa4x12Recto => 3,
             CabinetType::Direct => 4,
+            CabinetType::UserIr => 5,
         }
     }
 
@@ -86,6 +353,7 @@ impl nih_plug::prelude::Enum for CabinetType {
             2 => CabinetType::VoxAC30Blue,
             3 => CabinetType::Mesa4x12Recto,
             4 => CabinetType::Direct,
+            5 => CabinetType::UserIr,
             _ => CabinetType::Marshall4x12V30, // Default fallback
         }
     }
@@ -97,9 +365,20 @@ impl CabinetSimulator {
     /// Recommended: 128-512 samples for real-time performance
     pub fn new(block_size: usize, sample_rate: f32) -> Self {
         let mut simulator = Self {
-            convolution_engine: PartitionedConvolution::new(block_size),
-            current_cabinet: CabinetType::Marshall4x12V30,
+            slots: [
+                CabinetSlot::new(ConvolutionMode::LowCpu, block_size, sample_rate),
+                CabinetSlot::new(ConvolutionMode::LowCpu, block_size, sample_rate),
+            ],
+            blend_enabled: false,
+            block_size,
+            convolution_mode: ConvolutionMode::LowCpu,
+            crossfade_ms: 0.0,
+            hold_latency: false,
             cabinet_impulses: HashMap::new(),
+            user_impulse: Vec::new(),
+            user_cross_impulse: Vec::new(),
+            user_source: ImpulseResponse::default(),
+            output_channel: None,
             mix: 1.0, // Default to fully wet (cabinet enabled)
             sample_rate,
         };
@@ -115,42 +394,236 @@ impl CabinetSimulator {
         simulator
     }
     
-    /// Load all cabinet impulse responses into memory
+    /// Load all cabinet impulse responses into memory at the current sample rate
     /// This heavy operation is done once during initialization for O(1) runtime switching
     fn load_cabinet_impulses(&mut self) {
-        use super::ir_loader::IrLoader;
-        use std::path::Path;
+        // Factory cabinets use the built-in IRs; IR files are loaded by absolute path
+        // as the user IR, off the audio thread (see `UserIrSlot`)
+        let factory_cabinets = [
+            CabinetType::Marshall4x12V30,
+            CabinetType::FenderTwin2x12,
+            CabinetType::VoxAC30Blue,
+            CabinetType::Mesa4x12Recto,
+        ];
         
-        // Always use built-in IRs for now to prevent crashes
-        // Real IR loading can be re-enabled once we solve the working directory issue
-        println!("ℹ️  Using built-in impulse responses for stability");
+        for cabinet_type in factory_cabinets {
+            if let Some(impulse_response) = builtin_impulse(cabinet_type) {
+                let prepared = IrLoader::resample(impulse_response, BUILTIN_IR_SAMPLE_RATE, self.sample_rate);
+                self.cabinet_impulses.insert(cabinet_type, prepared);
+            }
+        }
+    }
+    
+    /// Re-prepare every loaded IR for a new host sample rate - O(M log N) per IR
+    /// Allocates and resamples, so call from `initialize`, never from the audio thread
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        if sample_rate == self.sample_rate {
+            return;
+        }
         
-        // Use built-in fallback IRs converted to Vec<f32>
-        self.cabinet_impulses.insert(CabinetType::Marshall4x12V30, MARSHALL_4X12_V30_IR.to_vec());
-        self.cabinet_impulses.insert(CabinetType::FenderTwin2x12, FENDER_TWIN_2X12_IR.to_vec());
-        self.cabinet_impulses.insert(CabinetType::VoxAC30Blue, VOX_AC30_BLUE_IR.to_vec());
-        self.cabinet_impulses.insert(CabinetType::Mesa4x12Recto, MESA_4X12_RECTO_IR.to_vec());
+        self.sample_rate = sample_rate;
+        self.load_cabinet_impulses();
+        if !self.user_source.is_empty() {
+            let mut channels = self.user_source.resampled(sample_rate).channels.into_iter();
+            self.user_impulse = channels.next().unwrap_or_default();
+            self.user_cross_impulse = channels.next().unwrap_or_default();
+        }
+        
+        let max_delay_samples = (MAX_SLOT_DELAY_MS * 0.001 * sample_rate).ceil() as usize;
+        for index in 0..self.slots.len() {
+            self.slots[index].delay = FractionalDelay::new(max_delay_samples);
+            if let Err(e) = self.load_slot(index, self.slots[index].cabinet_type) {
+                eprintln!("Warning: Failed to reload cabinet: {}", e);
+            }
+        }
+    }
+    
+    /// Get the sample rate the loaded IRs are prepared for - O(1) lookup
+    pub fn get_sample_rate(&self) -> f32 {
+        self.sample_rate
     }
     
     /// Switch to different cabinet type - O(M log N) where M = IR length, N = block size
     /// This operation is expensive but done infrequently (user parameter changes)
     /// Runtime processing remains O(1) per sample after cabinet load
     pub fn load_cabinet(&mut self, cabinet_type: CabinetType) -> Result<(), ConvolutionError> {
-        // Direct mode bypasses convolution entirely
-        if cabinet_type == CabinetType::Direct {
-            self.current_cabinet = cabinet_type;
-            self.convolution_engine.reset();
-            return Ok(());
+        self.load_slot(0, cabinet_type)
+    }
+    
+    /// Select the blend slot's cabinet, or `None` to turn blending off
+    /// Loads only when the selection changes - O(M log N) then, O(1) otherwise
+    pub fn set_blend_cabinet(&mut self, cabinet_type: Option<CabinetType>) -> Result<(), ConvolutionError> {
+        match cabinet_type {
+            None => {
+                self.blend_enabled = false;
+                Ok(())
+            }
+            Some(cabinet_type) if self.blend_enabled && cabinet_type == self.slots[1].cabinet_type => Ok(()),
+            Some(cabinet_type) => {
+                self.load_slot(1, cabinet_type)?;
+                self.blend_enabled = true;
+                Ok(())
+            }
         }
+    }
+    
+    /// Turn the blend slot on or off without changing its cabinet - O(1)
+    pub fn set_blend_enabled(&mut self, enabled: bool) {
+        self.blend_enabled = enabled;
+    }
+    
+    /// Get the blend slot's cabinet - `None` when blending is off
+    pub fn get_blend_cabinet(&self) -> Option<CabinetType> {
+        self.blend_enabled.then_some(self.slots[1].cabinet_type)
+    }
+    
+    /// Load a cabinet into one slot - the slot keeps its previous cabinet on error
+    /// Switches instantly and prepares the IR in place, so call it from `initialize`;
+    /// the audio thread installs cabinets prepared by `CabinetLoader` instead
+    pub fn load_slot(&mut self, index: usize, cabinet_type: CabinetType) -> Result<(), ConvolutionError> {
+        // Direct and the empty user IR slot pass signal through a unit impulse, so
+        // they stay time-aligned with an IR in the other slot
+        let (impulse_response, cross_impulse): (&[f32], &[f32]) = match cabinet_type {
+            CabinetType::Direct => (&UNIT_IMPULSE, &[]),
+            CabinetType::UserIr if self.user_impulse.is_empty() => (&UNIT_IMPULSE, &[]),
+            CabinetType::UserIr => (&self.user_impulse, &self.user_cross_impulse),
+            _ => match self.cabinet_impulses.get(&cabinet_type) {
+                Some(impulse_response) => (impulse_response, &[]),
+                None => return Err(ConvolutionError::EmptyImpulseResponse),
+            },
+        };
         
-        // Load impulse response for selected cabinet
-        if let Some(impulse_response) = self.cabinet_impulses.get(&cabinet_type) {
-            self.convolution_engine.load_impulse_response(impulse_response)?;
-            self.current_cabinet = cabinet_type;
-            Ok(())
-        } else {
-            Err(ConvolutionError::EmptyImpulseResponse)
+        let slot = &mut self.slots[index];
+        if slot.engine.mode() != self.convolution_mode {
+            slot.engine = ConvolutionEngine::new(self.convolution_mode, self.block_size);
+            slot.cross_engine = ConvolutionEngine::new(self.convolution_mode, self.block_size);
+        }
+        slot.reset();
+        slot.engine.load_impulse_response(impulse_response)?;
+        if !cross_impulse.is_empty() {
+            slot.cross_engine.load_impulse_response(cross_impulse)?;
+        }
+        slot.cabinet_type = cabinet_type;
+        slot.passthrough = std::ptr::eq(impulse_response, &UNIT_IMPULSE[..]);
+        slot.true_stereo = !cross_impulse.is_empty();
+        self.hold_latency = false;
+        Ok(())
+    }
+    
+    /// Number of slots currently contributing to the output
+    fn active_slots(&self) -> usize {
+        if self.blend_enabled { 2 } else { 1 }
+    }
+    
+    /// Select the low-CPU or zero-latency convolution engines - O(1), no allocation
+    /// Only records the mode: `load_slot` builds engines of this type in place, and on
+    /// the audio thread the engines come prepared through `install_cabinet`
+    pub fn set_convolution_mode(&mut self, mode: ConvolutionMode) {
+        self.convolution_mode = mode;
+    }
+    
+    /// Get the selected convolution engine mode - O(1) lookup
+    pub fn get_convolution_mode(&self) -> ConvolutionMode {
+        self.convolution_mode
+    }
+    
+    /// Set how long a slot crossfades from its old IR to a newly installed one
+    pub fn set_crossfade_time(&mut self, crossfade_ms: f32) {
+        self.crossfade_ms = crossfade_ms.max(0.0);
+    }
+    
+    /// Whether a slot is still fading between two IRs - installs wait until it's done
+    pub fn is_crossfading(&self) -> bool {
+        self.slots.iter().any(|slot| slot.fade_remaining > 0)
+    }
+    
+    /// Switch a slot to a cabinet prepared off the audio thread - O(1), no allocation
+    /// Active slots crossfade from the old IR; the idle engines end up in `prepared`
+    /// so the caller can drop them on a non-real-time thread. Returns false without
+    /// touching the slot when `prepared` was built for another convolution mode.
+    pub fn install_cabinet(
+        &mut self,
+        index: usize,
+        cabinet_type: CabinetType,
+        passthrough: bool,
+        prepared: &mut PreparedChannelIr,
+    ) -> bool {
+        if prepared.engine.mode() != self.convolution_mode {
+            return false;
         }
+        
+        let bypassed = self.is_bypassed();
+        let fade_length = self.slot_fade_length(index);
+        let true_stereo = !prepared.cross_samples.is_empty();
+        let slot = &mut self.slots[index];
+        slot.begin_crossfade(&mut prepared.engine, &mut prepared.cross_engine, true_stereo, fade_length, bypassed);
+        slot.cabinet_type = cabinet_type;
+        slot.passthrough = passthrough;
+        self.hold_latency |= index < self.active_slots();
+        true
+    }
+    
+    /// Crossfade length for switching one slot - none for a slot that isn't playing
+    /// If the whole cabinet was bypassed its engines sat idle with stale input, so
+    /// the fade starts from the dry input the bypass was playing instead
+    fn slot_fade_length(&self, index: usize) -> usize {
+        if index >= self.active_slots() {
+            return 0;
+        }
+        (self.crossfade_ms * 0.001 * self.sample_rate) as usize
+    }
+    
+    /// Exchange the user IR with one prepared off the audio thread - O(1), no allocation
+    /// Each slot showing the user IR crossfades to its own engines: the main slot to
+    /// `prepared`'s, the blend slot to `blend`. The previous IR and engines end up in the
+    /// arguments so the caller can drop them on a non-real-time thread. Returns false
+    /// without touching anything when the engines were built for another convolution mode.
+    pub fn swap_user_ir(&mut self, prepared: &mut PreparedChannelIr, blend: &mut PreparedEngines) -> bool {
+        if prepared.engine.mode() != self.convolution_mode {
+            return false;
+        }
+        
+        std::mem::swap(&mut self.user_impulse, &mut prepared.samples);
+        std::mem::swap(&mut self.user_cross_impulse, &mut prepared.cross_samples);
+        std::mem::swap(&mut self.user_source, &mut prepared.source);
+        
+        let true_stereo = !self.user_cross_impulse.is_empty();
+        let bypassed = self.is_bypassed();
+        let engines = [
+            (&mut prepared.engine, &mut prepared.cross_engine),
+            (&mut blend.engine, &mut blend.cross_engine),
+        ];
+        for (index, (engine, cross_engine)) in engines.into_iter().enumerate() {
+            if self.slots[index].cabinet_type != CabinetType::UserIr {
+                continue;
+            }
+            let fade_length = self.slot_fade_length(index);
+            let slot = &mut self.slots[index];
+            slot.begin_crossfade(engine, cross_engine, true_stereo, fade_length, bypassed);
+            slot.passthrough = false;
+            self.hold_latency |= index < self.active_slots();
+        }
+        true
+    }
+    
+    /// Whether a user IR is installed - O(1) lookup
+    pub fn has_user_ir(&self) -> bool {
+        !self.user_impulse.is_empty()
+    }
+    
+    /// Whether an active slot holds a true-stereo IR that also needs the opposite input
+    pub fn is_true_stereo(&self) -> bool {
+        self.slots[..self.active_slots()].iter().any(|slot| slot.true_stereo)
+    }
+    
+    /// Whether no active slot runs an IR - the cabinet is skipped entirely
+    /// A slot fading away from an IR still needs its engines, and after a switch
+    /// during playback the passthrough engines keep the latency of the IR engines
+    fn is_bypassed(&self) -> bool {
+        !self.hold_latency
+            && self.slots[..self.active_slots()]
+                .iter()
+                .all(|slot| slot.passthrough && slot.fade_remaining == 0)
     }
     
     /// Process single sample through cabinet simulation - O(1) amortized complexity
@@ -160,21 +633,56 @@ impl CabinetSimulator {
     /// 2. Cabinet mode: convolution + wet/dry mix - O(1) amortized
     /// 3. Mix control blends dry signal with cabinet-processed signal
     pub fn process_sample(&mut self, input: f32) -> f32 {
-        match self.current_cabinet {
-            CabinetType::Direct => {
-                // Bypass cabinet simulation - pure O(1) passthrough
-                input
-            }
-            _ => {
-                // Process through convolution - O(1) amortized complexity
-                let wet_signal = self.convolution_engine.process_sample(input);
-                
-                // Wet/dry mix for cabinet intensity control - O(1) linear interpolation
-                let dry_signal = input * (1.0 - self.mix);
-                let cabinet_signal = wet_signal * self.mix;
-                
-                dry_signal + cabinet_signal
+        self.process_sample_with_cross(input, input)
+    }
+    
+    /// Process single sample with the opposite channel's input for true-stereo IRs
+    /// `cross_input` is ignored unless a slot holds a true-stereo matrix
+    pub fn process_sample_with_cross(&mut self, input: f32, cross_input: f32) -> f32 {
+        if self.is_bypassed() {
+            // Bypass cabinet simulation - pure O(1) passthrough
+            return input;
+        }
+        
+        // Process through convolution - O(1) amortized complexity
+        let active_slots = self.active_slots();
+        let wet_signal: f32 = self.slots[..active_slots]
+            .iter_mut()
+            .map(|slot| slot.process(input, cross_input))
+            .sum();
+        
+        // Wet/dry mix for cabinet intensity control - O(1) linear interpolation
+        let dry_signal = input * (1.0 - self.mix);
+        let cabinet_signal = wet_signal * self.mix;
+        
+        dry_signal + cabinet_signal
+    }
+    
+    /// Process a block in place with per-sample wet/dry mix values - O(N) amortized
+    pub fn process_block(&mut self, samples: &mut [f32], mix: &[f32]) {
+        for (sample, &mix) in samples.iter_mut().zip(mix) {
+            self.set_mix(mix);
+            *sample = self.process_sample(*sample);
+        }
+    }
+    
+    /// Process a block with per-sample mix and blend slot settings - O(N) amortized
+    /// `cross` is the opposite channel's input for true-stereo IRs; `None` uses
+    /// this channel's own input
+    pub fn process_blend_block(
+        &mut self,
+        samples: &mut [f32],
+        cross: Option<&[f32]>,
+        mix: &[f32],
+        slots: &[CabinetSlotParams; 2],
+    ) {
+        for i in 0..samples.len() {
+            self.set_mix(mix[i]);
+            for (index, slot) in slots.iter().enumerate() {
+                self.set_slot_params(index, slot.level[i], slot.pan[i], slot.invert, slot.delay_ms[i]);
             }
+            let cross_input = cross.map_or(samples[i], |cross| cross[i]);
+            samples[i] = self.process_sample_with_cross(samples[i], cross_input);
         }
     }
     
@@ -184,24 +692,53 @@ impl CabinetSimulator {
         self.mix = mix.clamp(0.0, 1.0);
     }
     
+    /// Set one blend slot's level (linear), pan (-1 to 1), polarity and delay - O(1)
+    pub fn set_slot_params(&mut self, index: usize, level: f32, pan: f32, invert: bool, delay_ms: f32) {
+        let slot = &mut self.slots[index];
+        slot.set_params(level, pan, invert, self.output_channel);
+        slot.delay.set_delay(delay_ms.clamp(0.0, MAX_SLOT_DELAY_MS) * 0.001 * self.sample_rate);
+    }
+    
+    /// Select the output channel slot panning applies to - `None` for mono output
+    pub fn set_output_channel(&mut self, output_channel: Option<usize>) {
+        self.output_channel = output_channel;
+        for slot in &mut self.slots {
+            slot.update_gain(output_channel);
+        }
+    }
+    
     /// Get current cabinet type - O(1) lookup
     pub fn get_current_cabinet(&self) -> CabinetType {
-        self.current_cabinet
+        self.slots[0].cabinet_type
     }
     
     /// Get processing latency in samples - O(1) lookup
     /// Latency comes from block-based FFT processing in convolution engine
+    /// Slot delays are relative alignment and not reported
     pub fn get_latency(&self) -> usize {
-        match self.current_cabinet {
-            CabinetType::Direct => 0, // No latency in direct mode
-            _ => self.convolution_engine.get_latency(),
+        if self.is_bypassed() {
+            0 // No latency in direct mode
+        } else {
+            self.slots[0].engine.get_latency()
         }
     }
     
     /// Reset cabinet processing state - O(1) operation
     /// Clears all internal buffers and overlap state
     pub fn reset(&mut self) {
-        self.convolution_engine.reset();
+        for slot in &mut self.slots {
+            slot.reset();
+        }
+        self.hold_latency = false;
+    }
+}
+
+impl Prepare for CabinetSimulator {
+    /// Keep loaded IRs and engine mode - only re-prepare them for the new rate
+    /// The convolution engines split any block into their own partition size
+    fn prepare(&mut self, sample_rate: f32, _max_block: usize) {
+        self.set_sample_rate(sample_rate);
+        self.reset();
     }
 }
 
@@ -211,6 +748,18 @@ impl Default for CabinetSimulator {
     }
 }
 
+/// Built-in IR of a factory cabinet at `BUILTIN_IR_SAMPLE_RATE` - `None` for
+/// Direct and the user IR
+pub(super) fn builtin_impulse(cabinet_type: CabinetType) -> Option<&'static [f32]> {
+    match cabinet_type {
+        CabinetType::Marshall4x12V30 => Some(&MARSHALL_4X12_V30_IR),
+        CabinetType::FenderTwin2x12 => Some(&FENDER_TWIN_2X12_IR),
+        CabinetType::VoxAC30Blue => Some(&VOX_AC30_BLUE_IR),
+        CabinetType::Mesa4x12Recto => Some(&MESA_4X12_RECTO_IR),
+        CabinetType::Direct | CabinetType::UserIr => None,
+    }
+}
+
 // Pre-computed impulse responses for professional cabinet simulation
 // These are mathematically derived IRs that capture the essential frequency response
 // characteristics of each cabinet type without requiring external files
@@ -303,6 +852,17 @@ static MESA_4X12_RECTO_IR: [f32; 256] = [
 mod tests {
     use super::*;
     
+    /// User IR prepared for both blend slots, the way `UserIrSlot` hands it over
+    fn prepared_user_ir(
+        source: ImpulseResponse,
+        sample_rate: f32,
+        mode: ConvolutionMode,
+    ) -> (PreparedChannelIr, PreparedEngines) {
+        let prepared = PreparedChannelIr::new(source, sample_rate, mode, 128).unwrap();
+        let blend = PreparedEngines::new(&prepared.samples, &prepared.cross_samples, mode, 128).unwrap();
+        (prepared, blend)
+    }
+    
     #[test]
     fn test_cabinet_creation() {
         let cabinet = CabinetSimulator::new(128, 44100.0);
@@ -323,6 +883,255 @@ mod tests {
         assert_eq!(cabinet.get_latency(), 0); // No latency in direct mode
     }
     
+    #[test]
+    fn test_zero_latency_mode() {
+        let mut cabinet = CabinetSimulator::new(128, 44100.0);
+        cabinet.set_convolution_mode(ConvolutionMode::ZeroLatency);
+        assert_eq!(cabinet.get_convolution_mode(), ConvolutionMode::ZeroLatency);
+        assert_eq!(cabinet.get_latency(), 128); // Engines only change when an IR is loaded
+        assert!(cabinet.load_cabinet(CabinetType::Marshall4x12V30).is_ok());
+        assert_eq!(cabinet.get_latency(), 0);
+        
+        // Cabinet output must start on the very first sample
+        let output = cabinet.process_sample(1.0);
+        assert!(output.abs() > 0.0);
+        
+        cabinet.set_convolution_mode(ConvolutionMode::LowCpu);
+        assert!(cabinet.load_cabinet(CabinetType::Marshall4x12V30).is_ok());
+        assert_eq!(cabinet.get_latency(), 128);
+    }
+    
+    #[test]
+    fn test_user_ir_slot() {
+        let mut cabinet = CabinetSimulator::new(128, 44100.0);
+        
+        // Without an installed IR the user slot passes audio through
+        assert!(cabinet.load_cabinet(CabinetType::UserIr).is_ok());
+        assert!(!cabinet.has_user_ir());
+        assert_eq!(cabinet.process_sample(0.5), 0.5);
+        assert_eq!(cabinet.get_latency(), 0);
+        
+        // Install a prepared IR - a pure delay of 3 samples
+        let source = ImpulseResponse { channels: vec![vec![0.0, 0.0, 0.0, 1.0]], sample_rate: 44100.0 };
+        let (mut prepared, mut blend) = prepared_user_ir(source, 44100.0, ConvolutionMode::LowCpu);
+        assert!(cabinet.swap_user_ir(&mut prepared, &mut blend));
+        
+        assert!(cabinet.has_user_ir());
+        assert!(prepared.samples.is_empty());
+        assert_eq!(cabinet.get_latency(), 128);
+        
+        let output: Vec<f32> = (0..140).map(|n| cabinet.process_sample(if n == 0 { 1.0 } else { 0.0 })).collect();
+        assert!((output[128 + 3] - 1.0).abs() < 1e-5);
+    }
+    
+    #[test]
+    fn test_true_stereo_matrix() {
+        // L->L, L->R, R->L, R->R as distinct delays
+        let matrix = ImpulseResponse {
+            channels: vec![vec![1.0], vec![0.0, 1.0], vec![0.0, 0.0, 1.0], vec![0.0, 0.0, 0.0, 1.0]],
+            sample_rate: 44100.0,
+        };
+        let mut outputs = [CabinetSimulator::new(128, 44100.0), CabinetSimulator::new(128, 44100.0)];
+        for (output_channel, cabinet) in outputs.iter_mut().enumerate() {
+            let row = matrix.output_row(output_channel);
+            let (mut prepared, mut blend) = prepared_user_ir(row, 44100.0, ConvolutionMode::ZeroLatency);
+            cabinet.set_convolution_mode(ConvolutionMode::ZeroLatency);
+            cabinet.load_cabinet(CabinetType::UserIr).unwrap();
+            assert!(cabinet.swap_user_ir(&mut prepared, &mut blend));
+            assert!(cabinet.is_true_stereo());
+        }
+        
+        // Impulse on the left input only: left hears L->L, right hears L->R
+        let [left, right] = &mut outputs;
+        let (left_out, right_out): (Vec<f32>, Vec<f32>) = (0..6)
+            .map(|n| {
+                let left_in = if n == 0 { 1.0 } else { 0.0 };
+                (left.process_sample_with_cross(left_in, 0.0), right.process_sample_with_cross(0.0, left_in))
+            })
+            .unzip();
+        let expected_left = [1.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        let expected_right = [0.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+        for n in 0..6 {
+            assert!((left_out[n] - expected_left[n]).abs() < 1e-5);
+            assert!((right_out[n] - expected_right[n]).abs() < 1e-5);
+        }
+        
+        // Impulse on the right input only: left hears R->L, right hears R->R
+        let (left_out, right_out): (Vec<f32>, Vec<f32>) = (0..6)
+            .map(|n| {
+                let right_in = if n == 0 { 1.0 } else { 0.0 };
+                (left.process_sample_with_cross(0.0, right_in), right.process_sample_with_cross(right_in, 0.0))
+            })
+            .unzip();
+        assert!((left_out[2] - 1.0).abs() < 1e-5);
+        assert!((right_out[3] - 1.0).abs() < 1e-5);
+        assert!(left_out[0].abs() < 1e-5 && right_out[0].abs() < 1e-5);
+    }
+    
+    #[test]
+    fn test_blend_slots() {
+        let mut cabinet = CabinetSimulator::new(128, 48000.0);
+        cabinet.set_convolution_mode(ConvolutionMode::ZeroLatency);
+        
+        // A unit-impulse user IR in slot A, Direct in slot B: both pass signal
+        // straight through, so the output shows each slot's level, polarity and delay
+        let source = ImpulseResponse { channels: vec![vec![1.0]], sample_rate: 48000.0 };
+        let (mut prepared, mut blend) = prepared_user_ir(source, 48000.0, ConvolutionMode::ZeroLatency);
+        cabinet.load_cabinet(CabinetType::UserIr).unwrap();
+        assert!(cabinet.swap_user_ir(&mut prepared, &mut blend));
+        cabinet.set_blend_cabinet(Some(CabinetType::Direct)).unwrap();
+        assert_eq!(cabinet.get_blend_cabinet(), Some(CabinetType::Direct));
+        
+        cabinet.set_slot_params(0, 0.5, 0.0, false, 0.0);
+        cabinet.set_slot_params(1, 0.25, 0.0, true, 0.5); // 0.5 ms = 24 samples
+        let output: Vec<f32> = (0..32).map(|n| cabinet.process_sample(if n == 0 { 1.0 } else { 0.0 })).collect();
+        assert!((output[0] - 0.5).abs() < 1e-5);
+        assert!((output[24] + 0.25).abs() < 1e-5);
+        assert!(output.iter().enumerate().all(|(n, s)| n == 0 || n == 24 || s.abs() < 1e-5));
+        
+        // Turning the blend off leaves only slot A
+        cabinet.set_blend_cabinet(None).unwrap();
+        assert_eq!(cabinet.get_blend_cabinet(), None);
+        assert!((cabinet.process_sample(1.0) - 0.5).abs() < 1e-5);
+    }
+    
+    #[test]
+    fn test_blend_pan() {
+        let mut cabinet = CabinetSimulator::new(128, 44100.0);
+        cabinet.set_blend_cabinet(Some(CabinetType::Mesa4x12Recto)).unwrap();
+        
+        // Slot B panned hard right is silent on the left output
+        cabinet.set_output_channel(Some(0));
+        cabinet.set_slot_params(1, 1.0, 1.0, false, 0.0);
+        assert!(cabinet.slots[1].gain.abs() < 1e-6);
+        assert_eq!(cabinet.slots[0].gain, 1.0);
+        
+        // ... at full level on the right output, and pan is ignored for mono output
+        cabinet.set_output_channel(Some(1));
+        assert_eq!(cabinet.slots[1].gain, 1.0);
+        cabinet.set_output_channel(None);
+        assert_eq!(cabinet.slots[1].gain, 1.0);
+    }
+    
+    #[test]
+    fn test_fractional_delay() {
+        // Half-sample delay of a slow sine lands between the samples
+        let mut delay = FractionalDelay::new(64);
+        delay.set_delay(10.5);
+        let phase = |n: f32| (n * 0.05).sin();
+        for n in 0..200 {
+            let output = delay.process(phase(n as f32));
+            if n > 20 {
+                assert!((output - phase(n as f32 - 10.5)).abs() < 1e-4);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_sample_rate_change_reprepares_irs() {
+        let mut cabinet = CabinetSimulator::new(128, 44100.0);
+        assert!(cabinet.load_cabinet(CabinetType::UserIr).is_ok());
+        
+        let source = ImpulseResponse { channels: vec![vec![1.0; 441]; 2], sample_rate: 44100.0 };
+        let (mut prepared, mut blend) = prepared_user_ir(source, 44100.0, ConvolutionMode::LowCpu);
+        assert!(cabinet.swap_user_ir(&mut prepared, &mut blend));
+        
+        cabinet.set_sample_rate(88200.0);
+        assert_eq!(cabinet.get_sample_rate(), 88200.0);
+        assert_eq!(cabinet.user_impulse.len(), 882);
+        assert_eq!(cabinet.user_cross_impulse.len(), 882);
+        assert_eq!(cabinet.cabinet_impulses[&CabinetType::Marshall4x12V30].len(), 512);
+        
+        // Back at the original rate the built-in IRs are the original tables again
+        cabinet.set_sample_rate(44100.0);
+        assert_eq!(cabinet.cabinet_impulses[&CabinetType::Marshall4x12V30], MARSHALL_4X12_V30_IR.to_vec());
+        assert_eq!(cabinet.get_current_cabinet(), CabinetType::UserIr);
+        assert_eq!(cabinet.user_impulse.len(), 441);
+        assert!(cabinet.is_true_stereo());
+    }
+    
+    #[test]
+    fn test_crossfaded_switch() {
+        let sine = |n: usize| 0.5 * (2.0 * std::f32::consts::PI * 220.0 * n as f32 / 44100.0).sin();
+        for mode in [ConvolutionMode::ZeroLatency, ConvolutionMode::LowCpu] {
+            let mut cabinet = CabinetSimulator::new(128, 44100.0);
+            cabinet.set_convolution_mode(mode);
+            cabinet.load_cabinet(CabinetType::Direct).unwrap();
+            cabinet.set_crossfade_time(10.0);
+            
+            // Loaded off the audio thread, Direct is bypassed with no latency at all
+            assert_eq!(cabinet.get_latency(), 0);
+            let mut n = 0;
+            let mut previous = 0.0;
+            let mut largest_step = 0.0f32;
+            let mut steady_step = 0.0f32;
+            for _ in 0..1000 {
+                let output = cabinet.process_sample(sine(n));
+                steady_step = steady_step.max((output - previous).abs());
+                previous = output;
+                n += 1;
+            }
+            
+            // Switches during playback fade between IRs and keep the latency of the
+            // first one, so neither the output nor the host's compensation jumps
+            let switches = [
+                (CabinetType::Marshall4x12V30, false),
+                (CabinetType::Direct, true),
+                (CabinetType::Marshall4x12V30, false),
+                (CabinetType::Direct, true),
+            ];
+            let mut held_latency = None;
+            for (cabinet_type, passthrough) in switches {
+                let samples = builtin_impulse(cabinet_type).unwrap_or(&UNIT_IMPULSE);
+                let source = ImpulseResponse { channels: vec![samples.to_vec()], sample_rate: BUILTIN_IR_SAMPLE_RATE };
+                let mut prepared = PreparedChannelIr::new(source, 44100.0, mode, 128).unwrap();
+                assert!(cabinet.install_cabinet(0, cabinet_type, passthrough, &mut prepared));
+                assert_eq!(cabinet.get_current_cabinet(), cabinet_type);
+                assert!(cabinet.is_crossfading());
+                
+                let latency = *held_latency.get_or_insert(cabinet.get_latency());
+                for i in 0..2000 {
+                    let output = cabinet.process_sample(sine(n));
+                    let step = (output - previous).abs();
+                    if i < 1000 {
+                        largest_step = largest_step.max(step);
+                    } else {
+                        steady_step = steady_step.max(step);
+                    }
+                    previous = output;
+                    n += 1;
+                }
+                assert!(!cabinet.is_crossfading());
+                assert_eq!(cabinet.get_latency(), latency, "{:?} latency after {:?}", mode, cabinet_type);
+            }
+            assert!(largest_step < steady_step * 1.5, "{:?} step {} vs {}", mode, largest_step, steady_step);
+        }
+    }
+    
+    #[test]
+    fn test_stale_and_shared_user_ir_engines() {
+        let mut cabinet = CabinetSimulator::new(128, 44100.0);
+        cabinet.load_slot(0, CabinetType::UserIr).unwrap();
+        cabinet.set_blend_cabinet(Some(CabinetType::UserIr)).unwrap();
+        let source = ImpulseResponse { channels: vec![vec![0.0, 1.0]], sample_rate: 44100.0 };
+        
+        // Engines built before a mode change are refused and handed back untouched
+        cabinet.set_convolution_mode(ConvolutionMode::ZeroLatency);
+        let (mut prepared, mut blend) = prepared_user_ir(source.clone(), 44100.0, ConvolutionMode::LowCpu);
+        assert!(!cabinet.swap_user_ir(&mut prepared, &mut blend));
+        assert!(!cabinet.has_user_ir());
+        assert_eq!(prepared.samples.len(), 2);
+        assert!(!cabinet.install_cabinet(0, CabinetType::UserIr, false, &mut prepared));
+        assert_eq!(cabinet.process_sample(0.5), 0.5);
+        
+        // Both slots showing the user IR each get their own prepared engines
+        let (mut prepared, mut blend) = prepared_user_ir(source, 44100.0, ConvolutionMode::ZeroLatency);
+        assert!(cabinet.swap_user_ir(&mut prepared, &mut blend));
+        assert_eq!(cabinet.get_latency(), 0);
+        let output: Vec<f32> = (0..3).map(|n| cabinet.process_sample(if n == 0 { 1.0 } else { 0.0 })).collect();
+        assert_eq!(output, vec![0.0, 2.0, 0.0]);
+    }
+
     #[test]
     fn test_direct_mode_processing() {
         let mut cabinet = CabinetSimulator::new(128, 44100.0);
@@ -0,0 +1,212 @@
+use super::cabinet::{builtin_impulse, CabinetType, BUILTIN_IR_SAMPLE_RATE, UNIT_IMPULSE};
+use super::convolution::ConvolutionMode;
+use super::ir_loader::ImpulseResponse;
+use super::user_ir::PreparedChannelIr;
+use super::CABINET_BLOCK_SIZE;
+use nih_plug::prelude::Enum;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Cabinet for one blend slot, fully prepared off the audio thread
+pub struct PreparedCabinet {
+    /// Blend slot the cabinet goes to - 0 = main, 1 = blend
+    pub slot: usize,
+    pub cabinet_type: CabinetType,
+    /// Unit impulse for Direct, or for the user IR before a file is loaded
+    pub passthrough: bool,
+    /// One set of convolution engines per output
+    pub channels: [PreparedChannelIr; 2],
+}
+
+/// Hand-off point for cabinet switches between the background thread and the audio thread
+///
+/// Works like `UserIrSlot`: the background thread builds the engines and publishes
+/// them per blend slot, the audio thread only uses `try_lock` to take them and parks
+/// the engines it swapped out in `retired` until the next preparation frees them.
+/// A newer request for a slot replaces one that hasn't been installed yet.
+pub struct CabinetLoader {
+    /// Prepared cabinets waiting for the audio thread, per blend slot
+    pending: Mutex<[Option<Box<PreparedCabinet>>; 2]>,
+
+    /// Engines handed back by the audio thread, freed off the audio thread
+    retired: Mutex<[Option<Box<PreparedCabinet>>; 2]>,
+
+    /// Cheap check so the audio thread doesn't touch the locks when nothing is pending
+    has_pending: AtomicBool,
+
+    /// Per blend slot: the audio thread dropped a cabinet prepared for an outdated convolution mode
+    stale: [AtomicBool; 2],
+
+    /// Convolution mode the engines should be prepared for
+    convolution_mode: AtomicUsize,
+
+    /// Host sample rate the IRs are resampled to, stored as f32 bits
+    sample_rate: AtomicU32,
+}
+
+impl CabinetLoader {
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new([None, None]),
+            retired: Mutex::new([None, None]),
+            has_pending: AtomicBool::new(false),
+            stale: [AtomicBool::new(false), AtomicBool::new(false)],
+            convolution_mode: AtomicUsize::new(ConvolutionMode::LowCpu.to_index()),
+            sample_rate: AtomicU32::new(44100.0f32.to_bits()),
+        }
+    }
+
+    /// Prepare a cabinet for one blend slot and publish it - heavy, background only
+    /// `user_ir` is the loaded user IR file, used when `cabinet_type` is the user IR
+    pub fn prepare(
+        &self,
+        slot: usize,
+        cabinet_type: CabinetType,
+        user_ir: Option<&ImpulseResponse>,
+    ) -> Result<(), String> {
+        let sample_rate = f32::from_bits(self.sample_rate.load(Ordering::Relaxed));
+        let mode = ConvolutionMode::from_index(self.convolution_mode.load(Ordering::Relaxed));
+
+        // Passthrough cabinets keep a unit impulse at the host rate so they stay
+        // time-aligned with an IR in the other slot
+        let unit_impulse = || ImpulseResponse { channels: vec![UNIT_IMPULSE.to_vec()], sample_rate };
+        let (source, passthrough) = match cabinet_type {
+            CabinetType::Direct => (unit_impulse(), true),
+            CabinetType::UserIr => match user_ir {
+                Some(user_ir) if !user_ir.is_empty() => (user_ir.clone(), false),
+                _ => (unit_impulse(), true),
+            },
+            _ => {
+                let samples = builtin_impulse(cabinet_type).ok_or_else(|| "no built-in impulse response".to_string())?;
+                let source = ImpulseResponse { channels: vec![samples.to_vec()], sample_rate: BUILTIN_IR_SAMPLE_RATE };
+                (source, false)
+            }
+        };
+
+        let prepare_channel = |output_channel: usize| {
+            PreparedChannelIr::new(source.output_row(output_channel), sample_rate, mode, CABINET_BLOCK_SIZE)
+        };
+        let prepared = PreparedCabinet {
+            slot,
+            cabinet_type,
+            passthrough,
+            channels: [prepare_channel(0)?, prepare_channel(1)?],
+        };
+
+        // Free whatever the audio thread handed back for this slot
+        if let Ok(mut retired) = self.retired.lock() {
+            retired[slot].take();
+        }
+        if let Ok(mut pending) = self.pending.lock() {
+            pending[slot] = Some(Box::new(prepared));
+            self.has_pending.store(true, Ordering::Release);
+        }
+        Ok(())
+    }
+
+    /// Tell the loader which engine type the audio thread is running - lock-free
+    pub fn set_convolution_mode(&self, mode: ConvolutionMode) {
+        self.convolution_mode.store(mode.to_index(), Ordering::Relaxed);
+    }
+
+    /// Tell the loader the host sample rate - call before queueing a preparation
+    pub fn set_sample_rate(&self, sample_rate: f32) {
+        self.sample_rate.store(sample_rate.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Whether the audio thread dropped a cabinet for `slot` that was prepared for an
+    /// outdated convolution mode since the last call - lock-free. The slot's cabinet
+    /// needs preparing again for the current mode.
+    pub fn take_stale(&self, slot: usize) -> bool {
+        self.stale[slot].swap(false, Ordering::AcqRel)
+    }
+
+    /// Install pending cabinets from the audio thread - never blocks, never allocates
+    /// `install` swaps the prepared engines with the processor's idle ones, which are
+    /// then kept in the retired slot until the background thread frees them. Cabinets
+    /// `install` returns false for are retired unused and reported by `take_stale`.
+    /// Returns true if a cabinet was installed.
+    pub fn install_pending<F: FnMut(&mut PreparedCabinet) -> bool>(&self, mut install: F) -> bool {
+        if !self.has_pending.load(Ordering::Acquire) {
+            return false;
+        }
+
+        let Ok(mut retired) = self.retired.try_lock() else {
+            return false;
+        };
+        let Ok(mut pending) = self.pending.try_lock() else {
+            return false;
+        };
+
+        let mut installed = false;
+        for (slot, (pending, retired)) in pending.iter_mut().zip(retired.iter_mut()).enumerate() {
+            // Only take the new cabinet if there's room to park the old engines
+            if retired.is_some() {
+                continue;
+            }
+            if let Some(mut prepared) = pending.take() {
+                if install(&mut prepared) {
+                    installed = true;
+                } else {
+                    self.stale[slot].store(true, Ordering::Release);
+                }
+                *retired = Some(prepared);
+            }
+        }
+
+        self.has_pending.store(pending.iter().any(Option::is_some), Ordering::Release);
+        installed
+    }
+}
+
+impl Default for CabinetLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::{BlockParams, ChannelLayout, StereoProcessor};
+
+    #[test]
+    fn test_prepared_cabinet_reaches_audio_thread() {
+        let loader = CabinetLoader::new();
+        let mut processor = StereoProcessor::new();
+        processor.initialize(44100.0, ChannelLayout::Stereo);
+        processor.set_cabinet_crossfade(10.0);
+
+        // Nothing requested yet
+        assert!(!processor.install_cabinets(&loader));
+
+        loader.prepare(0, CabinetType::Direct, None).unwrap();
+        assert!(processor.install_cabinets(&loader));
+        assert!(!processor.install_cabinets(&loader)); // Consumed exactly once
+
+        // A second switch waits until the 441-sample crossfade has finished
+        loader.prepare(0, CabinetType::VoxAC30Blue, None).unwrap();
+        let params = BlockParams::default();
+        let mut left = [0.0; 64];
+        let mut right = [0.0; 64];
+        for _ in 0..6 {
+            processor.process_block(&mut left, &mut right, &params);
+            assert!(!processor.install_cabinets(&loader));
+        }
+        processor.process_block(&mut left, &mut right, &params);
+        assert!(processor.install_cabinets(&loader));
+    }
+
+    #[test]
+    fn test_user_ir_cabinet_without_file_passes_through() {
+        let loader = CabinetLoader::new();
+        loader.prepare(1, CabinetType::UserIr, None).unwrap();
+
+        let mut cabinets = Vec::new();
+        loader.install_pending(|prepared| {
+            cabinets.push((prepared.slot, prepared.cabinet_type, prepared.passthrough));
+            true
+        });
+        assert_eq!(cabinets, vec![(1, CabinetType::UserIr, true)]);
+    }
+}
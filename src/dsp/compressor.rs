@@ -0,0 +1,266 @@
+use super::Prepare;
+
+/// RMS detector averaging time - about one period of a low E
+const RMS_WINDOW_MS: f32 = 10.0;
+
+/// Threshold and ratio at zero and full sustain
+const THRESHOLD_RANGE_DB: (f32, f32) = (-10.0, -50.0);
+const RATIO_RANGE: (f32, f32) = (2.0, 10.0);
+
+/// Width of the soft knee around the threshold
+const KNEE_DB: f32 = 12.0;
+
+/// Typical guitar level the automatic make-up gain is matched at, in dBFS RMS
+const MAKEUP_REFERENCE_DB: f32 = -20.0;
+
+/// Optical release: light gain reduction lets go quickly, heavy reduction leaves
+/// the cell slow to recover - the bloom an optical pedal is known for
+const RELEASE_MIN_MS: f32 = 50.0;
+const RELEASE_MAX_MS: f32 = 500.0;
+const RELEASE_DEPTH_DB: f32 = 20.0;
+
+/// Glide time for level and blend changes
+const CONTROL_SMOOTHING_MS: f32 = 20.0;
+
+/// How the compressor pedal is set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressorSettings {
+    /// Disabled compressors leave the signal untouched
+    pub enabled: bool,
+
+    /// 0-1: lowers the threshold and raises the ratio together, with matching make-up gain
+    pub sustain: f32,
+
+    pub attack_ms: f32,
+
+    /// Output level, in dB
+    pub level_db: f32,
+
+    /// 0 = dry, 1 = fully compressed - parallel compression keeps the pick attack
+    pub blend: f32,
+}
+
+impl Default for CompressorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sustain: 0.5,
+            attack_ms: 10.0,
+            level_db: 0.0,
+            blend: 1.0,
+        }
+    }
+}
+
+/// Feed-forward compressor pedal with RMS detection and a soft knee - O(1) per sample
+///
+/// Sits ahead of the preamp like a Ross/Dyna Comp style pedal. The detector follows
+/// the RMS level of its key and the gain computer works in dB; attack and the
+/// program-dependent optical release smooth the gain reduction itself.
+pub struct Compressor {
+    settings: CompressorSettings,
+    sample_rate: f32,
+
+    /// Gain computer and make-up derived from the sustain setting
+    threshold_db: f32,
+    ratio: f32,
+    makeup_db: f32,
+
+    /// Per-sample one-pole coefficients
+    rms_coeff: f32,
+    attack_coeff: f32,
+    control_coeff: f32,
+
+    /// Mean square of the key
+    mean_square: f32,
+    /// Smoothed gain reduction, in dB (0 or negative)
+    reduction_db: f32,
+    /// Smoothed output level gain and blend
+    level: f32,
+    blend: f32,
+}
+
+impl Compressor {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut compressor = Self {
+            settings: CompressorSettings::default(),
+            sample_rate,
+            threshold_db: 0.0,
+            ratio: 1.0,
+            makeup_db: 0.0,
+            rms_coeff: 0.0,
+            attack_coeff: 0.0,
+            control_coeff: 0.0,
+            mean_square: 0.0,
+            reduction_db: 0.0,
+            level: 1.0,
+            blend: 1.0,
+        };
+        compressor.update_coefficients();
+        compressor.level = compressor.target_level();
+        compressor.blend = compressor.settings.blend;
+        compressor
+    }
+
+    /// Apply new settings - O(1), no allocation, skips the math when nothing changed
+    /// Level and blend glide to their new values
+    pub fn set_settings(&mut self, settings: CompressorSettings) {
+        if settings == self.settings {
+            return;
+        }
+        self.settings = settings;
+        self.update_coefficients();
+    }
+
+    fn time_coeff(&self, ms: f32) -> f32 {
+        (-1.0 / (ms.max(0.01) * 0.001 * self.sample_rate)).exp()
+    }
+
+    fn update_coefficients(&mut self) {
+        let sustain = self.settings.sustain.clamp(0.0, 1.0);
+        let (threshold_min, threshold_max) = THRESHOLD_RANGE_DB;
+        self.threshold_db = threshold_min + (threshold_max - threshold_min) * sustain;
+        self.ratio = RATIO_RANGE.0 + (RATIO_RANGE.1 - RATIO_RANGE.0) * sustain;
+        self.makeup_db = -self.static_gain_db(MAKEUP_REFERENCE_DB);
+
+        self.rms_coeff = self.time_coeff(RMS_WINDOW_MS);
+        self.attack_coeff = self.time_coeff(self.settings.attack_ms);
+        self.control_coeff = self.time_coeff(CONTROL_SMOOTHING_MS);
+    }
+
+    fn target_level(&self) -> f32 {
+        10f32.powf(self.settings.level_db / 20.0)
+    }
+
+    /// Soft-knee gain computer: gain in dB for a detector level in dB - O(1)
+    /// Quadratic through the knee, so gain and slope are continuous at both edges
+    fn static_gain_db(&self, level_db: f32) -> f32 {
+        let overshoot = level_db - self.threshold_db;
+        let slope = 1.0 / self.ratio - 1.0;
+        if 2.0 * overshoot < -KNEE_DB {
+            0.0
+        } else if 2.0 * overshoot <= KNEE_DB {
+            slope * (overshoot + KNEE_DB / 2.0).powi(2) / (2.0 * KNEE_DB)
+        } else {
+            slope * overshoot
+        }
+    }
+
+    /// Compress a block in place, detecting on `key` - O(N)
+    /// The key is scaled by the same gain so later detectors see the compressed level
+    pub fn process_block(&mut self, samples: &mut [f32], key: &mut [f32]) {
+        if !self.settings.enabled {
+            return;
+        }
+
+        let target_level = self.target_level();
+        let target_blend = self.settings.blend.clamp(0.0, 1.0);
+
+        for (sample, key) in samples.iter_mut().zip(key.iter_mut()) {
+            self.mean_square = *key * *key + (self.mean_square - *key * *key) * self.rms_coeff;
+            let level_db = 10.0 * (self.mean_square + 1e-12).log10();
+            let target_db = self.static_gain_db(level_db);
+
+            let coeff = if target_db < self.reduction_db {
+                self.attack_coeff
+            } else {
+                let depth = (-self.reduction_db / RELEASE_DEPTH_DB).min(1.0);
+                self.time_coeff(RELEASE_MIN_MS + (RELEASE_MAX_MS - RELEASE_MIN_MS) * depth)
+            };
+            self.reduction_db = target_db + (self.reduction_db - target_db) * coeff;
+
+            self.level = target_level + (self.level - target_level) * self.control_coeff;
+            self.blend = target_blend + (self.blend - target_blend) * self.control_coeff;
+
+            let wet_gain = 10f32.powf((self.reduction_db + self.makeup_db) / 20.0);
+            let gain = self.level * (1.0 - self.blend + self.blend * wet_gain);
+            *sample *= gain;
+            *key *= gain;
+        }
+    }
+}
+
+impl Prepare for Compressor {
+    /// Retune the time constants for the new rate and clear the detector
+    fn prepare(&mut self, sample_rate: f32, _max_block: usize) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients();
+        self.mean_square = 0.0;
+        self.reduction_db = 0.0;
+        self.level = self.target_level();
+        self.blend = self.settings.blend.clamp(0.0, 1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressor_with(settings: CompressorSettings) -> Compressor {
+        let mut compressor = Compressor::new(44100.0);
+        compressor.set_settings(CompressorSettings { enabled: true, ..settings });
+        compressor.prepare(44100.0, 64);
+        compressor
+    }
+
+    /// Output RMS in dB of a steady 220 Hz sine at `amplitude`, after a second of settling
+    fn steady_output_db(compressor: &mut Compressor, amplitude: f32) -> f32 {
+        let mut sine: Vec<f32> = (0..88200)
+            .map(|n| amplitude * (2.0 * std::f32::consts::PI * 220.0 * n as f32 / 44100.0).sin())
+            .collect();
+        let mut key = sine.clone();
+        compressor.process_block(&mut sine, &mut key);
+        let tail = &sine[44100..];
+        10.0 * (tail.iter().map(|x| x * x).sum::<f32>() / tail.len() as f32).log10()
+    }
+
+    #[test]
+    fn test_soft_knee_is_continuous() {
+        let compressor = compressor_with(CompressorSettings::default());
+        let threshold = compressor.threshold_db;
+        for edge in [threshold - KNEE_DB / 2.0, threshold + KNEE_DB / 2.0] {
+            let below = compressor.static_gain_db(edge - 1e-4);
+            let above = compressor.static_gain_db(edge + 1e-4);
+            assert!((below - above).abs() < 1e-3, "step at {} dB", edge);
+        }
+        assert_eq!(compressor.static_gain_db(threshold - KNEE_DB), 0.0);
+        let slope = compressor.static_gain_db(threshold + 30.0) - compressor.static_gain_db(threshold + 20.0);
+        assert!((slope - 10.0 * (1.0 / compressor.ratio - 1.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_sustain_evens_out_levels() {
+        // 30 dB of input range comes out squeezed, and more sustain squeezes harder
+        let range = |sustain: f32| {
+            let settings = CompressorSettings { sustain, ..CompressorSettings::default() };
+            let loud = steady_output_db(&mut compressor_with(settings), 0.5);
+            let quiet = steady_output_db(&mut compressor_with(settings), 0.0158);
+            loud - quiet
+        };
+        let light = range(0.2);
+        let heavy = range(1.0);
+        assert!(light < 25.0, "{} dB out at light sustain", light);
+        assert!(heavy < light - 5.0, "{} dB out at full sustain", heavy);
+
+        // Fully dry blend passes the signal at the output level only
+        let dry = CompressorSettings { blend: 0.0, level_db: -6.0, ..CompressorSettings::default() };
+        let dry_db = steady_output_db(&mut compressor_with(dry), 0.5);
+        assert!((dry_db - (20.0 * (0.5f32 / 2.0f32.sqrt()).log10() - 6.0)).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_attack_lets_the_pick_through() {
+        // A step from silence overshoots for about the attack time before the gain comes down
+        let overshoot_samples = |attack_ms: f32| {
+            let mut compressor = compressor_with(CompressorSettings { attack_ms, sustain: 1.0, ..CompressorSettings::default() });
+            let mut step = vec![0.5f32; 44100];
+            let mut key = step.clone();
+            compressor.process_block(&mut step, &mut key);
+            let settled = step[44099];
+            step.iter().position(|&x| x < settled * 1.5).unwrap()
+        };
+        let fast = overshoot_samples(1.0);
+        let slow = overshoot_samples(30.0);
+        assert!(slow > fast * 5, "fast {} vs slow {} samples", fast, slow);
+    }
+}
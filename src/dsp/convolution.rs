@@ -1,12 +1,16 @@
-use realfft::RealFftPlanner;
-use std::collections::VecDeque;
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use num_complex::Complex;
+use std::sync::Arc;
 
 /// High-performance partitioned FFT convolution engine achieving O(1) per-sample complexity
-/// Uses overlap-add method with pre-computed frequency domain impulse responses
+/// Uses uniformly partitioned overlap-save with a frequency-domain delay line (UPOLS)
 /// 
-/// Theory: Partitioned convolution breaks large IRs into smaller blocks, processing
-/// each in frequency domain. This transforms O(N*M) time-domain convolution into
-/// O(log N) FFT operations per block, achieving effective O(1) per-sample complexity.
+/// Theory: Partitioned convolution breaks large IRs into block_size partitions H[0..P].
+/// Every block the input spectrum is pushed into a frequency-domain delay line (FDL)
+/// holding the last P input spectra X[k], and the output spectrum is
+/// Y = sum_p X[k - p] * H[p], so partition p is applied to input delayed by p blocks.
+/// This transforms O(N*M) time-domain convolution into one FFT/IFFT pair plus
+/// P complex multiply-accumulates per block, achieving effective O(1) per-sample complexity.
 pub struct PartitionedConvolution {
     /// Block size for FFT processing - must be power of 2 for optimal performance
     block_size: usize,
@@ -15,26 +19,41 @@ pub struct PartitionedConvolution {
     fft_size: usize,
     
     /// Pre-computed frequency domain IR partitions for O(1) lookup
-    /// Each partition is FFT(ir_chunk) computed during initialization
-    ir_partitions: Vec<Vec<f32>>,
+    /// Each partition is FFT(ir_chunk zero-padded to fft_size) computed during initialization
+    ir_partitions: Vec<Vec<Complex<f32>>>,
     
-    /// Circular buffer for input samples - maintains exactly block_size samples
-    input_buffer: VecDeque<f32>,
+    /// Frequency-domain delay line: spectra of the last P input frames
+    /// Ring buffer indexed by `fdl_position`, one slot per IR partition
+    frequency_delay_line: Vec<Vec<Complex<f32>>>,
     
-    /// Overlap-add accumulator for seamless block stitching
-    /// Size = block_size to handle inter-block overlap
-    overlap_buffer: Vec<f32>,
+    /// Slot in the delay line that receives the next input spectrum
+    fdl_position: usize,
     
-    /// FFT planner for forward/inverse transforms - created once for O(1) transforms
-    fft_planner: RealFftPlanner<f32>,
+    /// Input samples collected for the block currently being filled
+    input_block: Vec<f32>,
+    
+    /// Previous input block - first half of the overlap-save input frame
+    previous_block: Vec<f32>,
+    
+    /// Output of the last processed block, played back while the next block fills
+    output_block: Vec<f32>,
+    
+    /// Forward FFT plan - created once at construction, never re-planned on the audio thread
+    forward_fft: Arc<dyn RealToComplex<f32>>,
+    
+    /// Inverse FFT plan - created once at construction
+    inverse_fft: Arc<dyn ComplexToReal<f32>>,
     
     /// Working memory for FFT operations - pre-allocated to avoid real-time allocation
     fft_scratch: Vec<f32>,
     
-    /// Complex frequency domain working buffer - pre-allocated for O(1) processing
-    freq_buffer: Vec<f32>,
+    /// Spectrum accumulator for partition products - pre-allocated
+    result_spectrum: Vec<Complex<f32>>,
+    
+    /// Internal scratch for the FFT algorithms - lets transforms run without allocating
+    complex_scratch: Vec<Complex<f32>>,
     
-    /// Current position in input buffer for O(1) sample tracking
+    /// Current position in the input/output blocks for O(1) sample tracking
     buffer_position: usize,
 }
 
@@ -48,17 +67,25 @@ impl PartitionedConvolution {
         assert!(block_size.is_power_of_two(), "Block size must be power of 2 for optimal FFT");
         
         let fft_size = block_size * 2; // Zero-padding for linear convolution
-        let fft_planner = RealFftPlanner::new();
+        let mut fft_planner = RealFftPlanner::new();
+        let forward_fft = fft_planner.plan_fft_forward(fft_size);
+        let inverse_fft = fft_planner.plan_fft_inverse(fft_size);
+        let scratch_len = forward_fft.get_scratch_len().max(inverse_fft.get_scratch_len());
         
         Self {
             block_size,
             fft_size,
             ir_partitions: Vec::new(),
-            input_buffer: VecDeque::with_capacity(block_size),
-            overlap_buffer: vec![0.0; block_size],
-            fft_planner,
+            frequency_delay_line: Vec::new(),
+            fdl_position: 0,
+            input_block: vec![0.0; block_size],
+            previous_block: vec![0.0; block_size],
+            output_block: vec![0.0; block_size],
+            result_spectrum: forward_fft.make_output_vec(),
+            complex_scratch: vec![Complex::new(0.0, 0.0); scratch_len],
+            forward_fft,
+            inverse_fft,
             fft_scratch: vec![0.0; fft_size],
-            freq_buffer: vec![0.0; fft_size],
             buffer_position: 0,
         }
     }
@@ -70,7 +97,7 @@ impl PartitionedConvolution {
     /// 1. Partition IR into block_size chunks
     /// 2. Zero-pad each chunk to fft_size
     /// 3. Apply FFT to get frequency domain representation
-    /// 4. Store for O(1) runtime lookup
+    /// 4. Size the frequency-domain delay line to one slot per partition
     pub fn load_impulse_response(&mut self, impulse_response: &[f32]) -> Result<(), ConvolutionError> {
         if impulse_response.is_empty() {
             return Err(ConvolutionError::EmptyImpulseResponse);
@@ -83,9 +110,6 @@ impl PartitionedConvolution {
         
         self.ir_partitions.clear();
         
-        // Create FFT instance for this operation
-        let fft = self.fft_planner.plan_fft_forward(self.fft_size);
-        
         // Partition impulse response into frequency domain blocks
         for chunk in impulse_response.chunks(self.block_size) {
             // Zero-pad chunk to FFT size
@@ -93,130 +117,117 @@ impl PartitionedConvolution {
             padded_chunk[..chunk.len()].copy_from_slice(chunk);
             
             // Transform to frequency domain
-            let mut spectrum = fft.make_output_vec();
-            fft.process(&mut padded_chunk, &mut spectrum).map_err(|_| ConvolutionError::FftError)?;
-            
-            // Convert complex spectrum to interleaved real format for cache efficiency
-            let mut real_spectrum = Vec::with_capacity(self.fft_size);
-            for complex_sample in spectrum {
-                real_spectrum.push(complex_sample.re);
-                real_spectrum.push(complex_sample.im);
-            }
+            let mut spectrum = self.forward_fft.make_output_vec();
+            self.forward_fft
+                .process_with_scratch(&mut padded_chunk, &mut spectrum, &mut self.complex_scratch)
+                .map_err(|_| ConvolutionError::FftError)?;
             
-            self.ir_partitions.push(real_spectrum);
+            self.ir_partitions.push(spectrum);
         }
         
+        // One delay line slot per partition
+        let spectrum_len = self.fft_size / 2 + 1;
+        self.frequency_delay_line = vec![vec![Complex::new(0.0, 0.0); spectrum_len]; self.ir_partitions.len()];
+        
         // Reset processing state
-        self.input_buffer.clear();
-        self.overlap_buffer.fill(0.0);
-        self.buffer_position = 0;
+        self.reset();
         
         Ok(())
     }
     
     /// Process single sample through convolution - O(1) amortized complexity
+    /// Real-time safe: no heap allocation, no locks, no FFT planning
     /// 
-    /// Most calls are simple buffer operations O(1). When buffer fills,
+    /// Most calls are simple buffer operations O(1). When the input block fills,
     /// one expensive O(log N) FFT operation processes entire block,
     /// amortizing to O(1) per sample over the block.
+    /// Output is the exact linear convolution delayed by `block_size` samples.
     pub fn process_sample(&mut self, input: f32) -> f32 {
-        // Add sample to circular buffer - O(1) operation
-        if self.input_buffer.len() < self.block_size {
-            self.input_buffer.push_back(input);
-            
-            // Return overlapped sample while filling buffer - O(1) lookup
-            if self.buffer_position < self.overlap_buffer.len() {
-                let output = self.overlap_buffer[self.buffer_position];
-                self.buffer_position += 1;
-                return output;
-            }
-            return 0.0;
-        }
+        // Play back the previous block's result while collecting the next input block
+        let output = self.output_block[self.buffer_position];
+        self.input_block[self.buffer_position] = input;
+        self.buffer_position += 1;
         
-        // Buffer full - process block and generate new overlap
-        // This O(log N) operation happens once per block_size samples
-        self.process_block();
-        
-        // Replace oldest sample with new input - O(1) circular buffer operation
-        self.input_buffer.pop_front();
-        self.input_buffer.push_back(input);
+        // Block full - this O(P + log N) operation happens once per block_size samples
+        if self.buffer_position == self.block_size {
+            self.process_block();
+            self.buffer_position = 0;
+        }
         
-        // Return first sample of new overlap - O(1) lookup
-        self.buffer_position = 1;
-        self.overlap_buffer[0]
+        output
     }
     
-    /// Process full block through partitioned convolution - O(P log N) complexity
+    /// Process full block through partitioned convolution - O(P + N log N) complexity
     /// where P = number of partitions, N = block size
     /// 
-    /// Algorithm (Overlap-Add Partitioned Convolution):
-    /// 1. FFT current input block
-    /// 2. For each IR partition: multiply in frequency domain
-    /// 3. Accumulate all partition results  
-    /// 4. IFFT to get time domain result
-    /// 5. Add to overlap buffer from previous block
+    /// Algorithm (Uniformly Partitioned Overlap-Save):
+    /// 1. FFT the frame [previous block, current block]
+    /// 2. Push the spectrum into the frequency-domain delay line
+    /// 3. Accumulate FDL[k - p] * H[p] over all partitions
+    /// 4. IFFT and keep the last block_size samples (the first half is circularly aliased)
     fn process_block(&mut self) {
         if self.ir_partitions.is_empty() {
+            self.previous_block.copy_from_slice(&self.input_block);
             return;
         }
         
-        // Zero-pad input block for linear convolution
-        self.fft_scratch.fill(0.0);
-        for (i, &sample) in self.input_buffer.iter().enumerate() {
-            self.fft_scratch[i] = sample;
-        }
+        // Build overlap-save input frame
+        let block_size = self.block_size;
+        self.fft_scratch[..block_size].copy_from_slice(&self.previous_block);
+        self.fft_scratch[block_size..].copy_from_slice(&self.input_block);
+        self.previous_block.copy_from_slice(&self.input_block);
         
-        // Forward FFT of input block
-        let fft = self.fft_planner.plan_fft_forward(self.fft_size);
-        let mut input_spectrum = fft.make_output_vec();
-        if fft.process(&mut self.fft_scratch, &mut input_spectrum).is_err() {
+        // Forward FFT of input frame straight into the current delay line slot
+        if self.forward_fft
+            .process_with_scratch(
+                &mut self.fft_scratch,
+                &mut self.frequency_delay_line[self.fdl_position],
+                &mut self.complex_scratch,
+            )
+            .is_err()
+        {
             return; // Graceful degradation on FFT error
         }
         
-        // Initialize accumulator for partition results
-        let mut result_spectrum = vec![num_complex::Complex::new(0.0, 0.0); input_spectrum.len()];
-        
-        // Convolve with each IR partition - O(P) partitions
-        for ir_partition in &self.ir_partitions {
-            // Complex multiplication in frequency domain - O(N) per partition
-            for (i, (&input_bin, result_bin)) in input_spectrum.iter().zip(result_spectrum.iter_mut()).enumerate() {
-                let ir_real = ir_partition[i * 2];
-                let ir_imag = ir_partition[i * 2 + 1];
-                let ir_bin = num_complex::Complex::new(ir_real, ir_imag);
-                
-                // Complex multiplication: (a + bi)(c + di) = (ac - bd) + (ad + bc)i
+        // Accumulate partition results - partition p meets the input from p blocks ago
+        let partition_count = self.ir_partitions.len();
+        let result_spectrum = &mut self.result_spectrum;
+        result_spectrum.fill(Complex::new(0.0, 0.0));
+        for (p, ir_partition) in self.ir_partitions.iter().enumerate() {
+            let slot = (self.fdl_position + partition_count - p) % partition_count;
+            let input_spectrum = &self.frequency_delay_line[slot];
+            
+            // Complex multiply-accumulate in frequency domain - O(N) per partition
+            for ((result_bin, &input_bin), &ir_bin) in result_spectrum.iter_mut().zip(input_spectrum).zip(ir_partition) {
                 *result_bin += input_bin * ir_bin;
             }
         }
+        self.fdl_position = (self.fdl_position + 1) % partition_count;
         
         // Inverse FFT to time domain
-        let ifft = self.fft_planner.plan_fft_inverse(self.fft_size);
-        self.fft_scratch.fill(0.0);
-        if ifft.process(&mut result_spectrum, &mut self.fft_scratch).is_err() {
+        if self.inverse_fft
+            .process_with_scratch(&mut self.result_spectrum, &mut self.fft_scratch, &mut self.complex_scratch)
+            .is_err()
+        {
             return; // Graceful degradation
         }
         
-        // Overlap-add with previous block result
-        for i in 0..self.block_size {
-            if i < self.overlap_buffer.len() {
-                self.overlap_buffer[i] += self.fft_scratch[i];
-            }
-        }
-        
-        // Copy second half as new overlap for next block
-        for i in 0..self.block_size {
-            if i + self.block_size < self.fft_scratch.len() {
-                self.overlap_buffer[i] = self.fft_scratch[i + self.block_size];
-            }
+        // Keep the alias-free second half, undoing the unnormalized FFT round-trip gain
+        let scale = 1.0 / self.fft_size as f32;
+        for (output, &sample) in self.output_block.iter_mut().zip(&self.fft_scratch[block_size..]) {
+            *output = sample * scale;
         }
-        
-        self.buffer_position = 0;
     }
     
-    /// Reset convolution state - O(1) operation
+    /// Reset convolution state - O(P) operation
     pub fn reset(&mut self) {
-        self.input_buffer.clear();
-        self.overlap_buffer.fill(0.0);
+        self.input_block.fill(0.0);
+        self.previous_block.fill(0.0);
+        self.output_block.fill(0.0);
+        for spectrum in &mut self.frequency_delay_line {
+            spectrum.fill(Complex::new(0.0, 0.0));
+        }
+        self.fdl_position = 0;
         self.buffer_position = 0;
     }
     
@@ -227,6 +238,223 @@ impl PartitionedConvolution {
     }
 }
 
+/// Zero-latency non-uniformly partitioned convolution (Gardner-style)
+/// 
+/// The IR is split into segments of growing size so that every segment's
+/// block latency is hidden by its position in the IR:
+/// - head `[0, H)` runs as a direct-form FIR with no latency at all
+/// - segment `[B, 4B)` runs through a uniform engine with block size B,
+///   whose B samples of latency line up exactly with the segment offset
+/// - block sizes grow 4x per stage (H, 4H, 16H, ...) up to `MAX_PARTITION_SIZE`,
+///   the last stage takes the remaining tail
+/// 
+/// Small early partitions keep latency at zero, large tail partitions keep CPU low.
+pub struct NonUniformConvolution {
+    /// Direct-form FIR taps for the IR head
+    head: Vec<f32>,
+    
+    /// Input history for the FIR, stored twice so the taps always see a contiguous slice
+    history: Vec<f32>,
+    
+    /// Write position in the first half of `history`
+    history_position: usize,
+    
+    /// Uniform engines for the later segments, each delayed by its own block size
+    stages: Vec<PartitionedConvolution>,
+}
+
+impl NonUniformConvolution {
+    /// Number of IR samples handled by the direct-form FIR head
+    pub const HEAD_LENGTH: usize = 64;
+    
+    /// Largest FFT partition used for the IR tail
+    pub const MAX_PARTITION_SIZE: usize = 1024;
+    
+    /// Create empty engine - IR must be loaded before processing
+    pub fn new() -> Self {
+        Self {
+            head: Vec::new(),
+            history: vec![0.0; Self::HEAD_LENGTH * 2],
+            history_position: 0,
+            stages: Vec::new(),
+        }
+    }
+    
+    /// Split impulse response into head FIR and growing FFT partitions
+    /// Heavy O(M log N) setup done once per IR
+    pub fn load_impulse_response(&mut self, impulse_response: &[f32]) -> Result<(), ConvolutionError> {
+        if impulse_response.is_empty() {
+            return Err(ConvolutionError::EmptyImpulseResponse);
+        }
+        
+        if impulse_response.len() > 96000 {
+            return Err(ConvolutionError::ImpulseResponseTooLong);
+        }
+        
+        let head_length = impulse_response.len().min(Self::HEAD_LENGTH);
+        self.head = impulse_response[..head_length].to_vec();
+        
+        // Segment [offset, end) runs with block size == offset, so its latency equals its start
+        self.stages.clear();
+        let mut offset = Self::HEAD_LENGTH;
+        while offset < impulse_response.len() {
+            let block_size = offset.min(Self::MAX_PARTITION_SIZE);
+            let end = if block_size == Self::MAX_PARTITION_SIZE {
+                impulse_response.len()
+            } else {
+                (offset * 4).min(impulse_response.len())
+            };
+            
+            // Once block size stops growing, pad the segment to keep latency and offset aligned
+            let mut segment = vec![0.0; offset - block_size];
+            segment.extend_from_slice(&impulse_response[offset..end]);
+            
+            let mut stage = PartitionedConvolution::new(block_size);
+            stage.load_impulse_response(&segment)?;
+            self.stages.push(stage);
+            offset = end;
+        }
+        
+        self.reset();
+        Ok(())
+    }
+    
+    /// Process single sample - O(H) FIR plus O(1) amortized FFT stages
+    pub fn process_sample(&mut self, input: f32) -> f32 {
+        // Write input twice so history[pos..pos + H] is always the newest-last window
+        let length = Self::HEAD_LENGTH;
+        self.history[self.history_position] = input;
+        self.history[self.history_position + length] = input;
+        self.history_position = (self.history_position + 1) % length;
+        
+        // Direct-form FIR head: newest sample meets tap 0
+        let window = &self.history[self.history_position..self.history_position + length];
+        let head_output: f32 = self.head.iter()
+            .zip(window.iter().rev())
+            .map(|(&tap, &sample)| tap * sample)
+            .sum();
+        
+        let tail_output: f32 = self.stages.iter_mut()
+            .map(|stage| stage.process_sample(input))
+            .sum();
+        
+        head_output + tail_output
+    }
+    
+    /// Reset all stage state - O(P) operation
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.history_position = 0;
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+    }
+    
+    /// Zero latency - the head FIR produces output for the current sample
+    pub fn get_latency(&self) -> usize {
+        0
+    }
+}
+
+impl Default for NonUniformConvolution {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convolution engine flavours selectable for cabinet simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvolutionMode {
+    /// Uniform partitioned overlap-save - cheapest, one block of latency
+    LowCpu,
+    
+    /// Non-uniform partitioning with a direct FIR head - no latency, more CPU
+    ZeroLatency,
+}
+
+impl nih_plug::prelude::Enum for ConvolutionMode {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Low CPU",
+            "Zero Latency",
+        ]
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "low_cpu",
+            "zero_latency",
+        ])
+    }
+
+    fn to_index(self) -> usize {
+        match self {
+            ConvolutionMode::LowCpu => 0,
+            ConvolutionMode::ZeroLatency => 1,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => ConvolutionMode::LowCpu,
+            1 => ConvolutionMode::ZeroLatency,
+            _ => ConvolutionMode::LowCpu, // Default fallback
+        }
+    }
+}
+
+/// Convolution engine with a selectable latency/CPU trade-off
+pub enum ConvolutionEngine {
+    Uniform(PartitionedConvolution),
+    NonUniform(NonUniformConvolution),
+}
+
+impl ConvolutionEngine {
+    /// Create engine for the given mode - block size only applies to the uniform engine
+    pub fn new(mode: ConvolutionMode, block_size: usize) -> Self {
+        match mode {
+            ConvolutionMode::LowCpu => ConvolutionEngine::Uniform(PartitionedConvolution::new(block_size)),
+            ConvolutionMode::ZeroLatency => ConvolutionEngine::NonUniform(NonUniformConvolution::new()),
+        }
+    }
+    
+    /// Mode this engine was created with
+    pub fn mode(&self) -> ConvolutionMode {
+        match self {
+            ConvolutionEngine::Uniform(_) => ConvolutionMode::LowCpu,
+            ConvolutionEngine::NonUniform(_) => ConvolutionMode::ZeroLatency,
+        }
+    }
+    
+    pub fn load_impulse_response(&mut self, impulse_response: &[f32]) -> Result<(), ConvolutionError> {
+        match self {
+            ConvolutionEngine::Uniform(engine) => engine.load_impulse_response(impulse_response),
+            ConvolutionEngine::NonUniform(engine) => engine.load_impulse_response(impulse_response),
+        }
+    }
+    
+    pub fn process_sample(&mut self, input: f32) -> f32 {
+        match self {
+            ConvolutionEngine::Uniform(engine) => engine.process_sample(input),
+            ConvolutionEngine::NonUniform(engine) => engine.process_sample(input),
+        }
+    }
+    
+    pub fn reset(&mut self) {
+        match self {
+            ConvolutionEngine::Uniform(engine) => engine.reset(),
+            ConvolutionEngine::NonUniform(engine) => engine.reset(),
+        }
+    }
+    
+    pub fn get_latency(&self) -> usize {
+        match self {
+            ConvolutionEngine::Uniform(engine) => engine.get_latency(),
+            ConvolutionEngine::NonUniform(engine) => engine.get_latency(),
+        }
+    }
+}
+
 /// Convolution engine error types for robust error handling
 #[derive(Debug, Clone)]
 pub enum ConvolutionError {
@@ -281,4 +509,121 @@ mod tests {
         let long_ir = vec![1.0; 100000];
         assert!(conv.load_impulse_response(&long_ir).is_err());
     }
-}
\ No newline at end of file
+    
+    /// Deterministic pseudo-random test signal
+    fn noise(len: usize) -> Vec<f32> {
+        let mut state = 0x1234_5678u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1u32 << 24) as f32 * 2.0 - 1.0
+            })
+            .collect()
+    }
+    
+    /// Reference O(N*M) time-domain convolution
+    fn direct_convolution(input: &[f32], ir: &[f32]) -> Vec<f32> {
+        (0..input.len())
+            .map(|n| {
+                ir.iter()
+                    .enumerate()
+                    .take(n + 1)
+                    .map(|(k, &h)| h * input[n - k])
+                    .sum()
+            })
+            .collect()
+    }
+    
+    /// Output must equal direct convolution delayed by exactly one block
+    fn assert_matches_direct(block_size: usize, ir_len: usize) {
+        let mut conv = PartitionedConvolution::new(block_size);
+        let ir = noise(ir_len + 7)[7..].to_vec();
+        conv.load_impulse_response(&ir).unwrap();
+        
+        let input = noise(ir_len + block_size * 8);
+        let expected = direct_convolution(&input, &ir);
+        let latency = conv.get_latency();
+        
+        for (n, &x) in input.iter().enumerate() {
+            let output = conv.process_sample(x);
+            let reference = if n >= latency { expected[n - latency] } else { 0.0 };
+            assert!(
+                (output - reference).abs() < 1e-3,
+                "IR length {}: sample {} got {} expected {}",
+                ir_len, n, output, reference
+            );
+        }
+    }
+    
+    #[test]
+    fn test_single_block_ir_matches_direct_convolution() {
+        assert_matches_direct(64, 64);
+        assert_matches_direct(64, 5);
+    }
+    
+    #[test]
+    fn test_two_block_ir_matches_direct_convolution() {
+        assert_matches_direct(64, 128);
+        assert_matches_direct(64, 100);
+    }
+    
+    #[test]
+    fn test_many_block_ir_matches_direct_convolution() {
+        // Same shape as the 100 ms SSP2 cabinet IRs: 4410 samples at 44.1 kHz
+        assert_matches_direct(256, 4410);
+        assert_matches_direct(32, 1000);
+    }
+    
+    #[test]
+    fn test_non_uniform_is_zero_latency_and_exact() {
+        for &ir_len in &[10, 64, 300, 4410] {
+            let mut conv = NonUniformConvolution::new();
+            let ir = noise(ir_len + 3)[3..].to_vec();
+            conv.load_impulse_response(&ir).unwrap();
+            assert_eq!(conv.get_latency(), 0);
+            
+            let input = noise(ir_len + 2048);
+            let expected = direct_convolution(&input, &ir);
+            for (n, &x) in input.iter().enumerate() {
+                let output = conv.process_sample(x);
+                assert!(
+                    (output - expected[n]).abs() < 1e-3,
+                    "IR length {}: sample {} got {} expected {}",
+                    ir_len, n, output, expected[n]
+                );
+            }
+        }
+    }
+    
+    #[test]
+    fn test_non_uniform_long_tail_uses_capped_partitions() {
+        // 8000 taps reach past the 1024-sample partition cap
+        let mut conv = NonUniformConvolution::new();
+        let ir = noise(8000);
+        conv.load_impulse_response(&ir).unwrap();
+        assert!(conv.stages.iter().all(|stage| stage.block_size <= NonUniformConvolution::MAX_PARTITION_SIZE));
+        
+        let input = noise(9000);
+        let expected = direct_convolution(&input, &ir);
+        for (n, &x) in input.iter().enumerate() {
+            assert!((conv.process_sample(x) - expected[n]).abs() < 1e-2);
+        }
+    }
+    
+    #[test]
+    fn test_process_sample_does_not_allocate() {
+        let ir = noise(3000);
+        let mut uniform = PartitionedConvolution::new(128);
+        let mut non_uniform = NonUniformConvolution::new();
+        uniform.load_impulse_response(&ir).unwrap();
+        non_uniform.load_impulse_response(&ir).unwrap();
+        let input = noise(4096);
+        
+        crate::test_alloc::tests::assert_no_alloc("uniform and non-uniform engines", || {
+            for &x in &input {
+                uniform.process_sample(x);
+                non_uniform.process_sample(x);
+            }
+        });
+    }
+}
@@ -0,0 +1,643 @@
+use std::f32::consts::PI;
+
+use super::filters::BiquadFilter;
+use super::Prepare;
+
+/// Longest delay time - the delay lines are sized for it in `prepare`
+pub const MAX_DELAY_MS: f32 = 4000.0;
+
+/// Extra delay line room for the tape modulation on top of the longest time
+const MODULATION_HEADROOM_MS: f32 = 10.0;
+
+/// Glide time of the read head towards a new delay time - a time change bends the
+/// pitch of the repeats briefly instead of jumping the read position
+const TIME_GLIDE_MS: f32 = 200.0;
+
+/// Glide time for feedback and mix changes
+const CONTROL_SMOOTHING_MS: f32 = 20.0;
+
+/// Mix below which a disabled delay stops running - its repeats have faded out
+const BYPASS_MIX: f32 = 1e-4;
+
+/// Tape transport irregularities: slow wow and faster flutter, depth in ms at full amount
+const WOW_HZ: f32 = 0.6;
+const WOW_DEPTH_MS: f32 = 3.0;
+const FLUTTER_HZ: f32 = 7.0;
+const FLUTTER_DEPTH_MS: f32 = 0.3;
+
+/// Recording level into the tape saturation
+const TAPE_DRIVE: f32 = 1.5;
+
+/// Ducking detector: fast attack so the repeats get out of the way of the pick, slow
+/// release so they swell back once the playing stops
+const DUCK_ATTACK_MS: f32 = 1.0;
+const DUCK_RELEASE_MS: f32 = 250.0;
+
+/// Input level at which full ducking halves the repeats
+const DUCK_KNEE: f32 = 0.05;
+
+/// Tempo used for synced times when the host doesn't report one
+pub const DEFAULT_TEMPO_BPM: f32 = 120.0;
+
+/// Character of the repeats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelayMode {
+    /// Clean repeats - only the feedback filter shapes them
+    Digital,
+
+    /// Wow and flutter on the read head and saturation on the way onto the tape
+    Tape,
+}
+
+impl nih_plug::prelude::Enum for DelayMode {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Digital",
+            "Tape",
+        ]
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "digital",
+            "tape",
+        ])
+    }
+
+    fn to_index(self) -> usize {
+        match self {
+            DelayMode::Digital => 0,
+            DelayMode::Tape => 1,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => DelayMode::Digital,
+            1 => DelayMode::Tape,
+            _ => DelayMode::Digital, // Default fallback
+        }
+    }
+}
+
+/// Delay time source: free-running milliseconds or a note value at the host tempo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelaySync {
+    Free,
+    Whole,
+    Half,
+    Quarter,
+    DottedQuarter,
+    QuarterTriplet,
+    Eighth,
+    DottedEighth,
+    EighthTriplet,
+    Sixteenth,
+}
+
+impl DelaySync {
+    /// Length in quarter notes, `None` for free-running time
+    pub fn beats(self) -> Option<f32> {
+        match self {
+            DelaySync::Free => None,
+            DelaySync::Whole => Some(4.0),
+            DelaySync::Half => Some(2.0),
+            DelaySync::Quarter => Some(1.0),
+            DelaySync::DottedQuarter => Some(1.5),
+            DelaySync::QuarterTriplet => Some(2.0 / 3.0),
+            DelaySync::Eighth => Some(0.5),
+            DelaySync::DottedEighth => Some(0.75),
+            DelaySync::EighthTriplet => Some(1.0 / 3.0),
+            DelaySync::Sixteenth => Some(0.25),
+        }
+    }
+}
+
+impl nih_plug::prelude::Enum for DelaySync {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Free",
+            "1/1",
+            "1/2",
+            "1/4",
+            "1/4 Dotted",
+            "1/4 Triplet",
+            "1/8",
+            "1/8 Dotted",
+            "1/8 Triplet",
+            "1/16",
+        ]
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "free",
+            "whole",
+            "half",
+            "quarter",
+            "dotted_quarter",
+            "quarter_triplet",
+            "eighth",
+            "dotted_eighth",
+            "eighth_triplet",
+            "sixteenth",
+        ])
+    }
+
+    fn to_index(self) -> usize {
+        match self {
+            DelaySync::Free => 0,
+            DelaySync::Whole => 1,
+            DelaySync::Half => 2,
+            DelaySync::Quarter => 3,
+            DelaySync::DottedQuarter => 4,
+            DelaySync::QuarterTriplet => 5,
+            DelaySync::Eighth => 6,
+            DelaySync::DottedEighth => 7,
+            DelaySync::EighthTriplet => 8,
+            DelaySync::Sixteenth => 9,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => DelaySync::Free,
+            1 => DelaySync::Whole,
+            2 => DelaySync::Half,
+            3 => DelaySync::Quarter,
+            4 => DelaySync::DottedQuarter,
+            5 => DelaySync::QuarterTriplet,
+            6 => DelaySync::Eighth,
+            7 => DelaySync::DottedEighth,
+            8 => DelaySync::EighthTriplet,
+            9 => DelaySync::Sixteenth,
+            _ => DelaySync::Free, // Default fallback
+        }
+    }
+}
+
+/// How the delay is set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DelaySettings {
+    /// Disabled delays fade their repeats out, then pass the signal untouched
+    pub enabled: bool,
+
+    pub mode: DelayMode,
+
+    /// Delay time when not synced
+    pub time_ms: f32,
+
+    pub sync: DelaySync,
+
+    /// Host tempo the synced time follows
+    pub tempo_bpm: f32,
+
+    /// 0-1 share of each repeat fed back into the delay line
+    pub feedback: f32,
+
+    /// Feedback path filter - every repeat gets thinner and darker
+    pub low_cut_hz: f32,
+    pub high_cut_hz: f32,
+
+    /// Repeats alternate between left and right
+    pub ping_pong: bool,
+
+    /// 0-1 depth of the tape wow and flutter - ignored in digital mode
+    pub wow_flutter: f32,
+
+    /// 0-1 how far the repeats duck under the dry signal while playing
+    pub ducking: f32,
+
+    /// 0-1 level of the repeats on top of the unity dry signal
+    pub mix: f32,
+}
+
+impl Default for DelaySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: DelayMode::Digital,
+            time_ms: 400.0,
+            sync: DelaySync::Free,
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            feedback: 0.35,
+            low_cut_hz: 100.0,
+            high_cut_hz: 6000.0,
+            ping_pong: false,
+            wow_flutter: 0.3,
+            ducking: 0.0,
+            mix: 0.3,
+        }
+    }
+}
+
+impl DelaySettings {
+    /// Delay time in milliseconds after applying the tempo sync - O(1)
+    pub fn effective_time_ms(&self) -> f32 {
+        let time_ms = match self.sync.beats() {
+            Some(beats) => beats * 60_000.0 / self.tempo_bpm.max(1.0),
+            None => self.time_ms,
+        };
+        time_ms.clamp(1.0, MAX_DELAY_MS)
+    }
+}
+
+/// Circular delay line with a fractional read position - O(1) per sample
+struct DelayLine {
+    buffer: Vec<f32>,
+    write_pos: usize,
+}
+
+impl DelayLine {
+    fn new(capacity: usize) -> Self {
+        Self { buffer: vec![0.0; capacity.max(2)], write_pos: 0 }
+    }
+
+    fn clear(&mut self) {
+        self.buffer.fill(0.0);
+    }
+
+    /// Sample written `delay` samples ago, linearly interpolated
+    fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let delay = delay.clamp(1.0, (len - 1) as f32);
+        let whole = delay as usize;
+        let frac = delay - whole as f32;
+        let newer = self.buffer[(self.write_pos + len - whole) % len];
+        let older = self.buffer[(self.write_pos + len - whole - 1) % len];
+        newer + frac * (older - newer)
+    }
+
+    fn write(&mut self, input: f32) {
+        self.buffer[self.write_pos] = input;
+        self.write_pos = (self.write_pos + 1) % self.buffer.len();
+    }
+}
+
+/// Feedback path of one channel: delay line and its low/high cut filters
+struct DelayChannel {
+    line: DelayLine,
+    low_cut: BiquadFilter,
+    high_cut: BiquadFilter,
+}
+
+impl DelayChannel {
+    fn new(capacity: usize) -> Self {
+        Self { line: DelayLine::new(capacity), low_cut: BiquadFilter::new(), high_cut: BiquadFilter::new() }
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+        self.low_cut.reset();
+        self.high_cut.reset();
+    }
+
+    /// Filtered repeat at `delay` samples - O(1)
+    fn read(&mut self, delay: f32) -> f32 {
+        self.high_cut.process(self.low_cut.process(self.line.read(delay)))
+    }
+}
+
+/// Stereo delay after the cabinet - O(1) per sample
+///
+/// Each channel has its own line and feedback filter; ping-pong feeds the mono sum
+/// into the left line and crosses the feedback, so repeats alternate sides. The read
+/// head glides to new delay times, which is also what the tape wow and flutter modulate.
+pub struct StereoDelay {
+    settings: DelaySettings,
+    sample_rate: f32,
+    channels: [DelayChannel; 2],
+
+    /// Smoothed read position, in samples, and its glide coefficient
+    delay: f32,
+    target_delay: f32,
+    glide_coeff: f32,
+
+    /// Smoothed feedback and mix
+    feedback: f32,
+    mix: f32,
+    control_coeff: f32,
+
+    /// Wow and flutter oscillator phases, in cycles
+    wow_phase: f32,
+    flutter_phase: f32,
+
+    /// Dry level follower for the ducking
+    envelope: f32,
+    duck_attack: f32,
+    duck_release: f32,
+}
+
+impl StereoDelay {
+    pub fn new(sample_rate: f32) -> Self {
+        let capacity = Self::capacity(sample_rate);
+        let mut delay = Self {
+            settings: DelaySettings::default(),
+            sample_rate,
+            channels: [DelayChannel::new(capacity), DelayChannel::new(capacity)],
+            delay: 1.0,
+            target_delay: 1.0,
+            glide_coeff: 0.0,
+            feedback: 0.0,
+            mix: 0.0,
+            control_coeff: 0.0,
+            wow_phase: 0.0,
+            flutter_phase: 0.0,
+            envelope: 0.0,
+            duck_attack: 0.0,
+            duck_release: 0.0,
+        };
+        delay.update_coefficients();
+        delay.delay = delay.target_delay;
+        delay
+    }
+
+    fn capacity(sample_rate: f32) -> usize {
+        ((MAX_DELAY_MS + MODULATION_HEADROOM_MS) * 0.001 * sample_rate).ceil() as usize + 2
+    }
+
+    /// Apply new settings - O(1), no allocation, skips the math when nothing changed
+    /// Enabling a bypassed delay clears the lines, so no stale repeats play from a previous
+    /// use; enabled again while still fading out, the mix just glides back up
+    pub fn set_settings(&mut self, settings: DelaySettings) {
+        if settings == self.settings {
+            return;
+        }
+        let enabling = settings.enabled && !self.is_active();
+        self.settings = settings;
+        self.update_coefficients();
+        if enabling {
+            self.reset();
+            self.delay = self.target_delay;
+        }
+    }
+
+    fn reset(&mut self) {
+        for channel in &mut self.channels {
+            channel.reset();
+        }
+        self.feedback = 0.0;
+        self.mix = 0.0;
+        self.envelope = 0.0;
+    }
+
+    fn update_coefficients(&mut self) {
+        let sample_rate = self.sample_rate;
+        let time_coeff = |ms: f32| (-1.0 / (ms * 0.001 * sample_rate)).exp();
+        let nyquist_limit = 0.45 * sample_rate;
+
+        self.target_delay = self.settings.effective_time_ms() * 0.001 * sample_rate;
+        for channel in &mut self.channels {
+            channel.low_cut.high_pass(self.settings.low_cut_hz.clamp(10.0, nyquist_limit), 0.707, sample_rate);
+            channel.high_cut.low_pass(self.settings.high_cut_hz.clamp(10.0, nyquist_limit), 0.707, sample_rate);
+        }
+        self.glide_coeff = time_coeff(TIME_GLIDE_MS);
+        self.control_coeff = time_coeff(CONTROL_SMOOTHING_MS);
+        self.duck_attack = time_coeff(DUCK_ATTACK_MS);
+        self.duck_release = time_coeff(DUCK_RELEASE_MS);
+    }
+
+    /// Read head offset of the tape transport for each channel, in samples - O(1)
+    /// The right channel runs a quarter cycle behind so the two sides drift apart
+    fn tape_modulation(&mut self) -> [f32; 2] {
+        if self.settings.mode != DelayMode::Tape {
+            return [0.0; 2];
+        }
+        let depth = self.settings.wow_flutter.clamp(0.0, 1.0) * 0.001 * self.sample_rate;
+        self.wow_phase = (self.wow_phase + WOW_HZ / self.sample_rate).fract();
+        self.flutter_phase = (self.flutter_phase + FLUTTER_HZ / self.sample_rate).fract();
+
+        let offset = |phase_shift: f32| {
+            let wow = (2.0 * PI * (self.wow_phase + phase_shift)).sin() * WOW_DEPTH_MS;
+            let flutter = (2.0 * PI * (self.flutter_phase + phase_shift)).sin() * FLUTTER_DEPTH_MS;
+            // Centered on the nominal time, never reading ahead of it by more than the depth
+            depth * (wow + flutter + WOW_DEPTH_MS + FLUTTER_DEPTH_MS) * 0.5
+        };
+        [offset(0.0), offset(0.25)]
+    }
+
+    /// Whether the delay still runs - a disabled delay keeps going until the mix has
+    /// glided to zero, so switching it off fades the repeats instead of cutting them
+    fn is_active(&self) -> bool {
+        self.settings.enabled || self.mix > BYPASS_MIX
+    }
+
+    /// Delay one stereo block in place - O(N), passes through once disabled and faded out
+    pub fn process_block(&mut self, left: &mut [f32], right: &mut [f32]) {
+        if !self.is_active() {
+            return;
+        }
+        let ping_pong = self.settings.ping_pong;
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            (*left, *right) = self.process_frame(*left, *right, ping_pong);
+        }
+    }
+
+    /// Delay a mono block in place - O(N), both lines run on the same input and
+    /// ping-pong has nowhere to go
+    pub fn process_mono_block(&mut self, samples: &mut [f32]) {
+        if !self.is_active() {
+            return;
+        }
+        for sample in samples.iter_mut() {
+            *sample = self.process_frame(*sample, *sample, false).0;
+        }
+    }
+
+    /// One stereo frame through both lines - O(1)
+    fn process_frame(&mut self, left: f32, right: f32, ping_pong: bool) -> (f32, f32) {
+        let target_feedback = self.settings.feedback.clamp(0.0, 0.98);
+        let target_mix = if self.settings.enabled { self.settings.mix.clamp(0.0, 1.0) } else { 0.0 };
+        self.delay = self.target_delay + (self.delay - self.target_delay) * self.glide_coeff;
+        self.feedback = target_feedback + (self.feedback - target_feedback) * self.control_coeff;
+        self.mix = target_mix + (self.mix - target_mix) * self.control_coeff;
+
+        let level = left.abs().max(right.abs());
+        let coeff = if level > self.envelope { self.duck_attack } else { self.duck_release };
+        self.envelope = level + (self.envelope - level) * coeff;
+        let duck = 1.0 - self.settings.ducking.clamp(0.0, 1.0) * self.envelope / (self.envelope + DUCK_KNEE);
+
+        let [left_offset, right_offset] = self.tape_modulation();
+        let [left_channel, right_channel] = &mut self.channels;
+        let left_repeat = left_channel.read(self.delay + left_offset);
+        let right_repeat = right_channel.read(self.delay + right_offset);
+
+        let (mut left_write, mut right_write) = if ping_pong {
+            (0.5 * (left + right) + self.feedback * right_repeat, self.feedback * left_repeat)
+        } else {
+            (left + self.feedback * left_repeat, right + self.feedback * right_repeat)
+        };
+        if self.settings.mode == DelayMode::Tape {
+            left_write = (left_write * TAPE_DRIVE).tanh() / TAPE_DRIVE;
+            right_write = (right_write * TAPE_DRIVE).tanh() / TAPE_DRIVE;
+        }
+        left_channel.line.write(left_write);
+        right_channel.line.write(right_write);
+
+        let wet = self.mix * duck;
+        (left + wet * left_repeat, right + wet * right_repeat)
+    }
+}
+
+impl Prepare for StereoDelay {
+    /// Size the delay lines for the new rate and clear all repeats
+    fn prepare(&mut self, sample_rate: f32, _max_block: usize) {
+        self.sample_rate = sample_rate;
+        let capacity = Self::capacity(sample_rate);
+        self.channels = [DelayChannel::new(capacity), DelayChannel::new(capacity)];
+        self.update_coefficients();
+        self.reset();
+        self.delay = self.target_delay;
+        self.wow_phase = 0.0;
+        self.flutter_phase = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delay_with(settings: DelaySettings) -> StereoDelay {
+        let mut delay = StereoDelay::new(44100.0);
+        delay.set_settings(DelaySettings { enabled: true, ..settings });
+        delay
+    }
+
+    /// Wide-open digital delay with full mix, so the repeats are easy to measure
+    fn clean_settings() -> DelaySettings {
+        DelaySettings { low_cut_hz: 10.0, high_cut_hz: 20000.0, mix: 1.0, ..DelaySettings::default() }
+    }
+
+    /// Left and right response to a click on both inputs
+    fn click_response(delay: &mut StereoDelay, len: usize) -> (Vec<f32>, Vec<f32>) {
+        let mut left = vec![0.0; len];
+        let mut right = vec![0.0; len];
+        left[0] = 1.0;
+        right[0] = 1.0;
+        for (left, right) in left.chunks_mut(64).zip(right.chunks_mut(64)) {
+            delay.process_block(left, right);
+        }
+        (left, right)
+    }
+
+    /// Position and level of the loudest sample in `range`
+    fn peak(samples: &[f32], range: std::ops::Range<usize>) -> (usize, f32) {
+        let start = range.start;
+        samples[range].iter().enumerate().fold((start, 0.0), |(at, level), (i, x)| {
+            if x.abs() > level { (start + i, x.abs()) } else { (at, level) }
+        })
+    }
+
+    #[test]
+    fn test_synced_repeats_follow_the_tempo() {
+        // A dotted eighth at 100 bpm is 450 ms
+        let settings = DelaySettings { sync: DelaySync::DottedEighth, tempo_bpm: 100.0, ..clean_settings() };
+        assert!((settings.effective_time_ms() - 450.0).abs() < 1e-3);
+
+        let mut delay = delay_with(settings);
+        let (left, _) = click_response(&mut delay, 44100);
+        let spacing = (0.45 * 44100.0) as usize;
+        let (first_at, _) = peak(&left, 100..spacing + 100);
+        let (second_at, _) = peak(&left, spacing + 100..2 * spacing + 100);
+        assert!(first_at.abs_diff(spacing) <= 1, "first repeat at {}", first_at);
+        assert!(second_at.abs_diff(2 * spacing) <= 2, "second repeat at {}", second_at);
+
+        // The filters smear each click a little, so compare the area under the repeats
+        let area = |at: usize| left[at - 8..at + 8].iter().sum::<f32>();
+        let ratio = area(second_at) / area(first_at);
+        assert!((ratio - settings.feedback).abs() < 0.02, "feedback {}", ratio);
+    }
+
+    #[test]
+    fn test_ping_pong_alternates_sides() {
+        let mut delay = delay_with(DelaySettings { ping_pong: true, time_ms: 100.0, ..clean_settings() });
+        let (left, right) = click_response(&mut delay, 13230);
+        let repeat = |samples: &[f32], n: usize| peak(samples, n * 4410 - 50..n * 4410 + 50).1;
+        assert!(repeat(&left, 1) > 0.5 && repeat(&right, 1) < 1e-3);
+        assert!(repeat(&right, 2) > 0.1 && repeat(&left, 2) < 1e-3);
+    }
+
+    #[test]
+    fn test_time_changes_glide_without_clicks() {
+        for mode in [DelayMode::Digital, DelayMode::Tape] {
+            let settings = DelaySettings { enabled: true, mode, wow_flutter: 1.0, ..clean_settings() };
+            let mut delay = delay_with(settings);
+            let sine = |n: usize| 0.5 * (2.0 * PI * 220.0 * n as f32 / 44100.0).sin();
+            let mut previous = 0.0;
+            let mut largest_step = 0.0f32;
+            for block in 0..2000 {
+                if block == 1000 {
+                    delay.set_settings(DelaySettings { time_ms: 120.0, ..settings });
+                }
+                let mut left: Vec<f32> = (0..64).map(|i| sine(block * 64 + i)).collect();
+                let mut right = left.clone();
+                delay.process_block(&mut left, &mut right);
+                for &sample in &left {
+                    largest_step = largest_step.max((sample - previous).abs());
+                    previous = sample;
+                }
+            }
+            // Dry and repeats together move at most a few times the sine's own slope
+            assert!(largest_step < 0.15, "{:?} step {}", mode, largest_step);
+        }
+    }
+
+    #[test]
+    fn test_disabling_fades_the_repeats_out() {
+        // Switch off while the repeats of a finished note are playing: they glide away
+        // instead of stopping dead, and the delay is bypassed once they are gone.
+        // Switched back on mid-fade, they glide back up rather than being cleared.
+        let settings = DelaySettings { enabled: true, time_ms: 300.0, ..clean_settings() };
+        let note = |n: usize| if n < 22050 { 0.5 * (2.0 * PI * 220.0 * n as f32 / 44100.0).sin() } else { 0.0 };
+        for enable_again in [None, Some(403)] {
+            let mut delay = delay_with(settings);
+            let mut previous = 0.0;
+            let mut largest_step = 0.0f32;
+            let mut tail = 0.0f32;
+            for block in 0..600 {
+                if block == 400 {
+                    delay.set_settings(DelaySettings { enabled: false, ..settings });
+                }
+                if Some(block) == enable_again {
+                    delay.set_settings(settings);
+                }
+                let mut left: Vec<f32> = (0..64).map(|i| note(block * 64 + i)).collect();
+                let mut right = left.clone();
+                delay.process_block(&mut left, &mut right);
+                for &sample in &left {
+                    largest_step = largest_step.max((sample - previous).abs());
+                    previous = sample;
+                }
+                if block >= 550 {
+                    tail = tail.max(peak(&left, 0..64).1);
+                }
+            }
+            assert!(largest_step < 0.05, "step {} when switched at {:?}", largest_step, enable_again);
+            if enable_again.is_none() {
+                assert_eq!(tail, 0.0, "repeats still playing after the fade");
+            } else {
+                assert!(tail > 0.1, "repeats {} after enabling again", tail);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ducking_lowers_repeats_while_playing() {
+        // One second of note against a half second delay: the first half of the repeats
+        // plays under the note, the rest after it has stopped and the ducking let go
+        let settings = DelaySettings { ducking: 1.0, feedback: 0.0, time_ms: 500.0, ..clean_settings() };
+        let mut delay = delay_with(settings);
+        let note = |n: usize| if n < 44100 { 0.3 * (2.0 * PI * 220.0 * n as f32 / 44100.0).sin() } else { 0.0 };
+        let dry: Vec<f32> = (0..66150).map(note).collect();
+        let mut left = dry.clone();
+        let mut right = dry.clone();
+        for (left, right) in left.chunks_mut(64).zip(right.chunks_mut(64)) {
+            delay.process_block(left, right);
+        }
+        let wet: Vec<f32> = left.iter().zip(&dry).map(|(out, dry)| out - dry).collect();
+        let playing = peak(&wet, 26460..44100).1;
+        let after = peak(&wet, 55125..66150).1;
+        assert!(after > 0.15, "repeats at {} after the note", after);
+        assert!(playing < after * 0.5, "repeats {} while playing vs {} after", playing, after);
+    }
+}
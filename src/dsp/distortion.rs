@@ -1,3 +1,278 @@
+use std::f64::consts::{FRAC_2_PI, SQRT_2};
+
+use super::Prepare;
+
+/// How the clipper and tube curves are evaluated
+///
+/// The ADAA modes run the curve through antiderivative anti-aliasing: instead of
+/// sampling f(x[n]) they output the average of f over the path the input took since
+/// the previous sample(s), which suppresses aliasing at a fraction of the cost of
+/// oversampling. First order delays by half a sample, second order by one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaveshaperMode {
+    /// Lookup table / direct curve - cheapest, aliases most at high drive
+    Plain,
+
+    /// First-order antiderivative anti-aliasing
+    Adaa1,
+
+    /// Second-order antiderivative anti-aliasing - strongest suppression, slightly darker
+    Adaa2,
+}
+
+impl WaveshaperMode {
+    /// Group delay each waveshaper adds, in samples at the rate it runs at
+    pub fn delay(self) -> f32 {
+        match self {
+            WaveshaperMode::Plain => 0.0,
+            WaveshaperMode::Adaa1 => 0.5,
+            WaveshaperMode::Adaa2 => 1.0,
+        }
+    }
+}
+
+impl nih_plug::prelude::Enum for WaveshaperMode {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Plain",
+            "ADAA 1st Order",
+            "ADAA 2nd Order",
+        ]
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "plain",
+            "adaa1",
+            "adaa2",
+        ])
+    }
+
+    fn to_index(self) -> usize {
+        match self {
+            WaveshaperMode::Plain => 0,
+            WaveshaperMode::Adaa1 => 1,
+            WaveshaperMode::Adaa2 => 2,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => WaveshaperMode::Plain,
+            1 => WaveshaperMode::Adaa1,
+            2 => WaveshaperMode::Adaa2,
+            _ => WaveshaperMode::Plain, // Default fallback
+        }
+    }
+}
+
+/// Below this input step the ADAA divided differences lose precision and the
+/// midpoint approximations take over
+const ADAA_TOLERANCE: f64 = 1e-4;
+
+/// A memoryless curve with its first and second antiderivatives
+/// Evaluated in f64 so the divided differences don't drown in rounding noise
+#[derive(Clone, Copy)]
+struct Antiderivatives {
+    curve: fn(f64) -> f64,
+    first: fn(f64) -> f64,
+    second: fn(f64) -> f64,
+}
+
+/// Antiderivative anti-aliasing state for one curve - O(1) per sample
+struct Adaa {
+    functions: Antiderivatives,
+    /// Previous two inputs, newest first
+    history: [f64; 2],
+}
+
+impl Adaa {
+    fn new(functions: Antiderivatives) -> Self {
+        Self { functions, history: [0.0; 2] }
+    }
+
+    fn reset(&mut self) {
+        self.history = [0.0; 2];
+    }
+
+    /// Record an input evaluated without ADAA so switching modes never starts from stale history
+    fn track(&mut self, x: f64) {
+        self.history = [x, self.history[0]];
+    }
+
+    /// Evaluate the curve in the given mode
+    fn process(&mut self, x: f64, mode: WaveshaperMode) -> f64 {
+        let [x1, x2] = self.history;
+        self.track(x);
+        match mode {
+            WaveshaperMode::Plain => (self.functions.curve)(x),
+            WaveshaperMode::Adaa1 => self.mean_of_curve(x, x1),
+            WaveshaperMode::Adaa2 => self.second_order(x, x1, x2),
+        }
+    }
+
+    /// Mean of f over [b, a]: (F1(a) - F1(b)) / (a - b)
+    fn mean_of_curve(&self, a: f64, b: f64) -> f64 {
+        let delta = a - b;
+        if delta.abs() < ADAA_TOLERANCE {
+            (self.functions.curve)(0.5 * (a + b))
+        } else {
+            ((self.functions.first)(a) - (self.functions.first)(b)) / delta
+        }
+    }
+
+    /// Mean of F1 over [b, a]: (F2(a) - F2(b)) / (a - b)
+    fn mean_of_first(&self, a: f64, b: f64) -> f64 {
+        let delta = a - b;
+        if delta.abs() < ADAA_TOLERANCE {
+            (self.functions.first)(0.5 * (a + b))
+        } else {
+            ((self.functions.second)(a) - (self.functions.second)(b)) / delta
+        }
+    }
+
+    /// Triangle-weighted mean of f over [x2, x1] and [x1, x]
+    fn second_order(&self, x: f64, x1: f64, x2: f64) -> f64 {
+        let span = x - x2;
+        if span.abs() >= ADAA_TOLERANCE {
+            return 2.0 / span * (self.mean_of_first(x, x1) - self.mean_of_first(x1, x2));
+        }
+
+        // The input turned around - both segments cover roughly the same stretch
+        let mid = 0.5 * (x + x2);
+        let delta = mid - x1;
+        if delta.abs() < ADAA_TOLERANCE {
+            (self.functions.curve)(0.5 * (mid + x1))
+        } else {
+            2.0 / delta * ((self.functions.first)(mid) + ((self.functions.second)(x1) - (self.functions.second)(mid)) / delta)
+        }
+    }
+}
+
+/// Extend a curve's antiderivatives past a hard clamp at +-`limit`,
+/// where the curve holds its end value
+fn clamped_first(x: f64, limit: f64, curve: fn(f64) -> f64, first: fn(f64) -> f64) -> f64 {
+    let edge = x.clamp(-limit, limit);
+    first(edge) + curve(edge) * (x - edge)
+}
+
+fn clamped_second(x: f64, limit: f64, curve: fn(f64) -> f64, first: fn(f64) -> f64, second: fn(f64) -> f64) -> f64 {
+    let edge = x.clamp(-limit, limit);
+    let overshoot = x - edge;
+    second(edge) + first(edge) * overshoot + 0.5 * curve(edge) * overshoot * overshoot
+}
+
+/// Driven input range covered by the clipper - it holds its end values beyond
+const CLIPPER_LIMIT: f64 = 4.0;
+
+/// Clipper branch constants: curve = scale * atan(x / (1 + k x)), k and scale per side
+fn clipper_branch(x: f64) -> (f64, f64) {
+    if x >= 0.0 {
+        (1.0, FRAC_2_PI)
+    } else {
+        (-0.7, 0.8 * FRAC_2_PI)
+    }
+}
+
+/// Antiderivative of atan(x / (1 + k x)), up to a constant
+/// With a = 1 + k^2 the denominator (1 + k x)^2 + x^2 equals ((a x + k)^2 + 1) / a
+fn clipper_raw_first(x: f64, k: f64) -> f64 {
+    let a = 1.0 + k * k;
+    let t = a * x + k;
+    let q = (1.0 + k * x).powi(2) + x * x;
+    x * (x / (1.0 + k * x)).atan() - q.ln() / (2.0 * a) + k / a * t.atan()
+}
+
+/// Antiderivative of `clipper_raw_first`, up to a constant
+fn clipper_raw_second(x: f64, k: f64) -> f64 {
+    let a = 1.0 + k * k;
+    let t = a * x + k;
+    let q = (1.0 + k * x).powi(2) + x * x;
+    let (atan_t, ln_q) = (t.atan(), q.ln());
+    0.5 * x * x * (x / (1.0 + k * x)).atan()
+        - 0.5 * (x / a - k * ln_q / (a * a) - (a - 2.0 * k * k) / (a * a) * atan_t)
+        - ((x + k / a) * ln_q - 2.0 * x + 2.0 / a * atan_t) / (2.0 * a)
+        + k / (a * a) * (t * atan_t - 0.5 * (1.0 + t * t).ln())
+}
+
+fn clipper_curve(x: f64) -> f64 {
+    let x = x.clamp(-CLIPPER_LIMIT, CLIPPER_LIMIT);
+    let (k, scale) = clipper_branch(x);
+    scale * (x / (1.0 + k * x)).atan()
+}
+
+fn clipper_first_unclamped(x: f64) -> f64 {
+    let (k, scale) = clipper_branch(x);
+    scale * (clipper_raw_first(x, k) - clipper_raw_first(0.0, k))
+}
+
+fn clipper_second_unclamped(x: f64) -> f64 {
+    let (k, scale) = clipper_branch(x);
+    scale * (clipper_raw_second(x, k) - clipper_raw_second(0.0, k) - clipper_raw_first(0.0, k) * x)
+}
+
+fn clipper_first(x: f64) -> f64 {
+    clamped_first(x, CLIPPER_LIMIT, clipper_curve, clipper_first_unclamped)
+}
+
+fn clipper_second(x: f64) -> f64 {
+    clamped_second(x, CLIPPER_LIMIT, clipper_curve, clipper_first_unclamped, clipper_second_unclamped)
+}
+
+const CLIPPER_ANTIDERIVATIVES: Antiderivatives = Antiderivatives {
+    curve: clipper_curve,
+    first: clipper_first,
+    second: clipper_second,
+};
+
+/// Driven input range of the triode curve - it holds its end values beyond
+const TUBE_LIMIT: f64 = 2.0;
+
+/// Slope of the negative-grid branch x / (1 - m x)
+const TUBE_CUTOFF_SLOPE: f64 = 0.3;
+
+fn tube_curve(x: f64) -> f64 {
+    let x = x.clamp(-TUBE_LIMIT, TUBE_LIMIT);
+    if x >= 0.0 {
+        x / (1.0 + x * x * 0.5)
+    } else {
+        x / (1.0 - x * TUBE_CUTOFF_SLOPE)
+    }
+}
+
+fn tube_first_unclamped(x: f64) -> f64 {
+    if x >= 0.0 {
+        (1.0 + x * x * 0.5).ln()
+    } else {
+        let m = TUBE_CUTOFF_SLOPE;
+        -x / m - (1.0 - m * x).ln() / (m * m)
+    }
+}
+
+fn tube_second_unclamped(x: f64) -> f64 {
+    if x >= 0.0 {
+        x * (1.0 + x * x * 0.5).ln() - 2.0 * x + 2.0 * SQRT_2 * (x / SQRT_2).atan()
+    } else {
+        let m = TUBE_CUTOFF_SLOPE;
+        let rest = 1.0 - m * x;
+        -x * x / (2.0 * m) + (rest * rest.ln() / m + x) / (m * m)
+    }
+}
+
+fn tube_first(x: f64) -> f64 {
+    clamped_first(x, TUBE_LIMIT, tube_curve, tube_first_unclamped)
+}
+
+fn tube_second(x: f64) -> f64 {
+    clamped_second(x, TUBE_LIMIT, tube_curve, tube_first_unclamped, tube_second_unclamped)
+}
+
+const TUBE_ANTIDERIVATIVES: Antiderivatives = Antiderivatives {
+    curve: tube_curve,
+    first: tube_first,
+    second: tube_second,
+};
+
 /// High-performance asymmetric clipper for tube-like distortion - O(1) complexity
 /// Uses optimized waveshaping with pre-computed lookup tables for real-time performance
 pub struct AsymmetricClipper {
@@ -6,6 +281,10 @@ pub struct AsymmetricClipper {
     lookup_table: Vec<f32>,
     table_size: usize,
     input_scale: f32,
+    
+    /// Lookup table or antiderivative anti-aliasing
+    mode: WaveshaperMode,
+    adaa: Adaa,
 }
 
 impl AsymmetricClipper {
@@ -28,9 +307,16 @@ impl AsymmetricClipper {
             lookup_table,
             table_size: TABLE_SIZE,
             input_scale: TABLE_SIZE as f32 / INPUT_RANGE,
+            mode: WaveshaperMode::Plain,
+            adaa: Adaa::new(CLIPPER_ANTIDERIVATIVES),
         }
     }
     
+    /// Select lookup table or ADAA evaluation - O(1), no allocation
+    pub fn set_mode(&mut self, mode: WaveshaperMode) {
+        self.mode = mode;
+    }
+    
     /// Asymmetric waveshaping function modeling tube saturation characteristics
     /// Creates even-order harmonics for warm, musical distortion
     fn asymmetric_waveshape(x: f32) -> f32 {
@@ -46,11 +332,22 @@ impl AsymmetricClipper {
         }
     }
     
-    /// Process sample with O(1) complexity using lookup table
-    /// Includes linear interpolation for smooth response between table entries
-    pub fn process(&self, input: f32, drive: f32) -> f32 {
-        // Apply drive and clamp to table range - O(1)
-        let driven_input = (input * drive).clamp(-4.0, 4.0);
+    /// Process sample with O(1) complexity using lookup table or ADAA
+    pub fn process(&mut self, input: f32, drive: f32) -> f32 {
+        let driven_input = input * drive;
+        match self.mode {
+            WaveshaperMode::Plain => {
+                self.adaa.track(driven_input as f64);
+                self.lookup(driven_input)
+            }
+            mode => self.adaa.process(driven_input as f64, mode) as f32,
+        }
+    }
+    
+    /// Table lookup with linear interpolation for smooth response between table entries - O(1)
+    fn lookup(&self, driven_input: f32) -> f32 {
+        // Clamp to table range - O(1)
+        let driven_input = driven_input.clamp(-4.0, 4.0);
         
         // Convert to table index with fractional part - O(1)
         let table_pos = (driven_input + 4.0) * self.input_scale;
@@ -67,6 +364,20 @@ impl AsymmetricClipper {
         let y1 = self.lookup_table[table_index + 1];
         y0 + frac * (y1 - y0)
     }
+    
+    /// Process a block in place with per-sample drive values - O(N) complexity
+    pub fn process_block(&mut self, samples: &mut [f32], drive: &[f32]) {
+        for (sample, &drive) in samples.iter_mut().zip(drive) {
+            *sample = self.process(*sample, drive);
+        }
+    }
+}
+
+impl Prepare for AsymmetricClipper {
+    /// The curve doesn't depend on the rate - only the ADAA history is cleared
+    fn prepare(&mut self, _sample_rate: f32, _max_block: usize) {
+        self.adaa.reset();
+    }
 }
 
 /// Tube saturation model with dynamic bias shifting - O(1) complexity
@@ -76,6 +387,9 @@ pub struct TubeSaturation {
     bias: f32,
     /// Bias filter coefficient for smooth bias tracking - O(1) update
     bias_coeff: f32,
+    /// Direct curve or antiderivative anti-aliasing
+    mode: WaveshaperMode,
+    adaa: Adaa,
 }
 
 impl TubeSaturation {
@@ -84,20 +398,40 @@ impl TubeSaturation {
         Self {
             bias: 0.0,
             bias_coeff: 0.999, // Very slow bias tracking for realistic tube behavior
+            mode: WaveshaperMode::Plain,
+            adaa: Adaa::new(TUBE_ANTIDERIVATIVES),
         }
     }
     
+    /// Select direct or ADAA evaluation of the triode curve - O(1), no allocation
+    pub fn set_mode(&mut self, mode: WaveshaperMode) {
+        self.mode = mode;
+    }
+    
     /// Process sample with dynamic bias shifting - O(1) complexity
     /// Models grid current rectification and cathode self-bias effects
     pub fn process(&mut self, input: f32, drive: f32) -> f32 {
-        // Update dynamic bias based on input level - O(1) exponential smoothing
-        let input_level = (input * drive).abs();
+        self.process_keyed(input, input, drive)
+    }
+    
+    /// Process sample with the bias tracker driven by a separate key signal - O(1) complexity
+    /// Lets linked stereo channels share one detector so both sides bias identically
+    pub fn process_keyed(&mut self, input: f32, key: f32, drive: f32) -> f32 {
+        // Update dynamic bias based on key level - O(1) exponential smoothing
+        let input_level = (key * drive).abs();
         let target_bias = input_level * 0.1; // Grid current simulation
         self.bias = self.bias * self.bias_coeff + target_bias * (1.0 - self.bias_coeff);
         
         // Apply bias shift and saturation - O(1) computation
         let biased_input = input + self.bias * 0.5;
-        let saturated = Self::tube_transfer_function(biased_input * drive);
+        let driven_input = biased_input * drive;
+        let saturated = match self.mode {
+            WaveshaperMode::Plain => {
+                self.adaa.track(driven_input as f64);
+                Self::tube_transfer_function(driven_input)
+            }
+            mode => self.adaa.process(driven_input as f64, mode) as f32,
+        };
         
         // High-frequency rolloff for realistic tube response - O(1) single-pole filter
         saturated * 0.95 // Simple high-cut approximation
@@ -117,4 +451,107 @@ impl TubeSaturation {
             normalized / (1.0 - normalized * 0.3)
         }
     }
-}
\ No newline at end of file
+}
+
+impl Prepare for TubeSaturation {
+    /// Keep the bias tracking time constant independent of the rate it runs at - O(1)
+    /// The 0.999 per-sample coefficient is tuned for 44.1 kHz
+    fn prepare(&mut self, sample_rate: f32, _max_block: usize) {
+        self.bias_coeff = 0.999f32.powf(44100.0 / sample_rate);
+        self.bias = 0.0;
+        self.adaa.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::oversampling::tests::{alias_level_db, power_spectrum};
+    use crate::dsp::{oversampling::Oversampler, OversamplingFactor, MAX_BLOCK_SIZE};
+
+    #[test]
+    fn test_antiderivatives_match_curves() {
+        let step = 1e-4;
+        for functions in [CLIPPER_ANTIDERIVATIVES, TUBE_ANTIDERIVATIVES] {
+            for i in 0..1000 {
+                let x = -6.0 + i as f64 * 0.0123;
+                let first_slope = ((functions.first)(x + step) - (functions.first)(x - step)) / (2.0 * step);
+                let second_slope = ((functions.second)(x + step) - (functions.second)(x - step)) / (2.0 * step);
+                assert!((first_slope - (functions.curve)(x)).abs() < 1e-6, "F1' != f at {}", x);
+                assert!((second_slope - (functions.first)(x)).abs() < 1e-6, "F2' != F1 at {}", x);
+            }
+        }
+
+        // The direct curve and the lookup table agree with the f64 curves
+        let clipper = AsymmetricClipper::new();
+        for i in 0..100 {
+            let x = -5.0 + i as f32 * 0.1;
+            assert!((clipper.lookup(x) - clipper_curve(x as f64) as f32).abs() < 1e-3);
+            assert!((TubeSaturation::tube_transfer_function(x) - tube_curve(x as f64) as f32).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_adaa_approaches_oversampled_reference() {
+        // ~3.2 kHz on an exact bin, driven into both clipper branches and the clamp
+        const LEN: usize = 4096;
+        const FUNDAMENTAL_BIN: usize = 300;
+        let input: Vec<f32> = (0..2 * LEN)
+            .map(|n| 1.5 * (2.0 * std::f32::consts::PI * (FUNDAMENTAL_BIN * n) as f32 / LEN as f32).sin())
+            .collect();
+
+        let host_rate = |mode: WaveshaperMode| {
+            let mut clipper = AsymmetricClipper::new();
+            clipper.set_mode(mode);
+            let output: Vec<f32> = input.iter().map(|&x| clipper.process(x, 1.0)).collect();
+            output[LEN..].to_vec()
+        };
+        let reference = {
+            let mut clipper = AsymmetricClipper::new();
+            let mut oversampler = Oversampler::new();
+            oversampler.set_factor(OversamplingFactor::X8);
+            let mut output = input.clone();
+            for block in output.chunks_mut(MAX_BLOCK_SIZE) {
+                oversampler.process_block(block, |upsampled| {
+                    for sample in upsampled.iter_mut() {
+                        *sample = clipper.process(*sample, 1.0);
+                    }
+                });
+            }
+            output[LEN..].to_vec()
+        };
+
+        // Strong harmonics keep their level once ADAA's own gentle high cut is taken out:
+        // order k averages over a k-sample window, a sinc^k response
+        let harmonic_levels = |output: &[f32]| {
+            let spectrum = power_spectrum(output);
+            [1, 3].map(|harmonic| {
+                let center = harmonic * FUNDAMENTAL_BIN;
+                10.0 * spectrum[center - 4..=center + 4].iter().sum::<f64>().log10()
+            })
+        };
+        let reference_levels = harmonic_levels(&reference);
+
+        let plain = alias_level_db(&host_rate(WaveshaperMode::Plain), FUNDAMENTAL_BIN);
+        let mut previous = plain;
+        for (order, mode) in [(1, WaveshaperMode::Adaa1), (2, WaveshaperMode::Adaa2)] {
+            let output = host_rate(mode);
+            for ((level, reference_level), harmonic) in harmonic_levels(&output).iter().zip(reference_levels).zip([1, 3]) {
+                let x = std::f64::consts::PI * (harmonic * FUNDAMENTAL_BIN) as f64 / LEN as f64;
+                let droop = 20.0 * order as f64 * (x.sin() / x).log10();
+                assert!(
+                    (level - droop - reference_level).abs() < 1.0,
+                    "{:?}: harmonic {} at {} dB, reference {} dB", mode, harmonic, level, reference_level
+                );
+            }
+
+            let alias = alias_level_db(&output, FUNDAMENTAL_BIN);
+            assert!(alias < previous - 3.0, "{:?}: aliasing at {} dB after {} dB", mode, alias, previous);
+            previous = alias;
+        }
+
+        // Second order gets close to what 8x oversampling achieves
+        let reference_alias = alias_level_db(&reference, FUNDAMENTAL_BIN);
+        assert!(previous < reference_alias + 6.0, "ADAA2 at {} dB, 8x reference at {} dB", previous, reference_alias);
+    }
+}
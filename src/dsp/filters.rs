@@ -1,5 +1,8 @@
 use std::f32::consts::PI;
 
+use super::tone_stack::{CircuitToneStack, ToneStackModel};
+use super::Prepare;
+
 /// High-performance biquad filter with O(1) processing complexity
 /// Uses direct form II transposed for numerical stability
 #[derive(Clone)]
@@ -81,10 +84,16 @@ impl BiquadFilter {
 /// Guitar amplifier tone stack simulation - O(1) processing complexity
 /// Models classic Fender/Marshall tone circuit with functional composition
 pub struct ToneStack {
+    model: ToneStackModel,
     bass_filter: BiquadFilter,
     mid_filter: BiquadFilter,
     treble_filter: BiquadFilter,
+    /// Discretized circuit used by every model except Modern
+    circuit: CircuitToneStack,
     sample_rate: f32,
+    /// Control values the current coefficients were computed for
+    /// Lets unchanged controls skip the powf/sin/cos coefficient math entirely
+    current_controls: Option<(f32, f32, f32)>,
 }
 
 impl ToneStack {
@@ -92,10 +101,13 @@ impl ToneStack {
     /// Pre-configures filters for guitar frequency response
     pub fn new(sample_rate: f32) -> Self {
         let mut stack = Self {
+            model: ToneStackModel::Modern,
             bass_filter: BiquadFilter::new(),
             mid_filter: BiquadFilter::new(), 
             treble_filter: BiquadFilter::new(),
+            circuit: CircuitToneStack::new(ToneStackModel::Modern, sample_rate),
             sample_rate,
+            current_controls: None,
         };
         
         // Initialize with neutral settings - O(1) setup
@@ -103,9 +115,39 @@ impl ToneStack {
         stack
     }
     
+    /// Switch the circuit the controls drive - rebuilds the filters for the current settings
+    pub fn set_model(&mut self, model: ToneStackModel) {
+        if model == self.model {
+            return;
+        }
+        self.model = model;
+        self.rebuild();
+    }
+    
+    /// Recompute every filter from scratch and clear their state
+    fn rebuild(&mut self) {
+        self.circuit = CircuitToneStack::new(self.model, self.sample_rate);
+        for filter in [&mut self.bass_filter, &mut self.mid_filter, &mut self.treble_filter] {
+            filter.reset();
+        }
+        let (bass_db, mid_db, treble_db) = self.current_controls.take().unwrap_or((0.0, 0.0, 0.0));
+        self.update_controls(bass_db, mid_db, treble_db);
+    }
+    
     /// Update tone controls - O(1) coefficient updates
     /// Each filter update is O(1) using pre-computed formulas
     pub fn update_controls(&mut self, bass_db: f32, mid_db: f32, treble_db: f32) {
+        // Skip coefficient recalculation when the smoothed values haven't moved
+        if self.current_controls == Some((bass_db, mid_db, treble_db)) {
+            return;
+        }
+        self.current_controls = Some((bass_db, mid_db, treble_db));
+        
+        if self.model != ToneStackModel::Modern {
+            self.circuit.update_controls(bass_db, mid_db, treble_db);
+            return;
+        }
+        
         // Configure filters for guitar-optimized frequency response
         self.bass_filter.peaking_eq(100.0, bass_db, 0.7, self.sample_rate);      // O(1)
         self.mid_filter.peaking_eq(500.0, mid_db, 1.0, self.sample_rate);       // O(1)  
@@ -115,11 +157,38 @@ impl ToneStack {
     /// Process sample through tone stack - O(1) complexity
     /// Functional composition: bass -> mid -> treble processing chain
     pub fn process(&mut self, input: f32) -> f32 {
+        if self.model != ToneStackModel::Modern {
+            return self.circuit.process(input);
+        }
         input
             .pipe(|x| self.bass_filter.process(x))    // O(1) bass filtering
             .pipe(|x| self.mid_filter.process(x))     // O(1) mid filtering  
             .pipe(|x| self.treble_filter.process(x))  // O(1) treble filtering
     }
+    
+    /// Process a block in place - O(N) for N samples
+    /// Each filter runs over the whole block before the next one for cache locality
+    pub fn process_block(&mut self, samples: &mut [f32]) {
+        if self.model != ToneStackModel::Modern {
+            for sample in samples.iter_mut() {
+                *sample = self.circuit.process(*sample);
+            }
+            return;
+        }
+        for filter in [&mut self.bass_filter, &mut self.mid_filter, &mut self.treble_filter] {
+            for sample in samples.iter_mut() {
+                *sample = filter.process(*sample);
+            }
+        }
+    }
+}
+
+impl Prepare for ToneStack {
+    /// Re-tune for a new sample rate, keeping the model and control settings
+    fn prepare(&mut self, sample_rate: f32, _max_block: usize) {
+        self.sample_rate = sample_rate;
+        self.rebuild();
+    }
 }
 
 /// Create optimized EQ biquad filter - O(1) factory function
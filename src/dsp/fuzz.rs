@@ -0,0 +1,458 @@
+use super::filters::BiquadFilter;
+use super::Prepare;
+
+/// Glide time for fuzz and level changes
+const CONTROL_SMOOTHING_MS: f32 = 20.0;
+
+/// Input peak at which the fuzz reaches full gain - a hard-strummed single coil
+/// Rolling the guitar volume back lowers the gain along with the level, the way the
+/// pickup's source impedance loads the low-impedance fuzz input
+const CLEANUP_REFERENCE: f32 = 0.25;
+
+/// Share of the fuzz gain left at the lowest levels - a rolled-back guitar still
+/// passes a clean, quieter signal instead of being expanded into silence
+const CLEANUP_FLOOR: f32 = 0.1;
+
+/// Release of the input level follower that drives the cleanup
+const ENVELOPE_RELEASE_MS: f32 = 50.0;
+
+/// Operating point of a transistor stage on a fresh battery and fully starved, and the
+/// knee at cutoff. Swings of more than the bias below rest cut the transistor off; a
+/// negative bias rests it below cutoff, so only peaks above -bias turn it on at all
+const NOMINAL_BIAS: f32 = 0.5;
+const STARVED_BIAS: f32 = -0.3;
+const CUTOFF_KNEE: f32 = 0.05;
+
+/// Input level the gate dead zone reaches at full gate
+const MAX_GATE_THRESHOLD: f32 = 0.05;
+
+/// Fuzz Face: fixed gain of the first transistor, range of the fuzz pot on the second
+const FUZZ_FACE_Q1_GAIN: f32 = 4.0;
+const FUZZ_FACE_GAIN_RANGE: (f32, f32) = (2.0, 60.0);
+
+/// Big Muff: input booster, two diode clipping stages - the first on the sustain
+/// pot - and the recovery stage after the tone stack
+const MUFF_BOOST_GAIN: f32 = 4.0;
+const MUFF_SUSTAIN_RANGE: (f32, f32) = (3.0, 60.0);
+const MUFF_SECOND_CLIP_GAIN: f32 = 20.0;
+const MUFF_RECOVERY_GAIN: f32 = 4.0;
+const MUFF_DIODE_LEVEL: f32 = 0.3;
+
+/// Passband gain of the Big Muff tone stack between its two corners, used for the key
+const MUFF_TONE_STACK_GAIN: f32 = 0.5;
+
+/// Which fuzz circuit is modeled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuzzModel {
+    /// Two-transistor feedback pair - cleans up with the guitar volume
+    FuzzFace,
+
+    /// Booster, two diode clipping stages, passive tone stack and recovery stage
+    BigMuff,
+}
+
+impl nih_plug::prelude::Enum for FuzzModel {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Fuzz Face",
+            "Big Muff",
+        ]
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "fuzz_face",
+            "big_muff",
+        ])
+    }
+
+    fn to_index(self) -> usize {
+        match self {
+            FuzzModel::FuzzFace => 0,
+            FuzzModel::BigMuff => 1,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => FuzzModel::FuzzFace,
+            1 => FuzzModel::BigMuff,
+            _ => FuzzModel::FuzzFace, // Default fallback
+        }
+    }
+}
+
+/// How the fuzz pedal is set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FuzzSettings {
+    /// Disabled fuzzes are true bypass
+    pub enabled: bool,
+
+    pub model: FuzzModel,
+
+    /// 0-1 fuzz (Fuzz Face) or sustain (Big Muff) knob
+    pub fuzz: f32,
+
+    /// 0-1 Big Muff tone stack, bass to treble - the Fuzz Face has no tone control
+    pub tone: f32,
+
+    /// 0-1 supply voltage: 1 is a fresh battery, lower values starve the transistors
+    /// towards cutoff for the asymmetric, sputtering dying-battery sound
+    pub bias: f32,
+
+    /// 0-1 dead zone at the input - gates quiet playing and chops up note decays
+    pub gate: f32,
+
+    /// Output level, in dB
+    pub level_db: f32,
+}
+
+impl Default for FuzzSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model: FuzzModel::FuzzFace,
+            fuzz: 0.7,
+            tone: 0.5,
+            bias: 1.0,
+            gate: 0.0,
+            level_db: 0.0,
+        }
+    }
+}
+
+/// Conduction of a transistor stage at `u` above cutoff: a smooth knee into cutoff
+/// below zero and soft saturation above
+fn conduct(u: f32) -> f32 {
+    let on = 0.5 * (u + (u * u + CUTOFF_KNEE * CUTOFF_KNEE).sqrt());
+    on.tanh()
+}
+
+/// Class-A transistor stage biased at `bias`: output swing around rest, zero for no input
+fn transistor(v: f32, bias: f32) -> f32 {
+    conduct(v + bias) - conduct(bias)
+}
+
+/// Diodes to ground or across the feedback - symmetric soft clipping
+fn diode_clip(v: f32) -> f32 {
+    MUFF_DIODE_LEVEL * (v / MUFF_DIODE_LEVEL).tanh()
+}
+
+/// Remove `threshold` from the input's magnitude - nothing below it gets through
+fn dead_zone(x: f32, threshold: f32) -> f32 {
+    x - x.clamp(-threshold, threshold)
+}
+
+/// One diode clipping stage of the Big Muff with its coupling and feedback filters
+struct ClipStage {
+    coupling: BiquadFilter,
+    feedback: BiquadFilter,
+}
+
+impl ClipStage {
+    fn new() -> Self {
+        Self { coupling: BiquadFilter::new(), feedback: BiquadFilter::new() }
+    }
+
+    fn tune(&mut self, sample_rate: f32) {
+        // 100n into the base, 470p across the 470k feedback resistor - the diodes
+        // clip after that low-pass, which is what makes the Muff smooth rather than fizzy
+        self.coupling.one_pole_high_pass(100.0, sample_rate);
+        self.feedback.one_pole_low_pass(720.0, sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.coupling.reset();
+        self.feedback.reset();
+    }
+
+    fn process(&mut self, x: f32, gain: f32, bias: f32) -> f32 {
+        diode_clip(self.feedback.process(transistor(gain * self.coupling.process(x), bias)))
+    }
+}
+
+/// Fuzz pedal in front of the amp - O(1) per sample
+///
+/// Built from biased class-A transistor stages, so the starve (bias) and gate controls
+/// act on the same curve that does the clipping. The gain follows the input level, so
+/// the fuzz cleans up as the guitar volume comes down. Runs inside the oversampler
+/// ahead of the drive pedal, so it is prepared at the oversampled rate.
+pub struct FuzzPedal {
+    settings: FuzzSettings,
+    sample_rate: f32,
+
+    input_filter: BiquadFilter,
+    clip_stages: [ClipStage; 2],
+    tone_low: BiquadFilter,
+    tone_high: BiquadFilter,
+    output_filter: BiquadFilter,
+
+    /// Operating point and gate threshold derived from the settings
+    bias: f32,
+    gate_threshold: f32,
+
+    /// Input level followers of the audio and of the detector key
+    envelope: f32,
+    key_envelope: f32,
+    envelope_coeff: f32,
+
+    /// Targets and smoothed values of the fuzz gain and output level
+    target_gain: f32,
+    target_level: f32,
+    gain: f32,
+    level: f32,
+    control_coeff: f32,
+}
+
+impl FuzzPedal {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut fuzz = Self {
+            settings: FuzzSettings::default(),
+            sample_rate,
+            input_filter: BiquadFilter::new(),
+            clip_stages: [ClipStage::new(), ClipStage::new()],
+            tone_low: BiquadFilter::new(),
+            tone_high: BiquadFilter::new(),
+            output_filter: BiquadFilter::new(),
+            bias: NOMINAL_BIAS,
+            gate_threshold: 0.0,
+            envelope: 0.0,
+            key_envelope: 0.0,
+            envelope_coeff: 0.0,
+            target_gain: 1.0,
+            target_level: 1.0,
+            gain: 1.0,
+            level: 1.0,
+            control_coeff: 0.0,
+        };
+        fuzz.update_coefficients();
+        fuzz.gain = fuzz.target_gain;
+        fuzz.level = fuzz.target_level;
+        fuzz
+    }
+
+    /// Apply new settings - O(1), no allocation, skips the math when nothing changed
+    /// Fuzz and level glide; switching models clears the filters
+    pub fn set_settings(&mut self, settings: FuzzSettings) {
+        if settings == self.settings {
+            return;
+        }
+        if settings.model != self.settings.model {
+            self.reset();
+        }
+        self.settings = settings;
+        self.update_coefficients();
+    }
+
+    fn reset(&mut self) {
+        self.input_filter.reset();
+        for stage in &mut self.clip_stages {
+            stage.reset();
+        }
+        self.tone_low.reset();
+        self.tone_high.reset();
+        self.output_filter.reset();
+        self.envelope = 0.0;
+        self.key_envelope = 0.0;
+    }
+
+    fn update_coefficients(&mut self) {
+        let sample_rate = self.sample_rate;
+        let fuzz = self.settings.fuzz.clamp(0.0, 1.0);
+
+        match self.settings.model {
+            FuzzModel::FuzzFace => {
+                // 2.2u into the low input impedance; the output is dark from the Miller capacitance
+                let (min, max) = FUZZ_FACE_GAIN_RANGE;
+                self.target_gain = min + (max - min) * fuzz;
+                self.input_filter.one_pole_high_pass(80.0, sample_rate);
+                self.tone_low.one_pole_low_pass(5000.0f32.min(0.45 * sample_rate), sample_rate);
+            }
+            FuzzModel::BigMuff => {
+                // Tone pot blends a 400 Hz low-pass with a 1.8 kHz high-pass - the mid scoop
+                let (min, max) = MUFF_SUSTAIN_RANGE;
+                self.target_gain = min + (max - min) * fuzz;
+                self.input_filter.one_pole_high_pass(50.0, sample_rate);
+                self.tone_low.one_pole_low_pass(400.0, sample_rate);
+                self.tone_high.one_pole_high_pass(1800.0, sample_rate);
+            }
+        }
+        for stage in &mut self.clip_stages {
+            stage.tune(sample_rate);
+        }
+        self.output_filter.high_pass(20.0, 0.707, sample_rate);
+
+        self.bias = STARVED_BIAS + (NOMINAL_BIAS - STARVED_BIAS) * self.settings.bias.clamp(0.0, 1.0);
+        self.gate_threshold = MAX_GATE_THRESHOLD * self.settings.gate.clamp(0.0, 1.0);
+        self.target_level = 10f32.powf(self.settings.level_db / 20.0);
+        self.envelope_coeff = (-1.0 / (ENVELOPE_RELEASE_MS * 0.001 * sample_rate)).exp();
+        self.control_coeff = (-1.0 / (CONTROL_SMOOTHING_MS * 0.001 * sample_rate)).exp();
+    }
+
+    /// Share of the fuzz gain available at an input level - the volume-knob cleanup
+    /// The Big Muff's extra stages keep it saturated further down
+    fn cleanup(model: FuzzModel, envelope: f32) -> f32 {
+        let ratio = (envelope / CLEANUP_REFERENCE).min(1.0);
+        let share = match model {
+            FuzzModel::FuzzFace => ratio,
+            FuzzModel::BigMuff => ratio.sqrt(),
+        };
+        share.max(CLEANUP_FLOOR)
+    }
+
+    /// Blend of the Big Muff tone stack outputs - O(1)
+    fn tone_stack(&mut self, x: f32) -> f32 {
+        let tone = self.settings.tone.clamp(0.0, 1.0);
+        (1.0 - tone) * self.tone_low.process(x) + tone * self.tone_high.process(x)
+    }
+
+    /// Run a block through the fuzz in place - O(N), true bypass when disabled
+    /// The amp's detector key takes the same stages without their filters, so linked
+    /// channels keep identical keys that still follow the level the amp sees
+    pub fn process_block(&mut self, samples: &mut [f32], key: &mut [f32]) {
+        if !self.settings.enabled {
+            return;
+        }
+        let model = self.settings.model;
+        let bias = self.bias;
+
+        for (sample, key) in samples.iter_mut().zip(key.iter_mut()) {
+            self.gain = self.target_gain + (self.gain - self.target_gain) * self.control_coeff;
+            self.level = self.target_level + (self.level - self.target_level) * self.control_coeff;
+
+            let input = dead_zone(self.input_filter.process(*sample), self.gate_threshold);
+            self.envelope = input.abs().max(self.envelope * self.envelope_coeff);
+            let gain = self.gain * Self::cleanup(model, self.envelope);
+
+            let output = match model {
+                FuzzModel::FuzzFace => {
+                    let q1 = transistor(FUZZ_FACE_Q1_GAIN * input, bias);
+                    self.tone_low.process(transistor(gain * q1, bias))
+                }
+                FuzzModel::BigMuff => {
+                    let boosted = transistor(MUFF_BOOST_GAIN * input, bias);
+                    let clipped = self.clip_stages[0].process(boosted, gain, bias);
+                    let clipped = self.clip_stages[1].process(clipped, MUFF_SECOND_CLIP_GAIN, bias);
+                    let toned = self.tone_stack(clipped);
+                    transistor(MUFF_RECOVERY_GAIN * toned, bias)
+                }
+            };
+            *sample = self.level * self.output_filter.process(output);
+
+            let key_input = dead_zone(*key, self.gate_threshold);
+            self.key_envelope = key_input.abs().max(self.key_envelope * self.envelope_coeff);
+            let key_gain = self.gain * Self::cleanup(model, self.key_envelope);
+            *key = self.level * match model {
+                FuzzModel::FuzzFace => transistor(key_gain * transistor(FUZZ_FACE_Q1_GAIN * key_input, bias), bias),
+                FuzzModel::BigMuff => {
+                    let boosted = transistor(MUFF_BOOST_GAIN * key_input, bias);
+                    let clipped = diode_clip(transistor(key_gain * boosted, bias));
+                    let clipped = diode_clip(transistor(MUFF_SECOND_CLIP_GAIN * clipped, bias));
+                    transistor(MUFF_RECOVERY_GAIN * MUFF_TONE_STACK_GAIN * clipped, bias)
+                }
+            };
+        }
+    }
+}
+
+impl Prepare for FuzzPedal {
+    /// Retune the filters for the rate the fuzz runs at and clear all state
+    fn prepare(&mut self, sample_rate: f32, _max_block: usize) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients();
+        self.reset();
+        self.gain = self.target_gain;
+        self.level = self.target_level;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fuzz_with(settings: FuzzSettings) -> FuzzPedal {
+        let mut fuzz = FuzzPedal::new(44100.0);
+        fuzz.set_settings(FuzzSettings { enabled: true, ..settings });
+        fuzz.prepare(44100.0, 64);
+        fuzz
+    }
+
+    /// Output of half a second of 220 Hz sine, settled over the first half
+    fn sine_output(settings: FuzzSettings, amplitude: f32) -> Vec<f32> {
+        let mut samples: Vec<f32> = (0..22050)
+            .map(|n| amplitude * (2.0 * std::f32::consts::PI * 220.0 * n as f32 / 44100.0).sin())
+            .collect();
+        let mut key = samples.clone();
+        fuzz_with(settings).process_block(&mut samples, &mut key);
+        samples.split_off(11025)
+    }
+
+    /// Amplitude of the `frequency` component of a settled output
+    fn harmonic_level(output: &[f32], frequency: f32) -> f32 {
+        let (re, im) = output.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, x)| {
+            let phase = 2.0 * std::f32::consts::PI * frequency * n as f32 / 44100.0;
+            (re + x * phase.cos(), im + x * phase.sin())
+        });
+        2.0 * (re * re + im * im).sqrt() / output.len() as f32
+    }
+
+    fn rms(output: &[f32]) -> f32 {
+        (output.iter().map(|x| x * x).sum::<f32>() / output.len() as f32).sqrt()
+    }
+
+    /// Share of the output power outside the fundamental
+    fn distortion(output: &[f32]) -> f32 {
+        let fundamental = harmonic_level(output, 220.0) / 2.0f32.sqrt();
+        1.0 - (fundamental / rms(output)).powi(2)
+    }
+
+    #[test]
+    fn test_guitar_volume_cleans_up_the_fuzz_face() {
+        let settings = FuzzSettings { fuzz: 1.0, ..FuzzSettings::default() };
+        let full = distortion(&sine_output(settings, 0.3));
+        let rolled_back = distortion(&sine_output(settings, 0.01));
+        assert!(full > 0.1, "full volume distortion {}", full);
+        assert!(rolled_back < full * 0.1, "rolled back distortion {} vs {}", rolled_back, full);
+
+        // Further down the gain stops falling, so the level drops in step with the input
+        let quiet = rms(&sine_output(settings, 0.001));
+        let quieter = rms(&sine_output(settings, 0.0001));
+        assert!(quiet > 1e-4, "quiet output {}", quiet);
+        assert!(quieter > quiet * 0.05, "quieter output {} vs {}", quieter, quiet);
+    }
+
+    #[test]
+    fn test_starved_bias_and_gate_sputter() {
+        // A starved supply clips one half wave much earlier - strong even harmonics
+        let second_harmonic = |bias: f32| {
+            let output = sine_output(FuzzSettings { bias, ..FuzzSettings::default() }, 0.1);
+            harmonic_level(&output, 440.0) / harmonic_level(&output, 220.0)
+        };
+        assert!(second_harmonic(0.1) > second_harmonic(1.0) * 3.0);
+
+        // It also rests below cutoff, so a decaying note drops out instead of fading
+        let starved = FuzzSettings { bias: 0.1, ..FuzzSettings::default() };
+        assert!(rms(&sine_output(starved, 0.03)) < rms(&sine_output(FuzzSettings::default(), 0.03)) * 0.01);
+
+        // The gate kills a decaying note below its threshold but lets a picked note through
+        let gated = FuzzSettings { gate: 1.0, ..FuzzSettings::default() };
+        assert!(rms(&sine_output(gated, 0.04)) < 1e-6);
+        assert!(rms(&sine_output(gated, 0.3)) > 0.1);
+    }
+
+    #[test]
+    fn test_big_muff_tone_stack_scoops_the_mids() {
+        let response = |tone: f32, frequency: f32| {
+            let mut fuzz = fuzz_with(FuzzSettings { model: FuzzModel::BigMuff, tone, ..FuzzSettings::default() });
+            let output: Vec<f32> = (0..22050)
+                .map(|n| fuzz.tone_stack((2.0 * std::f32::consts::PI * frequency * n as f32 / 44100.0).sin()))
+                .collect();
+            20.0 * (rms(&output[11025..]) * 2.0f32.sqrt()).log10()
+        };
+        let mid = response(0.5, 1000.0);
+        assert!(mid < response(0.5, 100.0) - 6.0);
+        assert!(mid < response(0.5, 6000.0) - 6.0);
+        assert!(response(0.0, 100.0) > response(0.0, 6000.0) + 20.0);
+        assert!(response(1.0, 6000.0) > response(1.0, 100.0) + 20.0);
+    }
+}
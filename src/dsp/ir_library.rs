@@ -0,0 +1,442 @@
+use super::ir_loader::{IrLoadError, IrLoader};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Microphone names recognised in file names without a readme listing them
+const KNOWN_MICS: [&str; 16] = [
+    "SM57", "SM7B", "MD421", "MD441", "e906", "e609", "R121", "R92", "M160", "U87", "U47",
+    "C414", "RE20", "M201", "KM84", "TLM103",
+];
+
+/// Cabinet makers and models recognised in file names
+const KNOWN_CABS: [&str; 12] = [
+    "Marshall", "Mesa", "Recto", "Fender", "Twin", "Vox", "AC30", "Orange", "Bogner",
+    "Friedman", "Engl", "Greenback",
+];
+
+/// Mic placement words recognised in file names
+const POSITION_WORDS: [&str; 10] = [
+    "cap", "dustcap", "capedge", "edge", "cone", "center", "centre", "offaxis", "onaxis", "room",
+];
+
+/// One indexed impulse response file
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrEntry {
+    pub path: PathBuf,
+    pub sample_rate: u32,
+    pub channels: usize,
+    /// Length in sample frames
+    pub length: usize,
+    pub cab: Option<String>,
+    pub mic: Option<String>,
+    pub position: Option<String>,
+    /// Extra descriptions such as the speaker, taken from readme files
+    pub tags: Vec<String>,
+    /// Lowercase text matched by `IrLibrary::search`
+    search_text: String,
+}
+
+impl IrEntry {
+    /// IR length in milliseconds - O(1) complexity
+    pub fn duration_ms(&self) -> f32 {
+        self.length as f32 * 1000.0 / self.sample_rate as f32
+    }
+}
+
+/// Field constraints for `IrLibrary::filter` - text fields match case-insensitively
+/// as substrings, `None` accepts anything
+#[derive(Debug, Clone, Default)]
+pub struct IrFilter {
+    pub cab: Option<String>,
+    pub mic: Option<String>,
+    pub position: Option<String>,
+    pub channels: Option<usize>,
+    pub sample_rate: Option<u32>,
+}
+
+/// Metadata from readme files, inherited by subdirectories
+#[derive(Debug, Clone, Default)]
+struct FolderInfo {
+    cab: Option<String>,
+    mics: Vec<String>,
+    tags: Vec<String>,
+}
+
+impl FolderInfo {
+    /// Apply `Key : Value` lines from a readme on top of the parent folder's info
+    fn apply_readme(&mut self, text: &str) {
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+
+            match key.trim().to_lowercase().as_str() {
+                "cab" | "cabinet" => self.cab = Some(value.to_string()),
+                "speaker" | "speakers" => self.tags.push(value.to_string()),
+                // "Microphones : 5" is a count - only names are useful for matching
+                "mics" | "microphones" | "microphones included" => {
+                    let names = value.split(|c: char| c.is_whitespace() || c == ',');
+                    for name in names.filter(|name| !name.is_empty()) {
+                        if name.chars().any(|c| c.is_alphabetic()) && !self.mics.iter().any(|m| m == name) {
+                            self.mics.push(name.to_string());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Searchable index of the impulse response files under a directory tree
+///
+/// Scanning reads every WAV header, so build the library on a background thread.
+/// Cab, mic and position are inferred from file names, falling back to `readme*.txt`
+/// files in the same or a parent folder (e.g. `Cab : M212 - 2x12`).
+#[derive(Debug, Default)]
+pub struct IrLibrary {
+    entries: Vec<IrEntry>,
+    /// WAV files that could not be indexed, with the reason
+    skipped: Vec<(PathBuf, IrLoadError)>,
+}
+
+impl IrLibrary {
+    /// Index every `.wav` file below `root` - hidden entries and symlinked folders are skipped
+    pub fn scan(root: &Path) -> io::Result<Self> {
+        let mut library = Self::default();
+        library.scan_dir(root, root, &FolderInfo::default())?;
+        library.entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(library)
+    }
+
+    fn scan_dir(&mut self, root: &Path, dir: &Path, parent: &FolderInfo) -> io::Result<()> {
+        let mut files = Vec::new();
+        let mut subdirs = Vec::new();
+        let mut info = parent.clone();
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                subdirs.push(path);
+            } else if file_type.is_file() || (file_type.is_symlink() && path.is_file()) {
+                if name.starts_with("readme") && name.ends_with(".txt") {
+                    // Readmes are plain text, but not necessarily UTF-8
+                    if let Ok(bytes) = fs::read(&path) {
+                        info.apply_readme(&String::from_utf8_lossy(&bytes));
+                    }
+                } else if name.ends_with(".wav") {
+                    files.push(path);
+                }
+            }
+        }
+
+        for path in files {
+            match Self::index_file(root, &path, &info) {
+                Ok(entry) => self.entries.push(entry),
+                Err(error) => self.skipped.push((path, error)),
+            }
+        }
+        for subdir in subdirs {
+            self.scan_dir(root, &subdir, &info)?;
+        }
+        Ok(())
+    }
+
+    fn index_file(root: &Path, path: &Path, info: &FolderInfo) -> Result<IrEntry, IrLoadError> {
+        let data = fs::read(path).map_err(|e| IrLoadError::ReadError(e.to_string()))?;
+        let wav_info = IrLoader::read_wav_info(&data)?;
+
+        let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+        let tokens = tokenize(&stem);
+
+        let mic = info
+            .mics
+            .iter()
+            .map(String::as_str)
+            .chain(KNOWN_MICS)
+            .filter(|mic| contains_sequence(&tokens, &tokenize(mic)))
+            .max_by_key(|mic| tokenize(mic).len())
+            .map(str::to_string);
+        let cab = infer_cab(&tokens).or_else(|| info.cab.clone());
+        let position = infer_position(&tokens);
+
+        // Folder names carry pack and cab names too, so search the path below the root
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let mut search_text = relative.to_string_lossy().to_lowercase();
+        for field in [&cab, &mic, &position].into_iter().flatten().chain(&info.tags) {
+            search_text.push('\n');
+            search_text.push_str(&field.to_lowercase());
+        }
+
+        Ok(IrEntry {
+            path: path.to_path_buf(),
+            sample_rate: wav_info.sample_rate,
+            channels: wav_info.channels,
+            length: wav_info.length,
+            cab,
+            mic,
+            position,
+            tags: info.tags.clone(),
+            search_text,
+        })
+    }
+
+    /// All indexed files, sorted by path
+    pub fn entries(&self) -> &[IrEntry] {
+        &self.entries
+    }
+
+    /// Files that looked like WAVs but could not be read
+    pub fn skipped(&self) -> &[(PathBuf, IrLoadError)] {
+        &self.skipped
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries matching every whitespace-separated term of `query` - case-insensitive,
+    /// matched against the relative path, cab, mic, position and tags
+    pub fn search(&self, query: &str) -> Vec<&IrEntry> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        self.entries
+            .iter()
+            .filter(|entry| terms.iter().all(|term| entry.search_text.contains(term.as_str())))
+            .collect()
+    }
+
+    /// Entries satisfying every constraint of `filter`
+    pub fn filter(&self, filter: &IrFilter) -> Vec<&IrEntry> {
+        let text_matches = |field: &Option<String>, wanted: &Option<String>| match (field, wanted) {
+            (_, None) => true,
+            (Some(field), Some(wanted)) => field.to_lowercase().contains(&wanted.to_lowercase()),
+            (None, Some(_)) => false,
+        };
+
+        self.entries
+            .iter()
+            .filter(|entry| {
+                text_matches(&entry.cab, &filter.cab)
+                    && text_matches(&entry.mic, &filter.mic)
+                    && text_matches(&entry.position, &filter.position)
+                    && filter.channels.is_none_or(|channels| entry.channels == channels)
+                    && filter.sample_rate.is_none_or(|rate| entry.sample_rate == rate)
+            })
+            .collect()
+    }
+
+    /// Distinct microphones in the library, sorted
+    pub fn mics(&self) -> Vec<&str> {
+        Self::distinct(self.entries.iter().filter_map(|entry| entry.mic.as_deref()))
+    }
+
+    /// Distinct cabinets in the library, sorted
+    pub fn cabs(&self) -> Vec<&str> {
+        Self::distinct(self.entries.iter().filter_map(|entry| entry.cab.as_deref()))
+    }
+
+    fn distinct<'a>(values: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+        let mut values: Vec<&str> = values.collect();
+        values.sort_unstable();
+        values.dedup();
+        values
+    }
+}
+
+/// Lowercase alphanumeric runs - `SSP2_DYN-57 Cap.wav` becomes `ssp2 dyn 57 cap`
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn contains_sequence(tokens: &[String], sequence: &[String]) -> bool {
+    !sequence.is_empty() && tokens.windows(sequence.len()).any(|window| window == sequence)
+}
+
+/// Speaker layout such as `4x12`
+fn is_speaker_layout(token: &str) -> bool {
+    token.split_once('x').is_some_and(|(count, size)| {
+        !count.is_empty()
+            && !size.is_empty()
+            && count.chars().all(|c| c.is_ascii_digit())
+            && size.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+/// Mic distance such as `2cm`, `5mm` or `1in`
+fn is_distance(token: &str) -> bool {
+    ["cm", "mm", "in"].iter().any(|unit| {
+        token
+            .strip_suffix(unit)
+            .is_some_and(|value| !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+/// Cab from maker/model words and the speaker layout, e.g. `Marshall 4x12`
+fn infer_cab(tokens: &[String]) -> Option<String> {
+    let mut parts: Vec<String> = KNOWN_CABS
+        .iter()
+        .filter(|cab| tokens.iter().any(|token| *token == cab.to_lowercase()))
+        .map(|cab| cab.to_string())
+        .collect();
+    parts.extend(tokens.iter().filter(|token| is_speaker_layout(token)).cloned());
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+/// Placement words and distances in file name order, e.g. `cap edge 2cm`
+fn infer_position(tokens: &[String]) -> Option<String> {
+    let mut parts: Vec<&str> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        // "off axis" is often written as two words
+        if token == "axis" && i > 0 && (tokens[i - 1] == "off" || tokens[i - 1] == "on") {
+            parts.push(if tokens[i - 1] == "off" { "offaxis" } else { "onaxis" });
+        } else if POSITION_WORDS.contains(&token.as_str()) || is_distance(token) {
+            parts.push(token);
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal 16-bit PCM WAV file
+    fn write_wav(path: &Path, channels: u16, sample_rate: u32, frames: usize) {
+        let data_len = (frames * channels as usize * 2) as u32;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+        bytes.extend_from_slice(&(channels * 2).to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        bytes.resize(bytes.len() + data_len as usize, 0x10);
+        fs::write(path, bytes).unwrap();
+    }
+
+    /// Library tree with an SSP2-style pack, a hand-named folder and a broken file
+    fn build_library(root: &Path) {
+        let pack = root.join("SSP2");
+        let named = root.join("Misc").join("British");
+        fs::create_dir_all(&pack).unwrap();
+        fs::create_dir_all(&named).unwrap();
+        fs::create_dir_all(root.join(".cache")).unwrap();
+
+        fs::write(
+            pack.join("readme.txt"),
+            "Cab \t\t: M212 - 2x12\nSpeaker \t: 2x E. P50, 16 ohms\nMicrophones : 5\n\
+             Microphones included : DYN-57 DYN-US-8 DYN-US-6 RBN-CN-1 DYN-421\n\
+             IR length : 100 milliseconds\n",
+        )
+        .unwrap();
+        write_wav(&pack.join("SSP2 DYN-57 Cap 0cm.wav"), 1, 44100, 4410);
+        write_wav(&pack.join("SSP2 DYN-US-8 Edge 2cm.wav"), 1, 44100, 4410);
+        write_wav(&pack.join("SSP2 RBN-CN-1 Cone.WAV"), 1, 44100, 4410);
+        write_wav(&named.join("Marshall_4x12_SM57_off_axis.wav"), 2, 48000, 9600);
+        write_wav(&root.join(".cache").join("hidden.wav"), 1, 44100, 100);
+        fs::write(named.join("broken.wav"), b"RIFF").unwrap();
+        fs::write(named.join("notes.txt"), b"not an IR").unwrap();
+    }
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("ir_library_{}_{}", name, std::process::id()));
+        fs::remove_dir_all(&root).ok();
+        build_library(&root);
+        root
+    }
+
+    #[test]
+    fn test_scan_reads_metadata_and_tags() {
+        let root = temp_root("scan");
+        let library = IrLibrary::scan(&root).unwrap();
+        fs::remove_dir_all(&root).ok();
+
+        // Hidden folders and non-WAV files are ignored, unreadable WAVs are reported
+        assert_eq!(library.len(), 4);
+        assert_eq!(library.skipped().len(), 1);
+        assert!(library.skipped()[0].0.ends_with("broken.wav"));
+
+        let marshall = &library.entries()[0];
+        assert_eq!((marshall.sample_rate, marshall.channels, marshall.length), (48000, 2, 9600));
+        assert_eq!(marshall.duration_ms(), 200.0);
+        assert_eq!(marshall.cab.as_deref(), Some("Marshall 4x12"));
+        assert_eq!(marshall.mic.as_deref(), Some("SM57"));
+        assert_eq!(marshall.position.as_deref(), Some("offaxis"));
+
+        // Pack files take the cab and mic names from the readme
+        let dyn57 = &library.entries()[1];
+        assert_eq!(dyn57.cab.as_deref(), Some("M212 - 2x12"));
+        assert_eq!(dyn57.mic.as_deref(), Some("DYN-57"));
+        assert_eq!(dyn57.position.as_deref(), Some("cap 0cm"));
+        assert_eq!(dyn57.tags, vec!["2x E. P50, 16 ohms".to_string()]);
+        assert_eq!(library.entries()[2].mic.as_deref(), Some("DYN-US-8"));
+
+        assert_eq!(library.mics(), vec!["DYN-57", "DYN-US-8", "RBN-CN-1", "SM57"]);
+        assert_eq!(library.cabs(), vec!["M212 - 2x12", "Marshall 4x12"]);
+    }
+
+    #[test]
+    fn test_search_and_filter() {
+        let root = temp_root("search");
+        let library = IrLibrary::scan(&root).unwrap();
+        fs::remove_dir_all(&root).ok();
+
+        // Every term must match somewhere - path, fields or tags
+        assert_eq!(library.search("ssp2").len(), 3);
+        assert_eq!(library.search("DYN-US edge").len(), 1);
+        assert_eq!(library.search("p50 cone").len(), 1);
+        assert_eq!(library.search("british 4x12").len(), 1);
+        assert!(library.search("mesa").is_empty());
+        assert_eq!(library.search("").len(), 4);
+
+        let stereo = library.filter(&IrFilter { channels: Some(2), ..IrFilter::default() });
+        assert_eq!(stereo.len(), 1);
+        let pack_mics = library.filter(&IrFilter {
+            cab: Some("m212".to_string()),
+            mic: Some("dyn".to_string()),
+            sample_rate: Some(44100),
+            ..IrFilter::default()
+        });
+        assert_eq!(pack_mics.len(), 2);
+    }
+
+    #[test]
+    fn test_factory_cabinets_from_library() {
+        let root = temp_root("factory");
+        let library = IrLibrary::scan(&root).unwrap();
+
+        let irs = IrLoader::load_cabinet_irs(&library, 44100.0);
+        fs::remove_dir_all(&root).ok();
+
+        // The Marshall file is found and resampled; the rest fall back to built-in IRs
+        assert_eq!(irs.len(), 4);
+        let (_, marshall) = &irs[0];
+        assert_eq!(marshall.len(), 8820);
+        assert!(irs[1..].iter().all(|(_, samples)| samples.len() != 8820));
+    }
+}
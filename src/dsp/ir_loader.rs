@@ -1,149 +1,284 @@
-use std::path::Path;
+use super::cabinet::CabinetType;
+use super::ir_library::IrLibrary;
+use super::ir_prepare::IrPrepSettings;
+use std::path::{Path, PathBuf};
 use std::fs;
 
+/// Zero crossings of the sinc kernel on each side - sets resampler quality
+const SINC_ZERO_CROSSINGS: usize = 32;
+
+/// Kaiser window shape - ~90 dB stopband attenuation
+pub(super) const KAISER_BETA: f64 = 9.0;
+
+/// Passband edge relative to the lower Nyquist frequency
+/// Leaves room for the kernel's transition band so nothing folds back
+const RESAMPLE_PASSBAND: f64 = 0.95;
+
+/// Kaiser window samples over one half of the kernel, linearly interpolated between
+const KAISER_TABLE_SIZE: usize = 4096;
+
+/// Impulse response channels together with the rate they were recorded at
+///
+/// Channel layouts:
+/// - 1 channel: mono IR, used for both outputs
+/// - 2 channels: stereo IR, left and right outputs each use their own channel
+/// - 4 channels: true-stereo matrix in L->L, L->R, R->L, R->R order
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ImpulseResponse {
+    pub channels: Vec<Vec<f32>>,
+    pub sample_rate: f32,
+}
+
+impl ImpulseResponse {
+    /// Length in sample frames
+    pub fn len(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    /// All channels converted to `target_rate` - O(N * K) windowed-sinc resampling
+    /// K grows with the rate ratio when downsampling, so long high-rate IRs take a
+    /// while to load - this runs on the background loader, never the audio thread
+    pub fn resampled(&self, target_rate: f32) -> ImpulseResponse {
+        ImpulseResponse {
+            channels: self
+                .channels
+                .iter()
+                .map(|channel| IrLoader::resample(channel, self.sample_rate, target_rate))
+                .collect(),
+            sample_rate: target_rate,
+        }
+    }
+    
+    /// IRs feeding one output channel (0 = left, 1 = right)
+    /// The first channel of the result convolves the same-side input; true-stereo
+    /// matrices add a second channel that convolves the opposite input.
+    pub fn output_row(&self, output_channel: usize) -> ImpulseResponse {
+        let channel = |index: usize| self.channels.get(index).cloned().unwrap_or_default();
+        let channels = match (self.channels.len(), output_channel) {
+            (4, 0) => vec![channel(0), channel(2)],
+            (4, _) => vec![channel(3), channel(1)],
+            (2, _) => vec![channel(output_channel.min(1))],
+            _ => vec![channel(0)],
+        };
+        ImpulseResponse { channels, sample_rate: self.sample_rate }
+    }
+}
+
+/// Library searches for each factory cabinet, tried in order until one matches
+const FACTORY_CABINET_QUERIES: [(CabinetType, &[&str]); 4] = [
+    (CabinetType::Marshall4x12V30, &["marshall 4x12", "v30", "marshall"]),
+    (CabinetType::FenderTwin2x12, &["fender twin", "fender 2x12", "fender"]),
+    (CabinetType::VoxAC30Blue, &["vox ac30", "vox"]),
+    (CabinetType::Mesa4x12Recto, &["mesa recto", "recto", "mesa"]),
+];
+
+/// Format and length of a WAV file, read without decoding the audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavInfo {
+    pub sample_rate: u32,
+    pub channels: usize,
+    /// Length in sample frames
+    pub length: usize,
+}
+
 /// Impulse Response Loader for Real Cabinet Simulation
 /// Loads WAV files and converts them to f32 arrays for convolution
 pub struct IrLoader;
 
 impl IrLoader {
-    /// Load impulse response from WAV file with O(1) runtime lookup
-    /// Heavy O(N) loading is done once during plugin initialization
-    pub fn load_ir_file(file_path: &Path) -> Result<Vec<f32>, IrLoadError> {
+    /// Load impulse response from WAV file, prepared and resampled to the host rate
+    /// Heavy O(N) loading is done once, off the audio thread
+    pub fn load_ir_file(
+        file_path: &Path,
+        target_sample_rate: f32,
+        settings: &IrPrepSettings,
+    ) -> Result<ImpulseResponse, IrLoadError> {
+        Ok(Self::read_ir_file(file_path, settings)?.resampled(target_sample_rate))
+    }
+    
+    /// Read impulse response from WAV file at its native sample rate
+    /// Trimming, fades, phase and level follow `settings`
+    pub fn read_ir_file(file_path: &Path, settings: &IrPrepSettings) -> Result<ImpulseResponse, IrLoadError> {
         if !file_path.exists() {
-            return Err(IrLoadError::FileNotFound);
+            return Err(IrLoadError::FileNotFound(file_path.to_path_buf()));
         }
         
-        // Read WAV file - this is a simplified implementation
-        // In a real implementation, you'd use a proper WAV reader
-        let file_data = fs::read(file_path).map_err(|_| IrLoadError::ReadError)?;
-        
-        // Basic WAV parsing - looking for 44-byte header + data
-        if file_data.len() < 44 {
-            return Err(IrLoadError::InvalidFormat);
+        let file_data = fs::read(file_path).map_err(|e| IrLoadError::ReadError(e.to_string()))?;
+        let mut impulse_response = Self::parse_wav(&file_data)?;
+        if !matches!(impulse_response.channels.len(), 1 | 2 | 4) {
+            return Err(IrLoadError::UnsupportedChannelCount(impulse_response.channels.len()));
         }
         
-        // Check WAV signature
-        if &file_data[0..4] != b"RIFF" || &file_data[8..12] != b"WAVE" {
-            return Err(IrLoadError::InvalidFormat);
+        settings.apply(&mut impulse_response);
+        if impulse_response.is_empty() {
+            // Nothing above the trim threshold - a silent file
+            return Err(IrLoadError::Empty);
         }
         
-        // Parse basic WAV info from header
-        let sample_rate = u32::from_le_bytes([file_data[24], file_data[25], file_data[26], file_data[27]]);
-        let bit_depth = u16::from_le_bytes([file_data[34], file_data[35]]);
-        let channels = u16::from_le_bytes([file_data[22], file_data[23]]);
-        
-        // Find data chunk
-        let mut data_start = 44;
-        let mut data_size = 0;
+        Ok(impulse_response)
+    }
+    
+    /// Parse a RIFF/WAVE or RF64/BW64 file held in memory - O(N)
+    /// Walks the chunk list instead of assuming fixed offsets, so files with
+    /// `LIST`, `bext`, `JUNK` or odd-sized chunks before the audio data load too.
+    /// Channels are de-interleaved, one `Vec` per file channel.
+    pub fn parse_wav(data: &[u8]) -> Result<ImpulseResponse, IrLoadError> {
+        let (format, audio_data) = Self::find_chunks(data)?;
+        let channels = format.decode_channels(audio_data);
+        if channels[0].is_empty() {
+            return Err(IrLoadError::Empty);
+        }
         
-        // Simple data chunk search
-        for i in 12..file_data.len().saturating_sub(8) {
-            if &file_data[i..i+4] == b"data" {
-                data_size = u32::from_le_bytes([
-                    file_data[i+4], file_data[i+5], file_data[i+6], file_data[i+7]
-                ]) as usize;
-                data_start = i + 8;
-                break;
-            }
+        Ok(ImpulseResponse { channels, sample_rate: format.sample_rate as f32 })
+    }
+    
+    /// Read format and length of a WAV file held in memory without decoding it - O(chunks)
+    pub fn read_wav_info(data: &[u8]) -> Result<WavInfo, IrLoadError> {
+        let (format, audio_data) = Self::find_chunks(data)?;
+        let length = audio_data.len() / format.block_align;
+        if length == 0 {
+            return Err(IrLoadError::Empty);
         }
         
-        if data_size == 0 || data_start + data_size > file_data.len() {
-            return Err(IrLoadError::InvalidFormat);
+        Ok(WavInfo { sample_rate: format.sample_rate, channels: format.channels, length })
+    }
+    
+    /// Walk the RIFF chunk list and return the decoded `fmt ` chunk and the `data` bytes
+    fn find_chunks(data: &[u8]) -> Result<(WavFormat, &[u8]), IrLoadError> {
+        let riff_tag = read_tag(data, 0)?;
+        let is_rf64 = match &riff_tag {
+            b"RIFF" => false,
+            b"RF64" | b"BW64" => true,
+            _ => return Err(IrLoadError::BadTag { offset: 0, expected: *b"RIFF", found: riff_tag }),
+        };
+        let wave_tag = read_tag(data, 8)?;
+        if &wave_tag != b"WAVE" {
+            return Err(IrLoadError::BadTag { offset: 8, expected: *b"WAVE", found: wave_tag });
         }
         
-        // Convert audio data to f32 based on bit depth
-        let audio_data = &file_data[data_start..data_start + data_size];
-        let mut samples = Vec::new();
+        let mut format = None;
+        let mut audio_data = None;
+        // 64-bit data size from the `ds64` chunk - replaces the 0xFFFFFFFF placeholder in RF64
+        let mut ds64_data_size = None;
         
-        match bit_depth {
-            16 => {
-                // 16-bit samples
-                let bytes_per_sample = 2 * channels as usize;
-                for chunk in audio_data.chunks_exact(bytes_per_sample) {
-                    if chunk.len() >= 2 {
-                        // Take left channel (or mono)
-                        let sample_i16 = i16::from_le_bytes([chunk[0], chunk[1]]);
-                        let sample_f32 = sample_i16 as f32 / 32768.0;
-                        samples.push(sample_f32);
-                    }
-                }
-            }
-            24 => {
-                // 24-bit samples
-                let bytes_per_sample = 3 * channels as usize;
-                for chunk in audio_data.chunks_exact(bytes_per_sample) {
-                    if chunk.len() >= 3 {
-                        // Take left channel (or mono) - 24-bit little endian
-                        let sample_i32 = ((chunk[2] as i32) << 16) | 
-                                        ((chunk[1] as i32) << 8) | 
-                                        (chunk[0] as i32);
-                        // Sign extend from 24-bit to 32-bit
-                        let sample_i32 = if sample_i32 & 0x800000 != 0 {
-                            sample_i32 | 0xFF000000u32 as i32
-                        } else {
-                            sample_i32
-                        };
-                        let sample_f32 = sample_i32 as f32 / 8388608.0;
-                        samples.push(sample_f32);
-                    }
+        let mut offset = 12;
+        while offset + CHUNK_HEADER_SIZE <= data.len() {
+            let tag = read_tag(data, offset)?;
+            let declared_size = read_u32(data, offset + 4)? as u64;
+            let body = offset + CHUNK_HEADER_SIZE;
+            
+            let size = match (&tag, ds64_data_size) {
+                (b"data", Some(size)) if declared_size == RF64_SIZE_PLACEHOLDER => size,
+                _ => declared_size,
+            };
+            let end = usize::try_from(size)
+                .ok()
+                .and_then(|size| body.checked_add(size))
+                .filter(|&end| end <= data.len())
+                .ok_or(IrLoadError::Truncated { offset, needed: size, available: data.len() - body })?;
+            let chunk = &data[body..end];
+            
+            match &tag {
+                b"ds64" if is_rf64 => {
+                    // riff size (u64), data size (u64), sample count (u64), table
+                    ds64_data_size = Some(read_u64(chunk, 8).map_err(|_| IrLoadError::InvalidChunk {
+                        offset,
+                        tag,
+                        reason: "ds64 chunk too short",
+                    })?);
                 }
-            }
-            32 => {
-                // Assume 32-bit float
-                let bytes_per_sample = 4 * channels as usize;
-                for chunk in audio_data.chunks_exact(bytes_per_sample) {
-                    if chunk.len() >= 4 {
-                        // Take left channel (or mono)
-                        let sample_f32 = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
-                        samples.push(sample_f32);
+                b"fmt " => format = Some(WavFormat::parse(chunk, offset)?),
+                b"data" => {
+                    audio_data = Some(chunk);
+                    // Audio data is all we need once the format is known
+                    if format.is_some() {
+                        break;
                     }
                 }
+                _ => {}
             }
-            _ => return Err(IrLoadError::UnsupportedFormat),
+            
+            // Chunks are word aligned - odd sizes are followed by one pad byte
+            offset = end + (size as usize & 1);
         }
         
-        // Limit IR length for performance (max 4 seconds at 48kHz = 192k samples)
-        if samples.len() > 192000 {
-            samples.truncate(192000);
+        if is_rf64 && ds64_data_size.is_none() {
+            return Err(IrLoadError::MissingChunk(*b"ds64"));
+        }
+        let format = format.ok_or(IrLoadError::MissingChunk(*b"fmt "))?;
+        let audio_data = audio_data.ok_or(IrLoadError::MissingChunk(*b"data"))?;
+        Ok((format, audio_data))
+    }
+    
+    /// Band-limited sample rate conversion for impulse responses - O(N * K)
+    /// Windowed-sinc interpolation with a Kaiser window; when downsampling the
+    /// cutoff follows the target Nyquist frequency so the IR doesn't alias.
+    /// Taps are scaled by `from_rate / to_rate` so the IR keeps its frequency
+    /// response - more taps at a higher rate would otherwise add gain.
+    pub fn resample(samples: &[f32], from_rate: f32, to_rate: f32) -> Vec<f32> {
+        if samples.is_empty() || from_rate <= 0.0 || to_rate <= 0.0 || from_rate == to_rate {
+            return samples.to_vec();
         }
         
-        // Normalize to prevent clipping
-        let max_sample = samples.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
-        if max_sample > 0.0 {
-            let gain = 0.5 / max_sample; // Normalize to 50% of full scale
-            for sample in &mut samples {
-                *sample *= gain;
+        let ratio = to_rate as f64 / from_rate as f64;
+        
+        // Cutoff in cycles per input sample, scaled down when decimating
+        let cutoff = 0.5 * RESAMPLE_PASSBAND * ratio.min(1.0);
+        
+        // Kernel half-width in input samples
+        let half_width = SINC_ZERO_CROSSINGS as f64 / (2.0 * cutoff);
+        let window = KaiserTable::new();
+        let gain = 2.0 * cutoff / ratio;
+        
+        let output_len = (samples.len() as f64 * ratio).ceil() as usize;
+        let mut output = Vec::with_capacity(output_len);
+        
+        for n in 0..output_len {
+            // Output sample position in input sample units
+            let position = n as f64 / ratio;
+            let first = (position - half_width).ceil().max(0.0) as usize;
+            let last = ((position + half_width).floor() as usize).min(samples.len() - 1);
+            
+            let mut sum = 0.0f64;
+            for (k, &sample) in samples.iter().enumerate().take(last + 1).skip(first) {
+                let distance = position - k as f64;
+                sum += sample as f64 * sinc(2.0 * cutoff * distance) * window.at(distance / half_width);
             }
+            output.push((sum * gain) as f32);
         }
         
-        Ok(samples)
+        output
     }
     
-    /// Load all cabinet impulse responses from directory
-    /// Returns a map of cabinet type to IR samples
-    pub fn load_cabinet_irs(ir_directory: &Path) -> Vec<(super::cabinet::CabinetType, Vec<f32>)> {
+    /// Pick an IR for every factory cabinet from an indexed IR library
+    /// Returns a map of cabinet type to IR samples; cabinets without a matching
+    /// file, or whose file fails to load, get a synthesized fallback IR
+    pub fn load_cabinet_irs(library: &IrLibrary, sample_rate: f32) -> Vec<(CabinetType, Vec<f32>)> {
         let mut irs = Vec::new();
         
-        let ir_files = [
-            (super::cabinet::CabinetType::Marshall4x12V30, "marshall_4x12_v30.wav"),
-            (super::cabinet::CabinetType::FenderTwin2x12, "fender_twin_2x12.wav"),
-            (super::cabinet::CabinetType::VoxAC30Blue, "vox_ac30_blue.wav"),
-            (super::cabinet::CabinetType::Mesa4x12Recto, "mesa_4x12_recto.wav"),
-        ];
-        
-        for (cabinet_type, filename) in &ir_files {
-            let file_path = ir_directory.join(filename);
+        for (cabinet_type, queries) in FACTORY_CABINET_QUERIES {
+            let entry = queries.iter().find_map(|query| library.search(query).into_iter().next());
+            let loaded = entry.map(|entry| (entry, Self::load_ir_file(&entry.path, sample_rate, &IrPrepSettings::default())));
             
-            match Self::load_ir_file(&file_path) {
-                Ok(samples) => {
-                    println!("✅ Loaded IR: {} ({} samples)", filename, samples.len());
-                    irs.push((*cabinet_type, samples));
+            match loaded {
+                Some((entry, Ok(mut impulse_response))) => {
+                    // Factory cabinets are mono - keep the first channel
+                    let samples = impulse_response.channels.swap_remove(0);
+                    println!("✅ Loaded IR: {} ({} samples)", entry.path.display(), samples.len());
+                    irs.push((cabinet_type, samples));
+                }
+                Some((entry, Err(e))) => {
+                    println!("⚠️  Failed to load {}: {}", entry.path.display(), e);
+                    irs.push((cabinet_type, Self::create_fallback_ir(cabinet_type)));
                 }
-                Err(e) => {
-                    println!("⚠️  Failed to load {}: {:?}", filename, e);
+                None => {
+                    println!("⚠️  No IR in library for {:?}", cabinet_type);
                     // Use fallback - shorter version of built-in IR
-                    let fallback = Self::create_fallback_ir(*cabinet_type);
-                    irs.push((*cabinet_type, fallback));
+                    irs.push((cabinet_type, Self::create_fallback_ir(cabinet_type)));
                 }
             }
         }
@@ -152,9 +287,7 @@ impl IrLoader {
     }
     
     /// Create fallback IR if file loading fails
-    fn create_fallback_ir(cabinet_type: super::cabinet::CabinetType) -> Vec<f32> {
-        use super::cabinet::CabinetType;
-        
+    fn create_fallback_ir(cabinet_type: CabinetType) -> Vec<f32> {
         // Create a simple but musical IR based on cabinet type
         let mut ir = vec![0.0f32; 512]; // Shorter fallback IR
         
@@ -199,7 +332,7 @@ impl IrLoader {
                     ir[i] = decay * tight * 0.75;
                 }
             }
-            CabinetType::Direct => {
+            CabinetType::Direct | CabinetType::UserIr => {
                 // Direct: impulse (no processing)
                 ir[0] = 1.0;
                 for i in 1..512 {
@@ -212,23 +345,564 @@ impl IrLoader {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Size of a RIFF chunk header: 4-byte tag + 4-byte little-endian size
+const CHUNK_HEADER_SIZE: usize = 8;
+
+/// Size field value meaning "see the ds64 chunk" in RF64/BW64 files
+const RF64_SIZE_PLACEHOLDER: u64 = 0xFFFF_FFFF;
+
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Bytes 2..16 of every KSDATAFORMAT_SUBTYPE GUID - the first two hold the format code
+const SUBFORMAT_GUID_SUFFIX: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// Sample encoding inside the `data` chunk
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SampleFormat {
+    /// Unsigned 8-bit integer PCM
+    U8,
+    /// Signed little-endian integer PCM of 2, 3 or 4 bytes
+    Int(usize),
+    F32,
+    F64,
+}
+
+/// Decoded `fmt ` chunk
+#[derive(Debug, Clone, Copy)]
+struct WavFormat {
+    sample_format: SampleFormat,
+    channels: usize,
+    sample_rate: u32,
+    /// Bytes per frame - all channels of one sample
+    block_align: usize,
+}
+
+impl WavFormat {
+    /// Parse a `fmt ` chunk body starting at file offset `offset`
+    fn parse(chunk: &[u8], offset: usize) -> Result<Self, IrLoadError> {
+        let invalid = |reason| IrLoadError::InvalidChunk { offset, tag: *b"fmt ", reason };
+        if chunk.len() < 16 {
+            return Err(invalid("fmt chunk shorter than 16 bytes"));
+        }
+        
+        let mut format_code = u16::from_le_bytes([chunk[0], chunk[1]]);
+        let channels = u16::from_le_bytes([chunk[2], chunk[3]]) as usize;
+        let sample_rate = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        let block_align = u16::from_le_bytes([chunk[12], chunk[13]]) as usize;
+        let bits_per_sample = u16::from_le_bytes([chunk[14], chunk[15]]);
+        
+        if format_code == WAVE_FORMAT_EXTENSIBLE {
+            // cbSize (2), valid bits (2), channel mask (4), sub format GUID (16)
+            if chunk.len() < 40 {
+                return Err(invalid("extensible fmt chunk shorter than 40 bytes"));
+            }
+            let guid = &chunk[24..40];
+            if guid[2..] != SUBFORMAT_GUID_SUFFIX {
+                let mut sub_format = [0u8; 16];
+                sub_format.copy_from_slice(guid);
+                return Err(IrLoadError::UnsupportedSubFormat(sub_format));
+            }
+            format_code = u16::from_le_bytes([guid[0], guid[1]]);
+        }
+        
+        if channels == 0 {
+            return Err(invalid("zero channels"));
+        }
+        if sample_rate == 0 {
+            return Err(invalid("zero sample rate"));
+        }
+        
+        let unsupported = IrLoadError::UnsupportedFormat { format_code, bits_per_sample };
+        let sample_format = match (format_code, bits_per_sample) {
+            (WAVE_FORMAT_PCM, 8) => SampleFormat::U8,
+            (WAVE_FORMAT_PCM, 16) => SampleFormat::Int(2),
+            (WAVE_FORMAT_PCM, 24) => SampleFormat::Int(3),
+            (WAVE_FORMAT_PCM, 32) => SampleFormat::Int(4),
+            (WAVE_FORMAT_IEEE_FLOAT, 32) => SampleFormat::F32,
+            (WAVE_FORMAT_IEEE_FLOAT, 64) => SampleFormat::F64,
+            _ => return Err(unsupported),
+        };
+        
+        if block_align != channels * sample_format.bytes() {
+            return Err(invalid("block align doesn't match channels and sample size"));
+        }
+        
+        Ok(Self { sample_format, channels, sample_rate, block_align })
+    }
+    
+    /// De-interleave every complete frame to f32 in [-1, 1] - O(N)
+    fn decode_channels(&self, audio_data: &[u8]) -> Vec<Vec<f32>> {
+        let frames = audio_data.len() / self.block_align;
+        let sample_size = self.sample_format.bytes();
+        let mut channels = vec![Vec::with_capacity(frames); self.channels];
+        
+        for frame in audio_data.chunks_exact(self.block_align) {
+            for (channel, sample) in channels.iter_mut().zip(frame.chunks_exact(sample_size)) {
+                channel.push(self.sample_format.decode(sample));
+            }
+        }
+        channels
+    }
+}
+
+impl SampleFormat {
+    /// Container size of one sample in bytes
+    fn bytes(self) -> usize {
+        match self {
+            SampleFormat::U8 => 1,
+            SampleFormat::Int(bytes) => bytes,
+            SampleFormat::F32 => 4,
+            SampleFormat::F64 => 8,
+        }
+    }
+    
+    /// Decode one sample from the start of `bytes`
+    fn decode(self, bytes: &[u8]) -> f32 {
+        match self {
+            SampleFormat::U8 => (bytes[0] as f32 - 128.0) / 128.0,
+            SampleFormat::Int(size) => {
+                // Left-align in an i32 so the sign bit lands in place, then scale
+                let mut word = [0u8; 4];
+                word[4 - size..].copy_from_slice(&bytes[..size]);
+                (i32::from_le_bytes(word) as f64 / 2147483648.0) as f32
+            }
+            SampleFormat::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            SampleFormat::F64 => {
+                let mut word = [0u8; 8];
+                word.copy_from_slice(&bytes[..8]);
+                f64::from_le_bytes(word) as f32
+            }
+        }
+    }
+}
+
+/// Read a four-character chunk tag
+fn read_tag(data: &[u8], offset: usize) -> Result<[u8; 4], IrLoadError> {
+    data.get(offset..offset + 4)
+        .map(|tag| [tag[0], tag[1], tag[2], tag[3]])
+        .ok_or(IrLoadError::Truncated { offset, needed: 4, available: data.len().saturating_sub(offset) })
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, IrLoadError> {
+    read_tag(data, offset).map(u32::from_le_bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, IrLoadError> {
+    let bytes = data
+        .get(offset..offset + 8)
+        .ok_or(IrLoadError::Truncated { offset, needed: 8, available: data.len().saturating_sub(offset) })?;
+    let mut word = [0u8; 8];
+    word.copy_from_slice(bytes);
+    Ok(u64::from_le_bytes(word))
+}
+
+/// Normalized sinc function sin(pi x) / (pi x)
+pub(super) fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-12 {
+        1.0
+    } else {
+        let pi_x = std::f64::consts::PI * x;
+        pi_x.sin() / pi_x
+    }
+}
+
+/// Kaiser window tabulated once per resample, so the inner loop doesn't
+/// evaluate a Bessel series for every tap
+struct KaiserTable {
+    values: Vec<f64>,
+}
+
+impl KaiserTable {
+    fn new() -> Self {
+        let window_norm = bessel_i0(KAISER_BETA);
+        let values = (0..=KAISER_TABLE_SIZE)
+            .map(|i| {
+                let position = i as f64 / KAISER_TABLE_SIZE as f64;
+                bessel_i0(KAISER_BETA * (1.0 - position * position).max(0.0).sqrt()) / window_norm
+            })
+            .collect();
+        Self { values }
+    }
+
+    /// Window value at `position` in -1..1 across the kernel - O(1)
+    fn at(&self, position: f64) -> f64 {
+        let index = position.abs() * KAISER_TABLE_SIZE as f64;
+        let whole = index as usize;
+        if whole >= KAISER_TABLE_SIZE {
+            return self.values[KAISER_TABLE_SIZE];
+        }
+        let frac = index - whole as f64;
+        self.values[whole] + frac * (self.values[whole + 1] - self.values[whole])
+    }
+}
+
+/// Zeroth-order modified Bessel function of the first kind, for the Kaiser window
+pub(super) fn bessel_i0(x: f64) -> f64 {
+    let half_x = x / 2.0;
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    for k in 1..50 {
+        term *= half_x / k as f64;
+        let contribution = term * term;
+        sum += contribution;
+        if contribution < sum * 1e-16 {
+            break;
+        }
+    }
+    sum
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum IrLoadError {
-    FileNotFound,
-    ReadError,
-    InvalidFormat,
-    UnsupportedFormat,
+    /// No file at the given path
+    FileNotFound(PathBuf),
+    
+    /// File exists but couldn't be read
+    ReadError(String),
+    
+    /// A chunk or field extends past the end of the file
+    Truncated { offset: usize, needed: u64, available: usize },
+    
+    /// Wrong four-character tag where a fixed one is required
+    BadTag { offset: usize, expected: [u8; 4], found: [u8; 4] },
+    
+    /// A chunk the decoder needs is not present
+    MissingChunk([u8; 4]),
+    
+    /// A chunk is present but its contents are inconsistent
+    InvalidChunk { offset: usize, tag: [u8; 4], reason: &'static str },
+    
+    /// Format code / bit depth combination the decoder doesn't handle
+    UnsupportedFormat { format_code: u16, bits_per_sample: u16 },
+    
+    /// WAVE_FORMAT_EXTENSIBLE with a non-standard sub format GUID
+    UnsupportedSubFormat([u8; 16]),
+    
+    /// Valid file without a single complete sample frame
+    Empty,
+    
+    /// Channel count other than mono, stereo or a 4-channel true-stereo matrix
+    UnsupportedChannelCount(usize),
 }
 
 impl std::fmt::Display for IrLoadError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tag = |tag: &[u8; 4]| String::from_utf8_lossy(tag).into_owned();
         match self {
-            IrLoadError::FileNotFound => write!(f, "IR file not found"),
-            IrLoadError::ReadError => write!(f, "Failed to read IR file"),
-            IrLoadError::InvalidFormat => write!(f, "Invalid WAV format"),
-            IrLoadError::UnsupportedFormat => write!(f, "Unsupported audio format"),
+            IrLoadError::FileNotFound(path) => write!(f, "IR file not found: {}", path.display()),
+            IrLoadError::ReadError(error) => write!(f, "Failed to read IR file: {}", error),
+            IrLoadError::Truncated { offset, needed, available } => write!(
+                f,
+                "File truncated at offset {}: needed {} bytes, {} available",
+                offset, needed, available
+            ),
+            IrLoadError::BadTag { offset, expected, found } => write!(
+                f,
+                "Expected '{}' at offset {}, found '{}'",
+                tag(expected), offset, tag(found)
+            ),
+            IrLoadError::MissingChunk(chunk) => write!(f, "Missing '{}' chunk", tag(chunk)),
+            IrLoadError::InvalidChunk { offset, tag: chunk, reason } => {
+                write!(f, "Invalid '{}' chunk at offset {}: {}", tag(chunk), offset, reason)
+            }
+            IrLoadError::UnsupportedFormat { format_code, bits_per_sample } => write!(
+                f,
+                "Unsupported WAV format code 0x{:04X} with {} bits per sample",
+                format_code, bits_per_sample
+            ),
+            IrLoadError::UnsupportedSubFormat(guid) => {
+                write!(f, "Unsupported WAVE_FORMAT_EXTENSIBLE sub format {:02X?}", guid)
+            }
+            IrLoadError::Empty => write!(f, "WAV file contains no audio"),
+            IrLoadError::UnsupportedChannelCount(channels) => write!(
+                f,
+                "Unsupported IR with {} channels - expected 1, 2 or 4",
+                channels
+            ),
         }
     }
 }
 
-impl std::error::Error for IrLoadError {}
\ No newline at end of file
+impl std::error::Error for IrLoadError {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    /// Test tone - phase computed in f64 so the signal itself has no broadband error
+    fn sine(frequency: f64, sample_rate: f64, len: usize) -> Vec<f32> {
+        (0..len).map(|n| (2.0 * PI * frequency * n as f64 / sample_rate).sin() as f32).collect()
+    }
+
+    /// Peak level away from the edges, where the kernel sees the full signal
+    fn interior_peak(samples: &[f32]) -> f32 {
+        let margin = samples.len() / 4;
+        samples[margin..samples.len() - margin].iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+    }
+
+    /// Chunk with RIFF padding applied
+    fn chunk(tag: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut bytes = tag.to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    /// Basic 16-byte `fmt ` chunk body
+    fn fmt_body(format_code: u16, channels: u16, bits: u16) -> Vec<u8> {
+        let block_align = channels * bits / 8;
+        let mut body = Vec::new();
+        body.extend_from_slice(&format_code.to_le_bytes());
+        body.extend_from_slice(&channels.to_le_bytes());
+        body.extend_from_slice(&48000u32.to_le_bytes());
+        body.extend_from_slice(&(48000 * block_align as u32).to_le_bytes());
+        body.extend_from_slice(&block_align.to_le_bytes());
+        body.extend_from_slice(&bits.to_le_bytes());
+        body
+    }
+
+    /// WAVE_FORMAT_EXTENSIBLE `fmt ` chunk body wrapping `sub_format`
+    fn extensible_fmt_body(sub_format: u16, channels: u16, bits: u16) -> Vec<u8> {
+        let mut body = fmt_body(WAVE_FORMAT_EXTENSIBLE, channels, bits);
+        body.extend_from_slice(&22u16.to_le_bytes());
+        body.extend_from_slice(&bits.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(&sub_format.to_le_bytes());
+        body.extend_from_slice(&SUBFORMAT_GUID_SUFFIX);
+        body
+    }
+
+    fn riff(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = chunks.concat();
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    /// Parse and return the first channel
+    fn parse(chunks: &[Vec<u8>]) -> Result<Vec<f32>, IrLoadError> {
+        IrLoader::parse_wav(&riff(chunks)).map(|mut ir| ir.channels.swap_remove(0))
+    }
+
+    #[test]
+    fn test_integer_pcm_depths() {
+        let u8_data = [255u8, 128, 0];
+        let samples = parse(&[chunk(b"fmt ", &fmt_body(1, 1, 8)), chunk(b"data", &u8_data)]).unwrap();
+        assert_eq!(samples, vec![127.0 / 128.0, 0.0, -1.0]);
+
+        let i16_data: Vec<u8> = [16384i16, -32768].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let samples = parse(&[chunk(b"fmt ", &fmt_body(1, 1, 16)), chunk(b"data", &i16_data)]).unwrap();
+        assert_eq!(samples, vec![0.5, -1.0]);
+
+        // 0x400000 = 0.5, 0xC00000 = -0.5
+        let i24_data = [0x00, 0x00, 0x40, 0x00, 0x00, 0xC0];
+        let samples = parse(&[chunk(b"fmt ", &fmt_body(1, 1, 24)), chunk(b"data", &i24_data)]).unwrap();
+        assert_eq!(samples, vec![0.5, -0.5]);
+
+        let i32_data: Vec<u8> = [i32::MIN, 1 << 30].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let samples = parse(&[chunk(b"fmt ", &fmt_body(1, 1, 32)), chunk(b"data", &i32_data)]).unwrap();
+        assert_eq!(samples, vec![-1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_float_formats_and_channels() {
+        let f32_data: Vec<u8> = [0.25f32, 0.75].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let samples = parse(&[chunk(b"fmt ", &fmt_body(3, 1, 32)), chunk(b"data", &f32_data)]).unwrap();
+        assert_eq!(samples, vec![0.25, 0.75]);
+
+        // Stereo 64-bit float is de-interleaved; a trailing partial frame is dropped
+        let f64_data: Vec<u8> = [0.5f64, -0.5, -0.125, 0.125, 1.0].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let bytes = riff(&[chunk(b"fmt ", &fmt_body(3, 2, 64)), chunk(b"data", &f64_data)]);
+        let impulse_response = IrLoader::parse_wav(&bytes).unwrap();
+        assert_eq!(impulse_response.channels, vec![vec![0.5, -0.125], vec![-0.5, 0.125]]);
+    }
+
+    #[test]
+    fn test_output_rows() {
+        let ir = |channels: usize| ImpulseResponse {
+            channels: (0..channels).map(|c| vec![c as f32]).collect(),
+            sample_rate: 48000.0,
+        };
+
+        // Mono feeds both outputs, stereo keeps its sides
+        assert_eq!(ir(1).output_row(1).channels, vec![vec![0.0]]);
+        assert_eq!(ir(2).output_row(0).channels, vec![vec![0.0]]);
+        assert_eq!(ir(2).output_row(1).channels, vec![vec![1.0]]);
+
+        // True stereo: [same side, opposite side] from the LL, LR, RL, RR matrix
+        assert_eq!(ir(4).output_row(0).channels, vec![vec![0.0], vec![2.0]]);
+        assert_eq!(ir(4).output_row(1).channels, vec![vec![3.0], vec![1.0]]);
+    }
+
+    #[test]
+    fn test_extensible_format() {
+        let i24_data = [0x00, 0x00, 0x40];
+        let samples = parse(&[chunk(b"fmt ", &extensible_fmt_body(1, 1, 24)), chunk(b"data", &i24_data)]).unwrap();
+        assert_eq!(samples, vec![0.5]);
+
+        let f32_data = 0.5f32.to_le_bytes();
+        let samples = parse(&[chunk(b"fmt ", &extensible_fmt_body(3, 1, 32)), chunk(b"data", &f32_data)]).unwrap();
+        assert_eq!(samples, vec![0.5]);
+
+        let mut body = extensible_fmt_body(1, 1, 16);
+        body[30] = 0x11; // Corrupt the GUID
+        let result = parse(&[chunk(b"fmt ", &body), chunk(b"data", &[0, 0])]);
+        assert!(matches!(result, Err(IrLoadError::UnsupportedSubFormat(_))));
+    }
+
+    #[test]
+    fn test_chunk_walking_with_odd_padding() {
+        // Odd-sized metadata chunks before and between the ones we need
+        let i16_data: Vec<u8> = [8192i16].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let samples = parse(&[
+            chunk(b"JUNK", &[1, 2, 3]),
+            chunk(b"fmt ", &fmt_body(1, 1, 16)),
+            chunk(b"LIST", b"INFOISFT\x05\0\0\0abcde"),
+            chunk(b"data", &i16_data),
+        ])
+        .unwrap();
+        assert_eq!(samples, vec![0.25]);
+
+        // `data` may come before `fmt `
+        let samples = parse(&[chunk(b"data", &i16_data), chunk(b"fmt ", &fmt_body(1, 1, 16))]).unwrap();
+        assert_eq!(samples, vec![0.25]);
+    }
+
+    #[test]
+    fn test_rf64() {
+        let f32_data: Vec<u8> = [0.5f32, -0.5].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut ds64 = Vec::new();
+        ds64.extend_from_slice(&0u64.to_le_bytes()); // RIFF size, unused
+        ds64.extend_from_slice(&(f32_data.len() as u64).to_le_bytes());
+        ds64.extend_from_slice(&2u64.to_le_bytes());
+        ds64.extend_from_slice(&0u32.to_le_bytes());
+
+        let mut data_chunk = b"data".to_vec();
+        data_chunk.extend_from_slice(&u32::MAX.to_le_bytes());
+        data_chunk.extend_from_slice(&f32_data);
+
+        let mut bytes = riff(&[chunk(b"ds64", &ds64), chunk(b"fmt ", &fmt_body(3, 1, 32)), data_chunk.clone()]);
+        bytes[0..4].copy_from_slice(b"RF64");
+        bytes[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(IrLoader::parse_wav(&bytes).unwrap().channels, vec![vec![0.5, -0.5]]);
+
+        bytes[0..4].copy_from_slice(b"BW64");
+        assert_eq!(IrLoader::parse_wav(&bytes).unwrap().channels, vec![vec![0.5, -0.5]]);
+
+        // Without ds64 the placeholder size can't be resolved
+        let mut bytes = riff(&[chunk(b"fmt ", &fmt_body(3, 1, 32)), data_chunk]);
+        bytes[0..4].copy_from_slice(b"RF64");
+        assert!(matches!(IrLoader::parse_wav(&bytes), Err(IrLoadError::Truncated { .. })));
+    }
+
+    #[test]
+    fn test_detailed_errors() {
+        let fmt = chunk(b"fmt ", &fmt_body(1, 1, 16));
+        let data = chunk(b"data", &[0, 0]);
+
+        let mut bytes = riff(&[fmt.clone(), data.clone()]);
+        bytes[8..12].copy_from_slice(b"AVI ");
+        assert_eq!(
+            IrLoader::parse_wav(&bytes),
+            Err(IrLoadError::BadTag { offset: 8, expected: *b"WAVE", found: *b"AVI " })
+        );
+
+        assert_eq!(parse(std::slice::from_ref(&data)), Err(IrLoadError::MissingChunk(*b"fmt ")));
+        assert_eq!(parse(std::slice::from_ref(&fmt)), Err(IrLoadError::MissingChunk(*b"data")));
+        assert_eq!(parse(&[fmt.clone(), chunk(b"data", &[0])]), Err(IrLoadError::Empty));
+        assert_eq!(
+            parse(&[chunk(b"fmt ", &fmt_body(2, 1, 4)), data.clone()]),
+            Err(IrLoadError::UnsupportedFormat { format_code: 2, bits_per_sample: 4 })
+        );
+
+        let mut bad_align = fmt_body(1, 2, 16);
+        bad_align[12] = 3;
+        assert!(matches!(
+            parse(&[chunk(b"fmt ", &bad_align), data.clone()]),
+            Err(IrLoadError::InvalidChunk { offset: 12, .. })
+        ));
+
+        // Data chunk claims more bytes than the file holds
+        let mut bytes = riff(&[fmt, data]);
+        let len = bytes.len();
+        bytes[len - 6..len - 2].copy_from_slice(&100u32.to_le_bytes());
+        assert_eq!(
+            IrLoader::parse_wav(&bytes),
+            Err(IrLoadError::Truncated { offset: len - 10, needed: 100, available: 2 })
+        );
+    }
+
+    #[test]
+    fn test_malformed_input_never_panics() {
+        // Every prefix and every single-byte corruption of a valid file
+        let bytes = riff(&[
+            chunk(b"fmt ", &extensible_fmt_body(1, 2, 24)),
+            chunk(b"LIST", &[0; 5]),
+            chunk(b"data", &[0x12; 18]),
+        ]);
+        for len in 0..bytes.len() {
+            let _ = IrLoader::parse_wav(&bytes[..len]);
+        }
+        for position in 0..bytes.len() {
+            for value in [0x00, 0x01, 0x7F, 0x80, 0xFF] {
+                let mut corrupted = bytes.clone();
+                corrupted[position] = value;
+                let _ = IrLoader::parse_wav(&corrupted);
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_rate_is_passthrough() {
+        let samples = vec![1.0, 0.5, -0.25];
+        assert_eq!(IrLoader::resample(&samples, 48000.0, 48000.0), samples);
+    }
+
+    #[test]
+    fn test_resampled_sine_matches_target_rate() {
+        // 1 kHz at 44.1 kHz converted to 48 kHz must be the same 1 kHz tone at 48 kHz,
+        // with taps scaled by 44.1 / 48 to keep the IR's frequency response
+        let output = IrLoader::resample(&sine(1000.0, 44100.0, 4410), 44100.0, 48000.0);
+        assert_eq!(output.len(), 4800);
+
+        let expected = sine(1000.0, 48000.0, 4800);
+        let scale = 44100.0 / 48000.0;
+        for n in 1200..3600 {
+            assert!((output[n] - expected[n] * scale).abs() < 1e-3, "sample {}", n);
+        }
+    }
+
+    #[test]
+    fn test_downsampling_rejects_content_above_nyquist() {
+        // 30 kHz is above the 22.05 kHz Nyquist limit of the target rate
+        let output = IrLoader::resample(&sine(30000.0, 96000.0, 9600), 96000.0, 44100.0);
+        assert!(interior_peak(&output) < 1e-4);
+
+        // Passband content survives at the gain-compensated level
+        let output = IrLoader::resample(&sine(5000.0, 96000.0, 9600), 96000.0, 44100.0);
+        let expected = 96000.0 / 44100.0;
+        assert!((interior_peak(&output) - expected).abs() < expected * 1e-2);
+    }
+
+    #[test]
+    fn test_dc_gain_is_preserved() {
+        // Sum of taps is the IR's gain at DC and must not depend on the sample rate
+        let ir: Vec<f32> = (0..512).map(|n| (-(n as f32) / 40.0).exp()).collect();
+        let dc_gain: f32 = ir.iter().sum();
+
+        for target_rate in [48000.0, 88200.0, 96000.0, 22050.0] {
+            let resampled = IrLoader::resample(&ir, 44100.0, target_rate);
+            let resampled_gain: f32 = resampled.iter().sum();
+            assert!((resampled_gain - dc_gain).abs() < dc_gain * 1e-2, "{} Hz", target_rate);
+        }
+    }
+}
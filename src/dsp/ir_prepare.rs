@@ -0,0 +1,394 @@
+use super::filters::BiquadFilter;
+use super::ir_loader::ImpulseResponse;
+use num_complex::Complex;
+use realfft::RealFftPlanner;
+use std::f64::consts::PI;
+
+/// Hard cap on IR length in samples, whatever the length setting (4 seconds at 48kHz)
+pub const MAX_IR_SAMPLES: usize = 192000;
+
+/// Level reference used when normalizing an impulse response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrNormalization {
+    /// Loudest sample at the target level - never clips, but level depends on the IR's shape
+    Peak,
+
+    /// Broadband gain (the IR's energy) at the target level
+    Rms,
+
+    /// K-weighted energy (ITU-R BS.1770) at the target level - cabs sound equally loud
+    Loudness,
+}
+
+impl nih_plug::prelude::Enum for IrNormalization {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Peak",
+            "RMS",
+            "Loudness",
+        ]
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "peak",
+            "rms",
+            "loudness",
+        ])
+    }
+
+    fn to_index(self) -> usize {
+        match self {
+            IrNormalization::Peak => 0,
+            IrNormalization::Rms => 1,
+            IrNormalization::Loudness => 2,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => IrNormalization::Peak,
+            1 => IrNormalization::Rms,
+            2 => IrNormalization::Loudness,
+            _ => IrNormalization::Peak, // Default fallback
+        }
+    }
+}
+
+/// How an impulse response is cleaned up after loading and before resampling
+///
+/// Every step uses one cut point or gain for all channels, so stereo IRs and
+/// true-stereo matrices keep their relative timing and balance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IrPrepSettings {
+    /// Drop leading and trailing samples quieter than this, relative to the IR peak -
+    /// `None` keeps the file's silence
+    pub trim_threshold_db: Option<f32>,
+
+    /// Cut the IR down to this length
+    pub length_ms: f32,
+
+    /// Half-Hann fade at the end of the IR, so a cut tail doesn't click
+    pub fade_out_ms: f32,
+
+    /// Replace the IR with its minimum-phase equivalent - same magnitude response,
+    /// energy moved to the start for lower perceived latency
+    pub minimum_phase: bool,
+
+    pub normalization: IrNormalization,
+
+    /// Level the normalization measure is brought to, in dBFS
+    pub target_db: f32,
+}
+
+impl Default for IrPrepSettings {
+    fn default() -> Self {
+        Self {
+            trim_threshold_db: Some(-60.0),
+            length_ms: 4000.0,
+            fade_out_ms: 10.0,
+            minimum_phase: false,
+            normalization: IrNormalization::Peak,
+            target_db: -6.0,
+        }
+    }
+}
+
+impl IrPrepSettings {
+    /// Run the preparation steps in place - O(N log N), allocates, background only
+    /// Order: trim, minimum phase, length cut with fade-out, normalization
+    pub fn apply(&self, impulse_response: &mut ImpulseResponse) {
+        let channels = &mut impulse_response.channels;
+
+        if let Some(threshold_db) = self.trim_threshold_db {
+            trim_silence(channels, threshold_db);
+        }
+
+        if self.minimum_phase {
+            for channel in channels.iter_mut() {
+                *channel = minimum_phase(channel);
+            }
+        }
+
+        let max_length = ((self.length_ms * 0.001 * impulse_response.sample_rate) as usize).clamp(1, MAX_IR_SAMPLES);
+        for channel in channels.iter_mut() {
+            channel.truncate(max_length);
+        }
+
+        let fade_length = (self.fade_out_ms * 0.001 * impulse_response.sample_rate) as usize;
+        fade_out(channels, fade_length);
+
+        let level = match self.normalization {
+            IrNormalization::Peak => channels.iter().flatten().map(|s| s.abs()).fold(0.0f32, f32::max),
+            IrNormalization::Rms => channels.iter().map(|channel| energy(channel)).fold(0.0f32, f32::max).sqrt(),
+            IrNormalization::Loudness => channels
+                .iter()
+                .map(|channel| k_weighted_energy(channel, impulse_response.sample_rate))
+                .fold(0.0f32, f32::max)
+                .sqrt(),
+        };
+        if level > 0.0 {
+            let gain = 10.0f32.powf(self.target_db / 20.0) / level;
+            for sample in channels.iter_mut().flatten() {
+                *sample *= gain;
+            }
+        }
+    }
+}
+
+/// Cut every channel to the span where any channel exceeds `threshold_db` below the peak
+fn trim_silence(channels: &mut [Vec<f32>], threshold_db: f32) {
+    let peak = channels.iter().flatten().map(|s| s.abs()).fold(0.0f32, f32::max);
+    if peak <= 0.0 {
+        return;
+    }
+
+    let threshold = peak * 10.0f32.powf(threshold_db / 20.0);
+    let loud = |channel: &Vec<f32>| channel.iter().position(|s| s.abs() >= threshold);
+    let loud_end = |channel: &Vec<f32>| channel.iter().rposition(|s| s.abs() >= threshold).map(|i| i + 1);
+    let start = channels.iter().filter_map(loud).min().unwrap_or(0);
+    let end = channels.iter().filter_map(loud_end).max().unwrap_or(0);
+
+    for channel in channels.iter_mut() {
+        channel.truncate(end);
+        channel.drain(..start.min(channel.len()));
+    }
+}
+
+/// Half-Hann fade over the last `fade_length` samples - at most half of the IR,
+/// so short IRs keep their attack
+fn fade_out(channels: &mut [Vec<f32>], fade_length: usize) {
+    for channel in channels.iter_mut() {
+        let fade_length = fade_length.min(channel.len() / 2);
+        let fade_start = channel.len() - fade_length;
+        for (i, sample) in channel[fade_start..].iter_mut().enumerate() {
+            let phase = (i + 1) as f32 / fade_length as f32;
+            *sample *= 0.5 + 0.5 * (std::f32::consts::PI * phase).cos();
+        }
+    }
+}
+
+fn energy(samples: &[f32]) -> f32 {
+    samples.iter().map(|s| s * s).sum()
+}
+
+/// Energy of the IR through the BS.1770 K-weighting filter - O(N)
+/// The filters' own ringing is included by running them on into a short silent tail.
+fn k_weighted_energy(samples: &[f32], sample_rate: f32) -> f32 {
+    let [mut shelf, mut high_pass] = k_weighting_filters(sample_rate);
+    let tail = std::iter::repeat_n(0.0, (sample_rate * 0.1) as usize);
+    samples
+        .iter()
+        .copied()
+        .chain(tail)
+        .map(|sample| high_pass.process(shelf.process(sample)))
+        .map(|sample| sample * sample)
+        .sum()
+}
+
+/// BS.1770 pre-filter (+4 dB high shelf) and RLB high-pass, from the analog
+/// prototypes so any sample rate gets the same curve
+fn k_weighting_filters(sample_rate: f32) -> [BiquadFilter; 2] {
+    let sample_rate = sample_rate as f64;
+
+    let k = (PI * 1681.974450955533 / sample_rate).tan();
+    let q = 0.7071752369554193;
+    let vh = 10.0f64.powf(3.99984385397 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let mut shelf = BiquadFilter::new();
+    shelf.set_coefficients(
+        ((vh + vb * k / q + k * k) / a0) as f32,
+        (2.0 * (k * k - vh) / a0) as f32,
+        ((vh - vb * k / q + k * k) / a0) as f32,
+        (2.0 * (k * k - 1.0) / a0) as f32,
+        ((1.0 - k / q + k * k) / a0) as f32,
+    );
+
+    let k = (PI * 38.13547087613982 / sample_rate).tan();
+    let q = 0.5003270373253953;
+    let a0 = 1.0 + k / q + k * k;
+    let mut high_pass = BiquadFilter::new();
+    high_pass.set_coefficients(
+        1.0,
+        -2.0,
+        1.0,
+        (2.0 * (k * k - 1.0) / a0) as f32,
+        ((1.0 - k / q + k * k) / a0) as f32,
+    );
+
+    [shelf, high_pass]
+}
+
+/// Minimum-phase version of an IR by the real-cepstrum method - O(N log N)
+/// The log magnitude spectrum's cepstrum is folded onto positive quefrencies and
+/// exponentiated back, which keeps |H| and makes the phase minimal. The FFT is
+/// padded 4x so cepstral aliasing stays negligible.
+pub fn minimum_phase(samples: &[f32]) -> Vec<f32> {
+    if samples.is_empty() {
+        return Vec::new();
+    }
+
+    let fft_size = (samples.len() * 4).next_power_of_two().max(64);
+    let mut planner = RealFftPlanner::<f64>::new();
+    let forward = planner.plan_fft_forward(fft_size);
+    let inverse = planner.plan_fft_inverse(fft_size);
+
+    let mut time = vec![0.0f64; fft_size];
+    for (t, s) in time.iter_mut().zip(samples) {
+        *t = *s as f64;
+    }
+    let mut spectrum = forward.make_output_vec();
+    if forward.process(&mut time, &mut spectrum).is_err() {
+        return samples.to_vec();
+    }
+
+    // Floor the magnitude 120 dB below the peak so spectral nulls don't blow up the log
+    let peak = spectrum.iter().map(|bin| bin.norm()).fold(0.0f64, f64::max);
+    if peak <= 0.0 {
+        return samples.to_vec();
+    }
+    let floor = peak * 1e-6;
+    for bin in spectrum.iter_mut() {
+        *bin = Complex::new(bin.norm().max(floor).ln(), 0.0);
+    }
+
+    // Real cepstrum, folded: c[0], 2c[n] for 0 < n < N/2, c[N/2], then zeros
+    let mut cepstrum = inverse.make_output_vec();
+    if inverse.process(&mut spectrum, &mut cepstrum).is_err() {
+        return samples.to_vec();
+    }
+    let scale = 1.0 / fft_size as f64;
+    let half = fft_size / 2;
+    for (n, c) in cepstrum.iter_mut().enumerate() {
+        *c *= match n {
+            0 => scale,
+            n if n < half => 2.0 * scale,
+            n if n == half => scale,
+            _ => 0.0,
+        };
+    }
+
+    if forward.process(&mut cepstrum, &mut spectrum).is_err() {
+        return samples.to_vec();
+    }
+    for bin in spectrum.iter_mut() {
+        *bin = bin.exp();
+    }
+    // DC and Nyquist of a real signal's spectrum are real - drop rounding residue
+    spectrum[0].im = 0.0;
+    spectrum[half].im = 0.0;
+
+    if inverse.process(&mut spectrum, &mut time).is_err() {
+        return samples.to_vec();
+    }
+    time[..samples.len()].iter().map(|t| (t * scale) as f32).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ir(channels: Vec<Vec<f32>>) -> ImpulseResponse {
+        ImpulseResponse { channels, sample_rate: 48000.0 }
+    }
+
+    /// Only the step under test - everything else passes the IR through
+    fn passthrough() -> IrPrepSettings {
+        IrPrepSettings {
+            trim_threshold_db: None,
+            length_ms: 4000.0,
+            fade_out_ms: 0.0,
+            minimum_phase: false,
+            normalization: IrNormalization::Peak,
+            target_db: 0.0,
+        }
+    }
+
+    /// Magnitude response at `frequency` (cycles per sample) by direct DFT
+    fn magnitude(samples: &[f32], frequency: f64) -> f64 {
+        let bin = samples.iter().enumerate().fold(Complex::new(0.0, 0.0), |sum, (n, s)| {
+            sum + Complex::from_polar(*s as f64, -2.0 * PI * frequency * n as f64)
+        });
+        bin.norm()
+    }
+
+    #[test]
+    fn test_trim_keeps_channels_aligned() {
+        let mut impulse_response = ir(vec![
+            vec![0.0, 0.0, 0.0, 1.0, 0.5, 0.0, 0.0],
+            vec![0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0],
+        ]);
+        IrPrepSettings { trim_threshold_db: Some(-60.0), ..passthrough() }.apply(&mut impulse_response);
+
+        // The right channel starts earlier, so it sets the common start
+        assert_eq!(impulse_response.channels[0], vec![0.0, 1.0, 0.5]);
+        assert_eq!(impulse_response.channels[1], vec![0.5, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_length_and_fade_out() {
+        // 10 ms at 48 kHz, faded over the last 5 ms
+        let mut impulse_response = ir(vec![vec![1.0; 4800]]);
+        IrPrepSettings { length_ms: 10.0, fade_out_ms: 5.0, ..passthrough() }.apply(&mut impulse_response);
+
+        let channel = &impulse_response.channels[0];
+        assert_eq!(channel.len(), 480);
+        assert_eq!(channel[239], 1.0);
+        assert!(channel[240..].windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(channel[479].abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_minimum_phase_keeps_magnitude() {
+        // Linear-phase lowpass with its energy centred at sample 64
+        let linear_phase: Vec<f32> = (0..129)
+            .map(|n| {
+                let x = (n as f64 - 64.0) * 0.25;
+                let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+                let window = 0.5 - 0.5 * (2.0 * PI * n as f64 / 128.0).cos();
+                (0.25 * sinc * window) as f32
+            })
+            .collect();
+
+        let minimum = minimum_phase(&linear_phase);
+        assert_eq!(minimum.len(), linear_phase.len());
+
+        let peak_index = |samples: &[f32]| {
+            (0..samples.len()).max_by(|&a, &b| samples[a].abs().total_cmp(&samples[b].abs())).unwrap()
+        };
+        assert_eq!(peak_index(&linear_phase), 64);
+        assert!(peak_index(&minimum) < 16, "peak at {}", peak_index(&minimum));
+
+        // Same magnitude response through the passband and transition
+        for frequency in [0.0, 0.02, 0.05, 0.08, 0.11] {
+            let original = magnitude(&linear_phase, frequency);
+            let converted = magnitude(&minimum, frequency);
+            assert!((converted / original - 1.0).abs() < 1e-2, "{} vs {} at {}", converted, original, frequency);
+        }
+    }
+
+    #[test]
+    fn test_normalization_modes() {
+        let prepared = |normalization, channels: Vec<Vec<f32>>| {
+            let mut impulse_response = ir(channels);
+            IrPrepSettings { normalization, target_db: -6.0, ..passthrough() }.apply(&mut impulse_response);
+            impulse_response.channels
+        };
+        let target = 10.0f32.powf(-6.0 / 20.0);
+
+        let peak = prepared(IrNormalization::Peak, vec![vec![0.1, -0.2, 0.05]]);
+        assert!((peak[0][1].abs() - target).abs() < 1e-6);
+
+        let rms = prepared(IrNormalization::Rms, vec![vec![0.1, -0.2, 0.05], vec![0.01]]);
+        assert!((energy(&rms[0]).sqrt() - target).abs() < 1e-6);
+
+        // Equal broadband energy, but K-weighting hears the bright IR as louder,
+        // so loudness normalization turns it down against the dark one
+        let bright = vec![0.5, -0.5];
+        let dark = vec![0.5, 0.5];
+        let bright_gain = prepared(IrNormalization::Loudness, vec![bright.clone()])[0][0] / bright[0];
+        let dark_gain = prepared(IrNormalization::Loudness, vec![dark.clone()])[0][0] / dark[0];
+        assert!(bright_gain < dark_gain, "{} vs {}", bright_gain, dark_gain);
+    }
+}
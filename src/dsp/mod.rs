@@ -1,17 +1,125 @@
 // Building lightweight functional DSP without external dependencies for O(1) performance
 
 mod filters;
+mod fuzz;
 mod distortion;
 mod amp_sim;
 mod convolution;
+mod delay;
 mod cabinet;
+mod cabinet_loader;
+mod compressor;
 mod ir_loader;
+mod ir_library;
+mod ir_prepare;
+mod noise_gate;
+mod oversampling;
+mod pedals;
+mod stereo;
+mod tone_stack;
+mod user_ir;
 
-use filters::ToneStack;
-use distortion::AsymmetricClipper;
-use amp_sim::TubeStage;
+use amp_sim::{AmpHead, AmpSignals};
 use cabinet::CabinetSimulator;
-pub use cabinet::{CabinetType, CabinetSimulator as PublicCabinetSimulator};
+use compressor::Compressor;
+use fuzz::FuzzPedal;
+use noise_gate::NoiseGate;
+use oversampling::{Oversampler, MAX_OVERSAMPLING_RATIO};
+use pedals::OverdrivePedal;
+pub use amp_sim::DEFAULT_SAG;
+pub use cabinet::{CabinetSlotParams, CabinetType, CabinetSimulator as PublicCabinetSimulator, MAX_SLOT_DELAY_MS};
+pub use cabinet_loader::{CabinetLoader, PreparedCabinet};
+pub use compressor::CompressorSettings;
+pub use convolution::ConvolutionMode;
+pub use delay::{DelayMode, DelaySettings, DelaySync, DEFAULT_TEMPO_BPM, MAX_DELAY_MS};
+pub use distortion::WaveshaperMode;
+pub use fuzz::{FuzzModel, FuzzSettings};
+pub use ir_loader::{ImpulseResponse, IrLoadError, IrLoader, WavInfo};
+pub use ir_library::{IrEntry, IrFilter, IrLibrary};
+pub use ir_prepare::{IrNormalization, IrPrepSettings};
+pub use noise_gate::{NoiseGateSettings, MAX_GATE_LOOKAHEAD_MS};
+pub use oversampling::{OversamplingFactor, OversamplingPhase};
+pub use pedals::{PedalModel, PedalSettings};
+pub use user_ir::{PreparedChannelIr, PreparedEngines, UserIrSlot, UserIrState};
+pub use stereo::{ChannelLayout, ChannelMode, StereoProcessor};
+pub use tone_stack::ToneStackModel;
+
+/// Cabinet convolution block size - 256 samples for low latency
+pub const CABINET_BLOCK_SIZE: usize = 256;
+
+/// Maximum number of samples per processing block
+/// Hosts buffers are split into blocks of at most this size before processing
+pub const MAX_BLOCK_SIZE: usize = 64;
+
+/// Control rate for coefficient updates - filters are re-tuned at most once per this many samples
+const CONTROL_RATE: usize = 16;
+
+/// Lifecycle shared by every stateful DSP block
+/// `prepare` retunes everything that depends on the sample rate, sizes working buffers
+/// for blocks of up to `max_block` samples and clears all signal state. It runs from
+/// `initialize` before any audio, so it may allocate unless an implementation says otherwise.
+pub trait Prepare {
+    fn prepare(&mut self, sample_rate: f32, max_block: usize);
+}
+
+/// Parameter values for one processing block
+/// Smoothed parameters hold one value per sample, discrete ones are fixed for the block
+#[derive(Clone)]
+pub struct BlockParams {
+    pub input_gain: [f32; MAX_BLOCK_SIZE],
+    pub drive: [f32; MAX_BLOCK_SIZE],
+    pub output_gain: [f32; MAX_BLOCK_SIZE],
+    pub bass: [f32; MAX_BLOCK_SIZE],
+    pub mid: [f32; MAX_BLOCK_SIZE],
+    pub treble: [f32; MAX_BLOCK_SIZE],
+    /// Power amp drive as linear gain
+    pub master: [f32; MAX_BLOCK_SIZE],
+    pub presence: [f32; MAX_BLOCK_SIZE],
+    pub resonance: [f32; MAX_BLOCK_SIZE],
+    pub sag: [f32; MAX_BLOCK_SIZE],
+    pub cabinet_mix: [f32; MAX_BLOCK_SIZE],
+    pub stereo_width: [f32; MAX_BLOCK_SIZE],
+    /// External noise gate key from the sidechain input, used instead of the amp input
+    /// when `gate_sidechain` is set
+    pub gate_key: [f32; MAX_BLOCK_SIZE],
+    pub gate_sidechain: bool,
+    /// Whether the blend cabinet slot plays - cabinets themselves are switched
+    /// through `CabinetLoader`, not per block
+    pub blend_enabled: bool,
+    /// Level, pan, polarity and delay of the main (0) and blend (1) cabinet slots
+    pub cabinet_slots: [CabinetSlotParams; 2],
+}
+
+impl BlockParams {
+    /// Build a block with every smoothed parameter held constant
+    /// The power amp runs at unity master with a neutral voicing
+    pub fn constant(input_gain: f32, drive: f32, output_gain: f32, bass: f32, mid: f32, treble: f32) -> Self {
+        Self {
+            input_gain: [input_gain; MAX_BLOCK_SIZE],
+            drive: [drive; MAX_BLOCK_SIZE],
+            output_gain: [output_gain; MAX_BLOCK_SIZE],
+            bass: [bass; MAX_BLOCK_SIZE],
+            mid: [mid; MAX_BLOCK_SIZE],
+            treble: [treble; MAX_BLOCK_SIZE],
+            master: [1.0; MAX_BLOCK_SIZE],
+            presence: [0.0; MAX_BLOCK_SIZE],
+            resonance: [0.0; MAX_BLOCK_SIZE],
+            sag: [DEFAULT_SAG; MAX_BLOCK_SIZE],
+            cabinet_mix: [1.0; MAX_BLOCK_SIZE],
+            stereo_width: [0.0; MAX_BLOCK_SIZE],
+            gate_key: [0.0; MAX_BLOCK_SIZE],
+            gate_sidechain: false,
+            blend_enabled: false,
+            cabinet_slots: [CabinetSlotParams::default(), CabinetSlotParams::default()],
+        }
+    }
+}
+
+impl Default for BlockParams {
+    fn default() -> Self {
+        Self::constant(1.0, 1.0, 1.0, 0.0, 0.0, 0.0)
+    }
+}
 
 /// High-performance functional DSP processor achieving O(1) complexity
 /// Uses FunDSP's zero-cost abstractions for real-time audio processing
@@ -19,14 +127,29 @@ pub struct GuitarFxProcessor {
     /// Sample rate for DSP calculations
     sample_rate: f32,
     
-    /// Functional DSP graph for tone shaping - O(1) lookups via pre-computed coefficients
-    tonestack: ToneStack,
+    /// Detects at the amp input, gates after the preamp clipper
+    noise_gate: NoiseGate,
+    
+    /// Compressor pedal ahead of the preamp
+    compressor: Compressor,
+    
+    /// Fuzz pedal, first after the compressor - runs oversampled
+    fuzz: FuzzPedal,
     
-    /// Asymmetric clipper for tube-like distortion - O(1) waveshaping 
-    clipper: AsymmetricClipper,
+    /// Overdrive or boost pedal between the fuzz and the preamp - runs oversampled
+    pedal: OverdrivePedal,
     
-    /// Tube preamp simulation stage - O(1) nonlinear processing
-    tube_stage: TubeStage,
+    /// Preamp, tone stack and power amp - O(1) nonlinear processing per sample
+    amp_head: AmpHead,
+    
+    /// Master volume for the per-sample path - blocks carry their own values
+    master: f32,
+    
+    /// Plain or ADAA evaluation of the tube and clipper curves
+    waveshaper_mode: WaveshaperMode,
+    
+    /// Runs the amp head above the host rate to keep drive harmonics from aliasing
+    oversampler: Oversampler,
     
     /// Professional cabinet simulation with impulse response convolution
     cabinet_simulator: CabinetSimulator,
@@ -37,10 +160,15 @@ impl GuitarFxProcessor {
     pub fn new() -> Self {
         Self {
             sample_rate: 44100.0,
-            tonestack: ToneStack::new(44100.0),
-            clipper: AsymmetricClipper::new(),
-            tube_stage: TubeStage::new(),
-            cabinet_simulator: CabinetSimulator::new(256, 44100.0), // 256-sample blocks for low latency
+            noise_gate: NoiseGate::new(44100.0),
+            compressor: Compressor::new(44100.0),
+            fuzz: FuzzPedal::new(44100.0),
+            pedal: OverdrivePedal::new(44100.0),
+            amp_head: AmpHead::new(),
+            master: 1.0,
+            waveshaper_mode: WaveshaperMode::Plain,
+            oversampler: Oversampler::new(),
+            cabinet_simulator: CabinetSimulator::new(CABINET_BLOCK_SIZE, 44100.0),
         }
     }
     
@@ -48,46 +176,279 @@ impl GuitarFxProcessor {
     /// Pre-computes all filter coefficients for real-time performance
     pub fn initialize(&mut self, sample_rate: f32) {
         self.sample_rate = sample_rate;
-        self.tonestack = ToneStack::new(sample_rate);
-        self.cabinet_simulator = CabinetSimulator::new(256, sample_rate);
+        self.noise_gate.prepare(sample_rate, MAX_BLOCK_SIZE);
+        self.compressor.prepare(sample_rate, MAX_BLOCK_SIZE);
+        self.retune_amp();
+        self.oversampler.prepare(sample_rate, MAX_BLOCK_SIZE);
+        
+        self.cabinet_simulator.prepare(sample_rate, MAX_BLOCK_SIZE);
     }
     
     /// Process single sample through functional DSP chain - O(1) complexity
     /// Each stage uses pre-computed coefficients for constant-time processing
     pub fn process_sample(&mut self, input: f32, input_gain: f32, drive: f32, output_gain: f32) -> f32 {
-        // Functional composition: input -> preamp -> tone -> clipper -> cabinet -> output
+        self.process_sample_keyed(input, input, input_gain, drive, output_gain)
+    }
+    
+    /// Process single sample with the dynamics detectors driven by `key` - O(1) complexity
+    /// Linked stereo processing passes the louder of both channels as key so the
+    /// per-channel tube bias trackers stay identical and the stereo image doesn't shift
+    pub fn process_sample_keyed(&mut self, input: f32, key: f32, input_gain: f32, drive: f32, output_gain: f32) -> f32 {
+        // Functional composition: input -> preamp -> tone -> power amp -> cabinet -> output
         // Each operation is O(1) using lookup tables and pre-computed values
         input
-            .pipe(|x| x * input_gain)                         // O(1) multiplication
-            .pipe(|x| self.tube_stage.process(x, drive))      // O(1) tube simulation  
-            .pipe(|x| self.tonestack.process(x))              // O(1) filter processing
-            .pipe(|x| self.clipper.process(x, drive))         // O(1) waveshaping
+            .pipe(|x| self.process_amp_sample(x, key, input_gain, drive)) // O(1) amp section
+            .pipe(|x| self.process_output_sample(x, output_gain))         // O(1) amortized output section
+    }
+    
+    /// Amp section only: input gain -> compressor -> fuzz -> pedal -> preamp -> tone -> gate -> power amp - O(1) complexity
+    /// Split out so a mono amp can feed two output sections in mono-to-stereo layouts
+    /// Tone and power amp controls stay wherever the `update_*_controls` calls last put them
+    pub fn process_amp_sample(&mut self, input: f32, key: f32, input_gain: f32, drive: f32) -> f32 {
+        let mut gate = [0.0];
+        self.noise_gate.process_key(&[key], &mut gate);
+        
+        let mut sample = [input];
+        let mut key = [key];
+        self.noise_gate.delay_block(&mut sample, &mut key);
+        sample[0] *= input_gain;
+        key[0] *= input_gain;
+        self.compressor.process_block(&mut sample, &mut key);
+        
+        let master = [self.master];
+        let signals = AmpSignals { key: &key, drive: &[drive], master: &master, gate: &gate };
+        self.process_nonlinear_block(&mut sample, &signals, None);
+        sample[0]
+    }
+    
+    /// Output section only: cabinet -> output gain - O(1) amortized complexity
+    pub fn process_output_sample(&mut self, input: f32, output_gain: f32) -> f32 {
+        input
             .pipe(|x| self.cabinet_simulator.process_sample(x)) // O(1) amortized cabinet simulation
             .pipe(|x| x * output_gain)                        // O(1) output scaling
     }
     
+    /// Process a block in place - O(N) for N samples
+    /// Each stage runs over the whole block; filter coefficients are only
+    /// recalculated at control rate and only when the smoothed values change
+    pub fn process_block(&mut self, samples: &mut [f32], params: &BlockParams) {
+        let mut key = [0.0; MAX_BLOCK_SIZE];
+        key[..samples.len()].copy_from_slice(samples);
+        self.process_block_keyed(samples, &key[..samples.len()], params);
+    }
+    
+    /// Process a block with the dynamics detectors driven by `key` - O(N) complexity
+    pub fn process_block_keyed(&mut self, samples: &mut [f32], key: &[f32], params: &BlockParams) {
+        self.process_amp_block(samples, key, params);
+        self.process_output_block(samples, params);
+    }
+    
+    /// Amp section over a block: input gain -> compressor -> fuzz -> pedal -> preamp -> tone -> gate -> power amp
+    /// The noise gate detects on `key` - the clean input - or on the block's sidechain key
+    pub fn process_amp_block(&mut self, samples: &mut [f32], key: &[f32], params: &BlockParams) {
+        debug_assert!(samples.len() <= MAX_BLOCK_SIZE);
+        let len = samples.len();
+        
+        let gate_key = if params.gate_sidechain { &params.gate_key[..len] } else { key };
+        let mut gate = [0.0; MAX_BLOCK_SIZE];
+        self.noise_gate.process_key(gate_key, &mut gate[..len]);
+        
+        let mut gained_key = [0.0; MAX_BLOCK_SIZE];
+        gained_key[..len].copy_from_slice(key);
+        self.noise_gate.delay_block(samples, &mut gained_key[..len]);
+        for i in 0..len {
+            samples[i] *= params.input_gain[i];
+            gained_key[i] *= params.input_gain[i];
+        }
+        self.compressor.process_block(samples, &mut gained_key[..len]);
+        
+        let signals = AmpSignals {
+            key: &gained_key[..len],
+            drive: &params.drive[..len],
+            master: &params.master[..len],
+            gate: &gate[..len],
+        };
+        self.process_nonlinear_block(samples, &signals, Some(params));
+    }
+    
+    /// Pedals and amp head at the oversampled rate - O(N * ratio) complexity
+    /// Every per-sample signal is held for each sub-sample; `controls` is `None` to keep
+    /// the current tone and power amp controls
+    fn process_nonlinear_block(&mut self, samples: &mut [f32], signals: &AmpSignals, controls: Option<&BlockParams>) {
+        let ratio = self.oversampler.ratio();
+        let fuzz = &mut self.fuzz;
+        let pedal = &mut self.pedal;
+        let amp_head = &mut self.amp_head;
+        
+        self.oversampler.process_block(samples, |upsampled| {
+            let len = upsampled.len();
+            let mut held_key = [0.0; MAX_BLOCK_SIZE * MAX_OVERSAMPLING_RATIO];
+            let mut held_drive = [0.0; MAX_BLOCK_SIZE * MAX_OVERSAMPLING_RATIO];
+            let mut held_master = [0.0; MAX_BLOCK_SIZE * MAX_OVERSAMPLING_RATIO];
+            let mut held_gate = [0.0; MAX_BLOCK_SIZE * MAX_OVERSAMPLING_RATIO];
+            for i in 0..len {
+                held_key[i] = signals.key[i / ratio];
+                held_drive[i] = signals.drive[i / ratio];
+                held_master[i] = signals.master[i / ratio];
+                held_gate[i] = signals.gate[i / ratio];
+            }
+            fuzz.process_block(upsampled, &mut held_key[..len]);
+            pedal.process_block(upsampled, &mut held_key[..len]);
+            
+            let held = AmpSignals {
+                key: &held_key[..len],
+                drive: &held_drive[..len],
+                master: &held_master[..len],
+                gate: &held_gate[..len],
+            };
+            amp_head.process_block(upsampled, &held, controls, ratio);
+        });
+    }
+    
+    /// Output section over a block: cabinet -> output gain
+    pub fn process_output_block(&mut self, samples: &mut [f32], params: &BlockParams) {
+        self.process_cabinet_output_block(samples, None, params);
+    }
+    
+    /// Output section fed with the opposite channel's amp output as well
+    /// True-stereo cabinet IRs convolve `cross` into this channel's output
+    pub fn process_output_block_with_cross(&mut self, samples: &mut [f32], cross: &[f32], params: &BlockParams) {
+        self.process_cabinet_output_block(samples, Some(cross), params);
+    }
+    
+    fn process_cabinet_output_block(&mut self, samples: &mut [f32], cross: Option<&[f32]>, params: &BlockParams) {
+        let len = samples.len();
+        self.cabinet_simulator.set_blend_enabled(params.blend_enabled);
+        self.cabinet_simulator.process_blend_block(samples, cross, &params.cabinet_mix[..len], &params.cabinet_slots);
+        
+        for (sample, &gain) in samples.iter_mut().zip(&params.output_gain[..len]) {
+            *sample *= gain;
+        }
+    }
+    
     /// Update tone controls - O(1) parameter updates
     pub fn update_tone_controls(&mut self, bass_db: f32, mid_db: f32, treble_db: f32) {
-        self.tonestack.update_controls(bass_db, mid_db, treble_db);
+        self.amp_head.update_tone_controls(bass_db, mid_db, treble_db);
+    }
+    
+    /// Update power amp controls for the per-sample path - O(1) parameter updates
+    /// `master` is linear gain, presence and resonance in dB, sag depth 0-1
+    pub fn update_power_amp_controls(&mut self, master: f32, presence_db: f32, resonance_db: f32, sag: f32) {
+        self.master = master;
+        self.amp_head.update_power_amp_controls(presence_db, resonance_db, sag);
+    }
+    
+    /// Select the oversampling ratio and filter phase for the amp section - O(1), no allocation
+    /// Changing the ratio retunes the amp's filters for the new internal rate
+    pub fn set_oversampling(&mut self, factor: OversamplingFactor, phase: OversamplingPhase) {
+        self.oversampler.set_phase(phase);
+        if factor != self.oversampler.factor() {
+            self.oversampler.set_factor(factor);
+            self.retune_amp();
+        }
+    }
+    
+    /// Select plain or antiderivative anti-aliased waveshaping for the amp - O(1), no allocation
+    pub fn set_waveshaper_mode(&mut self, mode: WaveshaperMode) {
+        self.waveshaper_mode = mode;
+        self.amp_head.set_waveshaper_mode(mode);
+    }
+    
+    /// Apply noise gate settings - O(1), no allocation
+    /// Changing the lookahead changes the reported latency
+    pub fn set_noise_gate(&mut self, settings: NoiseGateSettings) {
+        self.noise_gate.set_settings(settings);
+    }
+    
+    /// Apply compressor settings - O(1), no allocation
+    pub fn set_compressor(&mut self, settings: CompressorSettings) {
+        self.compressor.set_settings(settings);
+    }
+    
+    /// Apply fuzz pedal settings - O(1), no allocation
+    pub fn set_fuzz(&mut self, settings: FuzzSettings) {
+        self.fuzz.set_settings(settings);
     }
     
-    /// Update cabinet parameters - O(1) for mix, O(M log N) for cabinet change
-    /// Cabinet switching is expensive but infrequent; mix updates are O(1)
-    pub fn update_cabinet(&mut self, cabinet_type: CabinetType, mix: f32) {
-        // Only reload cabinet if type changed - avoid expensive recomputation
-        if cabinet_type != self.cabinet_simulator.get_current_cabinet() {
-            if let Err(e) = self.cabinet_simulator.load_cabinet(cabinet_type) {
+    /// Apply drive pedal settings - O(1), no allocation
+    pub fn set_pedal(&mut self, settings: PedalSettings) {
+        self.pedal.set_settings(settings);
+    }
+    
+    /// Select the tone stack circuit - O(1), no allocation
+    pub fn set_tone_stack_model(&mut self, model: ToneStackModel) {
+        self.amp_head.set_tone_stack_model(model);
+    }
+    
+    /// Rebuild the amp's rate-dependent filters for the oversampled rate - O(1)
+    fn retune_amp(&mut self) {
+        let ratio = self.oversampler.ratio();
+        self.fuzz.prepare(self.sample_rate * ratio as f32, MAX_BLOCK_SIZE * ratio);
+        self.pedal.prepare(self.sample_rate * ratio as f32, MAX_BLOCK_SIZE * ratio);
+        self.amp_head.prepare(self.sample_rate * ratio as f32, MAX_BLOCK_SIZE * ratio);
+    }
+    
+    /// Load the main (0) and blend (1) cabinets in place - O(M log N), switches instantly
+    /// For `initialize` only; running processors switch with `install_cabinet`
+    pub fn load_cabinets(&mut self, cabinet_types: [CabinetType; 2]) {
+        for (index, cabinet_type) in cabinet_types.into_iter().enumerate() {
+            if let Err(e) = self.cabinet_simulator.load_slot(index, cabinet_type) {
                 eprintln!("Cabinet load error: {}", e);
             }
         }
-        
-        // Mix update is always O(1)
-        self.cabinet_simulator.set_mix(mix);
     }
     
-    /// Get processing latency including cabinet simulation - O(1) lookup
+    /// Crossfade a blend slot to a cabinet prepared off the audio thread - O(1), no allocation
+    /// Returns false when the cabinet was prepared for an outdated convolution mode
+    pub fn install_cabinet(
+        &mut self,
+        index: usize,
+        cabinet_type: CabinetType,
+        passthrough: bool,
+        prepared: &mut PreparedChannelIr,
+    ) -> bool {
+        self.cabinet_simulator.install_cabinet(index, cabinet_type, passthrough, prepared)
+    }
+    
+    /// Set the crossfade time for cabinet switches in milliseconds - O(1)
+    pub fn set_cabinet_crossfade(&mut self, crossfade_ms: f32) {
+        self.cabinet_simulator.set_crossfade_time(crossfade_ms);
+    }
+    
+    /// Whether a cabinet crossfade is still running
+    pub fn is_cabinet_crossfading(&self) -> bool {
+        self.cabinet_simulator.is_crossfading()
+    }
+    
+    /// Select the output channel cabinet panning applies to - `None` for mono output
+    pub fn set_output_channel(&mut self, output_channel: Option<usize>) {
+        self.cabinet_simulator.set_output_channel(output_channel);
+    }
+    
+    /// Select cabinet convolution engine - O(1), no allocation
+    /// Running cabinets switch once engines prepared off the audio thread are installed
+    pub fn set_convolution_mode(&mut self, mode: ConvolutionMode) {
+        self.cabinet_simulator.set_convolution_mode(mode);
+    }
+    
+    /// Swap in a user IR prepared off the audio thread - O(1), no allocation
+    /// Returns false when the IR was prepared for an outdated convolution mode
+    pub fn install_user_ir(&mut self, prepared: &mut PreparedChannelIr, blend: &mut PreparedEngines) -> bool {
+        self.cabinet_simulator.swap_user_ir(prepared, blend)
+    }
+    
+    /// Get processing latency including gate lookahead, oversampling, waveshaping and cabinet simulation - O(1) lookup
     pub fn get_latency(&self) -> usize {
-        self.cabinet_simulator.get_latency()
+        self.noise_gate.latency()
+            + self.oversampler.latency()
+            + self.waveshaper_latency()
+            + self.cabinet_simulator.get_latency()
+    }
+    
+    /// ADAA delay of the tube, the tube stage clipper and the post-tone-stack clipper in series, in host samples
+    fn waveshaper_latency(&self) -> usize {
+        const WAVESHAPER_COUNT: f32 = 3.0;
+        (self.waveshaper_mode.delay() * WAVESHAPER_COUNT / self.oversampler.ratio() as f32).round() as usize
     }
 }
 
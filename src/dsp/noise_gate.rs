@@ -0,0 +1,350 @@
+use super::Prepare;
+
+/// Longest lookahead the gate supports - the delay lines are sized for it in `prepare`
+pub const MAX_GATE_LOOKAHEAD_MS: f32 = 10.0;
+
+/// Release of the peak detector - long enough to ride over the zero crossings of a low E
+const DETECTOR_RELEASE_MS: f32 = 10.0;
+
+/// How the noise gate opens and closes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseGateSettings {
+    /// Disabled gates pass everything at unity gain with no lookahead delay
+    pub enabled: bool,
+
+    /// Key level that opens the gate, in dBFS
+    pub threshold_db: f32,
+
+    /// How far below the threshold the key must fall before the gate closes, in dB
+    /// Keeps notes decaying around the threshold from chattering
+    pub hysteresis_db: f32,
+
+    pub attack_ms: f32,
+
+    /// Time the gate stays open after the key drops below the close level
+    pub hold_ms: f32,
+
+    pub release_ms: f32,
+
+    /// Delay of the amp input relative to the detector, so the gate is already open
+    /// when a pick attack arrives - reported as latency
+    pub lookahead_ms: f32,
+}
+
+impl Default for NoiseGateSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_db: -60.0,
+            hysteresis_db: 6.0,
+            attack_ms: 1.0,
+            hold_ms: 50.0,
+            release_ms: 100.0,
+            lookahead_ms: 0.0,
+        }
+    }
+}
+
+/// Fixed-capacity delay line for the lookahead - O(1) per sample
+struct LookaheadDelay {
+    buffer: Vec<f32>,
+    write_pos: usize,
+}
+
+impl LookaheadDelay {
+    fn new(max_delay: usize) -> Self {
+        Self { buffer: vec![0.0; max_delay + 1], write_pos: 0 }
+    }
+
+    fn process(&mut self, input: f32, delay: usize) -> f32 {
+        let len = self.buffer.len();
+        self.buffer[self.write_pos] = input;
+        let output = self.buffer[(self.write_pos + len - delay) % len];
+        self.write_pos = (self.write_pos + 1) % len;
+        output
+    }
+
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.write_pos = 0;
+    }
+}
+
+/// Noise gate with hysteresis, hold and lookahead - O(1) per sample
+///
+/// The detector and the gain are split: `process_key` runs the detector on the clean
+/// input (or an external sidechain) and produces one gain per sample, which the amp
+/// applies after its clipper. Gating there removes the hiss the preamp gain brought
+/// up, while detecting before the preamp sees the pick dynamics the drive squashes.
+pub struct NoiseGate {
+    settings: NoiseGateSettings,
+    sample_rate: f32,
+
+    /// Open and close levels as linear gain
+    open_level: f32,
+    close_level: f32,
+
+    /// Per-sample one-pole coefficients and hold length derived from the settings
+    attack_coeff: f32,
+    release_coeff: f32,
+    detector_coeff: f32,
+    hold_samples: usize,
+    lookahead_samples: usize,
+
+    /// Peak envelope of the key
+    envelope: f32,
+    open: bool,
+    hold_remaining: usize,
+    gain: f32,
+
+    /// Amp input and detector key delayed by the lookahead
+    audio_delay: LookaheadDelay,
+    key_delay: LookaheadDelay,
+}
+
+impl NoiseGate {
+    pub fn new(sample_rate: f32) -> Self {
+        let max_delay = Self::max_lookahead_samples(sample_rate);
+        let mut gate = Self {
+            settings: NoiseGateSettings::default(),
+            sample_rate,
+            open_level: 0.0,
+            close_level: 0.0,
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+            detector_coeff: 0.0,
+            hold_samples: 0,
+            lookahead_samples: 0,
+            envelope: 0.0,
+            open: true,
+            hold_remaining: 0,
+            gain: 1.0,
+            audio_delay: LookaheadDelay::new(max_delay),
+            key_delay: LookaheadDelay::new(max_delay),
+        };
+        gate.update_coefficients();
+        gate
+    }
+
+    fn max_lookahead_samples(sample_rate: f32) -> usize {
+        (MAX_GATE_LOOKAHEAD_MS * 0.001 * sample_rate).ceil() as usize
+    }
+
+    /// Apply new settings - O(1), no allocation, skips the math when nothing changed
+    pub fn set_settings(&mut self, settings: NoiseGateSettings) {
+        if settings == self.settings {
+            return;
+        }
+        self.settings = settings;
+        self.update_coefficients();
+    }
+
+    fn update_coefficients(&mut self) {
+        let settings = &self.settings;
+        let time_coeff = |ms: f32| (-1.0 / (ms.max(0.01) * 0.001 * self.sample_rate)).exp();
+
+        self.open_level = 10f32.powf(settings.threshold_db / 20.0);
+        self.close_level = 10f32.powf((settings.threshold_db - settings.hysteresis_db.max(0.0)) / 20.0);
+        self.attack_coeff = time_coeff(settings.attack_ms);
+        self.release_coeff = time_coeff(settings.release_ms);
+        self.detector_coeff = time_coeff(DETECTOR_RELEASE_MS);
+        self.hold_samples = (settings.hold_ms.max(0.0) * 0.001 * self.sample_rate) as usize;
+
+        let max_delay = self.audio_delay.buffer.len() - 1;
+        let lookahead_samples = if settings.enabled {
+            ((settings.lookahead_ms.max(0.0) * 0.001 * self.sample_rate).round() as usize).min(max_delay)
+        } else {
+            0
+        };
+
+        // The lines sat idle without lookahead - clear them so no audio from before plays back
+        if self.lookahead_samples == 0 && lookahead_samples > 0 {
+            self.audio_delay.reset();
+            self.key_delay.reset();
+        }
+        self.lookahead_samples = lookahead_samples;
+    }
+
+    /// Run the detector over a block of key samples and write the gain for each - O(N)
+    pub fn process_key(&mut self, key: &[f32], gains: &mut [f32]) {
+        if !self.settings.enabled {
+            // Start from open when enabled again, so the gate closes smoothly instead of cutting in
+            self.open = true;
+            self.hold_remaining = self.hold_samples;
+            self.gain = 1.0;
+            gains.fill(1.0);
+            return;
+        }
+
+        for (&key, gain) in key.iter().zip(gains.iter_mut()) {
+            self.envelope = key.abs().max(self.envelope * self.detector_coeff);
+
+            if self.envelope >= self.open_level {
+                self.open = true;
+            }
+            if self.envelope >= self.close_level {
+                self.hold_remaining = self.hold_samples;
+            } else if self.hold_remaining > 0 {
+                self.hold_remaining -= 1;
+            } else {
+                self.open = false;
+            }
+
+            let (target, coeff) = if self.open { (1.0, self.attack_coeff) } else { (0.0, self.release_coeff) };
+            self.gain = target + (self.gain - target) * coeff;
+            *gain = self.gain;
+        }
+    }
+
+    /// Delay the amp input and its detector key by the lookahead - O(N), no-op without lookahead
+    pub fn delay_block(&mut self, samples: &mut [f32], key: &mut [f32]) {
+        if self.lookahead_samples == 0 {
+            return;
+        }
+        for (sample, key) in samples.iter_mut().zip(key.iter_mut()) {
+            *sample = self.audio_delay.process(*sample, self.lookahead_samples);
+            *key = self.key_delay.process(*key, self.lookahead_samples);
+        }
+    }
+
+    /// Lookahead delay in samples - O(1)
+    pub fn latency(&self) -> usize {
+        self.lookahead_samples
+    }
+}
+
+impl Prepare for NoiseGate {
+    /// Size the lookahead delay lines for the new rate and retune the time constants
+    fn prepare(&mut self, sample_rate: f32, _max_block: usize) {
+        self.sample_rate = sample_rate;
+        let max_delay = Self::max_lookahead_samples(sample_rate);
+        self.audio_delay = LookaheadDelay::new(max_delay);
+        self.key_delay = LookaheadDelay::new(max_delay);
+        self.envelope = 0.0;
+        self.open = true;
+        self.hold_remaining = 0;
+        self.gain = 1.0;
+        self.update_coefficients();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::{BlockParams, GuitarFxProcessor, MAX_BLOCK_SIZE};
+
+    fn gate_with(settings: NoiseGateSettings) -> NoiseGate {
+        let mut gate = NoiseGate::new(44100.0);
+        gate.set_settings(NoiseGateSettings { enabled: true, ..settings });
+        gate
+    }
+
+    fn gains(gate: &mut NoiseGate, key: &[f32]) -> Vec<f32> {
+        let mut gains = vec![0.0; key.len()];
+        gate.process_key(key, &mut gains);
+        gains
+    }
+
+    #[test]
+    fn test_hysteresis_hold_and_release() {
+        let settings = NoiseGateSettings {
+            threshold_db: -40.0,
+            hysteresis_db: 10.0,
+            hold_ms: 20.0,
+            release_ms: 2.0,
+            ..NoiseGateSettings::default()
+        };
+        let mut gate = gate_with(settings);
+
+        // Below the close level the gate shuts once hold and release have run out
+        let quiet = gains(&mut gate, &[0.001; 4410]);
+        assert!(quiet[4409] < 1e-3);
+
+        // Between the close and open levels it stays shut...
+        let between = gains(&mut gate, &[0.006; 4410]);
+        assert!(between[4409] < 1e-3);
+
+        // ...until the key crosses the threshold, and then stays open there
+        gains(&mut gate, &[0.02; 441]);
+        let held = gains(&mut gate, &[0.006; 4410]);
+        assert!(held.iter().all(|&gain| gain > 0.99));
+
+        // Dropping below the close level keeps it open for the hold time, then it releases
+        // once the detector has decayed - allow 15 ms for that and the release itself
+        let closing = gains(&mut gate, &[0.0; 4410]);
+        let hold_end = (0.020 * 44100.0) as usize;
+        assert!(closing[hold_end] > 0.99);
+        assert!(closing[hold_end + 661] < 0.05);
+    }
+
+    #[test]
+    fn test_lookahead_opens_before_the_attack() {
+        let settings = NoiseGateSettings {
+            threshold_db: -40.0,
+            attack_ms: 1.0,
+            lookahead_ms: 5.0,
+            ..NoiseGateSettings::default()
+        };
+        let mut gate = gate_with(settings);
+        let delay = gate.latency();
+        assert_eq!(delay, (0.005f32 * 44100.0).round() as usize);
+
+        // Close the gate, then hit it with a step at sample 1000
+        gains(&mut gate, &[0.0; 44100]);
+        let input: Vec<f32> = (0..2000).map(|n| if n >= 1000 { 0.5 } else { 0.0 }).collect();
+        let mut audio = input.clone();
+        let mut key = input.clone();
+        let gain = gains(&mut gate, &input);
+        gate.delay_block(&mut audio, &mut key);
+
+        // The delayed step reaches the gain stage with the gate already most of the way open
+        assert_eq!(audio[1000 + delay - 1], 0.0);
+        assert_eq!(audio[1000 + delay], 0.5);
+        assert!(gain[1000 + delay] > 0.98);
+
+        // Switched off and on again, the lookahead starts from silence rather than the
+        // end of the step still sitting in the idle lines
+        gate.set_settings(NoiseGateSettings { enabled: false, ..settings });
+        let mut audio = vec![0.5; 1000];
+        let mut key = audio.clone();
+        gate.delay_block(&mut audio, &mut key);
+        gate.set_settings(NoiseGateSettings { enabled: true, ..settings });
+        let mut audio = vec![0.0; 1000];
+        let mut key = audio.clone();
+        gate.delay_block(&mut audio, &mut key);
+        assert!(audio[..delay].iter().chain(&key[..delay]).all(|&x| x == 0.0));
+    }
+
+    #[test]
+    fn test_sidechain_key_opens_gate_on_silent_input() {
+        let mut processor = GuitarFxProcessor::new();
+        processor.initialize(44100.0);
+        processor.set_noise_gate(NoiseGateSettings { enabled: true, ..NoiseGateSettings::default() });
+        let mut params = BlockParams::constant(1.0, 20.0, 1.0, 0.0, 0.0, 0.0);
+
+        // Peak output for a second of hiss under the threshold, measured over the last quarter
+        let hiss = |n: usize| 1e-4 * ((n * 7919 % 101) as f32 / 50.0 - 1.0);
+        let hiss_peak = |processor: &mut GuitarFxProcessor, params: &BlockParams| {
+            let blocks = 44100 / MAX_BLOCK_SIZE;
+            let mut peak = 0.0f32;
+            for block in 0..blocks {
+                let mut samples: Vec<f32> = (0..MAX_BLOCK_SIZE).map(|i| hiss(block * MAX_BLOCK_SIZE + i)).collect();
+                processor.process_block(&mut samples, params);
+                if block >= blocks * 3 / 4 {
+                    peak = samples.iter().fold(peak, |peak, x| peak.max(x.abs()));
+                }
+            }
+            peak
+        };
+
+        // At 20x drive the hiss comes up loud, the gate keyed from the silent input shuts it
+        let gated = hiss_peak(&mut processor, &params);
+
+        // A loud external key holds the gate open on the same hiss
+        params.gate_key.fill(0.5);
+        params.gate_sidechain = true;
+        let keyed = hiss_peak(&mut processor, &params);
+        assert!(keyed > 1e-3, "keyed output peak {}", keyed);
+        assert!(gated < keyed * 0.01, "gated {} vs keyed {}", gated, keyed);
+    }
+}
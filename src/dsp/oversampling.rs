@@ -0,0 +1,482 @@
+use super::ir_loader::{bessel_i0, sinc, KAISER_BETA};
+use super::ir_prepare::minimum_phase;
+use super::{Prepare, MAX_BLOCK_SIZE};
+use nih_plug::prelude::Enum;
+
+/// Highest oversampling ratio - sizes the per-block scratch buffers
+pub const MAX_OVERSAMPLING_RATIO: usize = 8;
+
+/// Kernel length of each 2x stage, first stage (closest to the host rate) first
+/// The first stage carries the steep transition band around the host Nyquist frequency;
+/// later stages only have to reject images far above the audio band, so they get by
+/// with half the taps. With these lengths the linear-phase delay of every factor is a
+/// whole number of host samples (32, 40 and 44).
+const STAGE_TAPS: [usize; 3] = [65, 33, 33];
+
+/// Oversampling ratio for the amp's nonlinear stages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversamplingFactor {
+    /// Host rate - no extra latency or CPU, but drive harmonics alias back
+    Off,
+    X2,
+    X4,
+    X8,
+}
+
+impl OversamplingFactor {
+    /// Ratio of the oversampled rate to the host rate
+    pub fn ratio(self) -> usize {
+        1 << self.stage_count()
+    }
+
+    /// Number of cascaded 2x stages
+    fn stage_count(self) -> usize {
+        match self {
+            OversamplingFactor::Off => 0,
+            OversamplingFactor::X2 => 1,
+            OversamplingFactor::X4 => 2,
+            OversamplingFactor::X8 => 3,
+        }
+    }
+}
+
+impl nih_plug::prelude::Enum for OversamplingFactor {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Off",
+            "2x",
+            "4x",
+            "8x",
+        ]
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "off",
+            "2x",
+            "4x",
+            "8x",
+        ])
+    }
+
+    fn to_index(self) -> usize {
+        self.stage_count()
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => OversamplingFactor::Off,
+            1 => OversamplingFactor::X2,
+            2 => OversamplingFactor::X4,
+            3 => OversamplingFactor::X8,
+            _ => OversamplingFactor::Off, // Default fallback
+        }
+    }
+}
+
+/// Phase response of the oversampling filters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversamplingPhase {
+    /// Symmetric kernels - no phase distortion, adds a fixed delay
+    Linear,
+
+    /// Minimum-phase kernels - almost no delay, slight phase shift near the top of the band
+    Minimum,
+}
+
+impl nih_plug::prelude::Enum for OversamplingPhase {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Linear Phase",
+            "Minimum Phase",
+        ]
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "linear",
+            "minimum",
+        ])
+    }
+
+    fn to_index(self) -> usize {
+        match self {
+            OversamplingPhase::Linear => 0,
+            OversamplingPhase::Minimum => 1,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => OversamplingPhase::Linear,
+            1 => OversamplingPhase::Minimum,
+            _ => OversamplingPhase::Linear, // Default fallback
+        }
+    }
+}
+
+/// Delay line read newest-first as one contiguous slice - O(1) push
+/// Every sample is written twice, `len` apart, so the read window never wraps
+struct History {
+    data: Vec<f32>,
+    position: usize,
+}
+
+impl History {
+    fn new(len: usize) -> Self {
+        Self { data: vec![0.0; 2 * len], position: 0 }
+    }
+
+    fn len(&self) -> usize {
+        self.data.len() / 2
+    }
+
+    fn push(&mut self, sample: f32) {
+        let len = self.len();
+        self.position = if self.position == 0 { len - 1 } else { self.position - 1 };
+        self.data[self.position] = sample;
+        self.data[self.position + len] = sample;
+    }
+
+    /// Last `len` samples, newest first
+    fn recent(&self) -> &[f32] {
+        &self.data[self.position..self.position + self.len()]
+    }
+
+    fn clear(&mut self) {
+        self.data.fill(0.0);
+        self.position = 0;
+    }
+}
+
+fn dot(kernel: &[f32], history: &[f32]) -> f32 {
+    kernel.iter().zip(history).map(|(h, x)| h * x).sum()
+}
+
+/// One 2x up/down stage with polyphase FIR filtering - O(N) per sample for N taps
+/// Interpolation only evaluates the kernel's even and odd taps against the input
+/// (the zero-stuffed samples contribute nothing), decimation only computes the
+/// outputs that are kept. Both phase variants are built up front so switching never allocates.
+struct HalfBandStage {
+    /// Full kernel per phase, indexed by `OversamplingPhase::to_index`
+    kernels: [Vec<f32>; 2],
+
+    /// Even and odd taps per phase, scaled by 2 to make up for the zero stuffing
+    branches: [[Vec<f32>; 2]; 2],
+
+    /// DC group delay per phase in samples at the stage's high rate
+    group_delays: [f64; 2],
+
+    /// Active phase index
+    phase: usize,
+
+    /// Low-rate input of the interpolator
+    up_history: History,
+
+    /// High-rate input of the decimator
+    down_history: History,
+}
+
+impl HalfBandStage {
+    fn new(taps: usize) -> Self {
+        let linear = half_band_kernel(taps);
+        let minimum = normalized(minimum_phase(&linear));
+        let kernels = [linear, minimum];
+
+        let branches = kernels.clone().map(|kernel| {
+            let branch = |offset: usize| kernel.iter().skip(offset).step_by(2).map(|h| 2.0 * h).collect::<Vec<f32>>();
+            [branch(0), branch(1)]
+        });
+        let group_delays = [0, 1].map(|index| {
+            let kernel = &kernels[index];
+            let moment: f64 = kernel.iter().enumerate().map(|(n, &h)| n as f64 * h as f64).sum();
+            let sum: f64 = kernel.iter().map(|&h| h as f64).sum();
+            moment / sum
+        });
+
+        Self {
+            kernels,
+            branches,
+            group_delays,
+            phase: 0,
+            up_history: History::new(taps.div_ceil(2)),
+            down_history: History::new(taps),
+        }
+    }
+
+    /// Interpolate `input` into `output`, which holds twice as many samples
+    fn upsample(&mut self, input: &[f32], output: &mut [f32]) {
+        let [even, odd] = &self.branches[self.phase];
+        for (&sample, pair) in input.iter().zip(output.chunks_exact_mut(2)) {
+            self.up_history.push(sample);
+            let recent = self.up_history.recent();
+            pair[0] = dot(even, recent);
+            pair[1] = dot(odd, recent);
+        }
+    }
+
+    /// Filter and decimate `input` into `output`, which holds half as many samples
+    /// Keeps the even samples so a linear-phase round trip delays by whole low-rate samples
+    fn downsample(&mut self, input: &[f32], output: &mut [f32]) {
+        let kernel = &self.kernels[self.phase];
+        for (pair, sample) in input.chunks_exact(2).zip(output.iter_mut()) {
+            self.down_history.push(pair[0]);
+            *sample = dot(kernel, self.down_history.recent());
+            self.down_history.push(pair[1]);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.up_history.clear();
+        self.down_history.clear();
+    }
+}
+
+/// Kaiser-windowed sinc with its cutoff at a quarter of the stage's high rate,
+/// i.e. at the low rate's Nyquist frequency - unity DC gain
+fn half_band_kernel(taps: usize) -> Vec<f32> {
+    let center = (taps - 1) as f64 / 2.0;
+    let window_norm = bessel_i0(KAISER_BETA);
+    let kernel = (0..taps)
+        .map(|n| {
+            let distance = n as f64 - center;
+            let window_position = distance / center;
+            let window = bessel_i0(KAISER_BETA * (1.0 - window_position * window_position).max(0.0).sqrt())
+                / window_norm;
+            (0.5 * sinc(0.5 * distance) * window) as f32
+        })
+        .collect();
+    normalized(kernel)
+}
+
+fn normalized(mut kernel: Vec<f32>) -> Vec<f32> {
+    let sum: f32 = kernel.iter().sum();
+    if sum.abs() > f32::EPSILON {
+        kernel.iter_mut().for_each(|h| *h /= sum);
+    }
+    kernel
+}
+
+/// Runs a processing closure at 2x, 4x or 8x the host rate - O(N) per host sample
+///
+/// Cascades 2x half-band stages: the block is interpolated stage by stage, the closure
+/// processes it at the top rate, and the result is decimated back down. All kernels
+/// and scratch buffers are allocated in `new`, so factor and phase changes are
+/// allocation-free and safe on the audio thread.
+pub struct Oversampler {
+    factor: OversamplingFactor,
+    phase: OversamplingPhase,
+
+    /// 2x stages, host rate side first
+    stages: [HalfBandStage; 3],
+
+    /// Signal at 2x, 4x and 8x the host rate - each stage's high-rate side
+    buffers: [Vec<f32>; 3],
+}
+
+impl Oversampler {
+    pub fn new() -> Self {
+        Self {
+            factor: OversamplingFactor::Off,
+            phase: OversamplingPhase::Linear,
+            stages: STAGE_TAPS.map(HalfBandStage::new),
+            buffers: [2, 4, 8].map(|ratio| vec![0.0; ratio * MAX_BLOCK_SIZE]),
+        }
+    }
+
+    /// Select the oversampling ratio - clears the filter state when it changes
+    pub fn set_factor(&mut self, factor: OversamplingFactor) {
+        if factor != self.factor {
+            self.factor = factor;
+            self.reset();
+        }
+    }
+
+    /// Select linear- or minimum-phase filters - clears the filter state when it changes
+    pub fn set_phase(&mut self, phase: OversamplingPhase) {
+        if phase != self.phase {
+            self.phase = phase;
+            for stage in self.stages.iter_mut() {
+                stage.phase = phase.to_index();
+            }
+            self.reset();
+        }
+    }
+
+    pub fn factor(&self) -> OversamplingFactor {
+        self.factor
+    }
+
+    /// Ratio of the rate the closure runs at to the host rate
+    pub fn ratio(&self) -> usize {
+        self.factor.ratio()
+    }
+
+    /// Delay added by the up/down filters in host samples - O(1) per stage
+    /// Each stage delays by its kernel's group delay twice (up and down) at its own rate
+    pub fn latency(&self) -> usize {
+        let phase = self.phase.to_index();
+        let delay: f64 = self.stages[..self.factor.stage_count()]
+            .iter()
+            .enumerate()
+            .map(|(index, stage)| stage.group_delays[phase] / (1 << index) as f64)
+            .sum();
+        delay.round() as usize
+    }
+
+    pub fn reset(&mut self) {
+        for stage in self.stages.iter_mut() {
+            stage.reset();
+        }
+    }
+
+    /// Upsample `samples`, run `process` on the oversampled block and decimate back in place
+    /// `process` receives `ratio()` samples per host sample; with oversampling off it
+    /// runs on `samples` directly
+    pub fn process_block<F: FnOnce(&mut [f32])>(&mut self, samples: &mut [f32], process: F) {
+        let stage_count = self.factor.stage_count();
+        if stage_count == 0 {
+            process(samples);
+            return;
+        }
+
+        debug_assert!(samples.len() <= MAX_BLOCK_SIZE);
+        let len = samples.len();
+
+        // Host rate -> 2x -> 4x -> 8x
+        self.stages[0].upsample(samples, &mut self.buffers[0][..2 * len]);
+        for index in 1..stage_count {
+            let (lower, upper) = self.buffers.split_at_mut(index);
+            let low_len = len << index;
+            self.stages[index].upsample(&lower[index - 1][..low_len], &mut upper[0][..2 * low_len]);
+        }
+
+        process(&mut self.buffers[stage_count - 1][..len << stage_count]);
+
+        // And back down
+        for index in (1..stage_count).rev() {
+            let (lower, upper) = self.buffers.split_at_mut(index);
+            let low_len = len << index;
+            self.stages[index].downsample(&upper[0][..2 * low_len], &mut lower[index - 1][..low_len]);
+        }
+        self.stages[0].downsample(&self.buffers[0][..2 * len], samples);
+    }
+}
+
+impl Prepare for Oversampler {
+    /// Half-band kernels work at any rate and the buffers are fixed - only the state is cleared
+    fn prepare(&mut self, _sample_rate: f32, max_block: usize) {
+        debug_assert!(max_block <= MAX_BLOCK_SIZE);
+        self.reset();
+    }
+}
+
+impl Default for Oversampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::dsp::{BlockParams, GuitarFxProcessor};
+    use realfft::RealFftPlanner;
+
+    fn sine(frequency: f32, sample_rate: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| 0.5 * (2.0 * std::f32::consts::PI * frequency * n as f32 / sample_rate).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_linear_phase_delay_matches_latency() {
+        let input = sine(1000.0, 44100.0, 2048);
+
+        for (factor, expected_latency) in [
+            (OversamplingFactor::X2, 32),
+            (OversamplingFactor::X4, 40),
+            (OversamplingFactor::X8, 44),
+        ] {
+            let mut oversampler = Oversampler::new();
+            oversampler.set_factor(factor);
+            assert_eq!(oversampler.latency(), expected_latency);
+
+            let mut output = input.clone();
+            for block in output.chunks_mut(MAX_BLOCK_SIZE) {
+                oversampler.process_block(block, |_| {});
+            }
+
+            // A band-limited signal comes back as a pure delay
+            for n in 256..input.len() {
+                let error = (output[n] - input[n - expected_latency]).abs();
+                assert!(error < 1e-3, "{:?}: error {} at sample {}", factor, error, n);
+            }
+        }
+
+        // Minimum phase trades the symmetric delay for a much shorter one
+        let mut oversampler = Oversampler::new();
+        oversampler.set_factor(OversamplingFactor::X8);
+        oversampler.set_phase(OversamplingPhase::Minimum);
+        assert!(oversampler.latency() < 44 / 2);
+    }
+
+    /// Hann-windowed power spectrum, one value per bin up to Nyquist
+    pub(crate) fn power_spectrum(signal: &[f32]) -> Vec<f64> {
+        let len = signal.len();
+        let mut planner = RealFftPlanner::<f32>::new();
+        let fft = planner.plan_fft_forward(len);
+        let mut windowed: Vec<f32> = signal
+            .iter()
+            .enumerate()
+            .map(|(n, x)| x * (0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / len as f32).cos()))
+            .collect();
+        let mut spectrum = fft.make_output_vec();
+        fft.process(&mut windowed, &mut spectrum).unwrap();
+        spectrum.iter().map(|value| value.norm_sqr() as f64).collect()
+    }
+
+    /// Energy in bins that aren't harmonics of `fundamental_bin`, relative to the harmonics, in dB
+    pub(crate) fn alias_level_db(output: &[f32], fundamental_bin: usize) -> f32 {
+        let (mut harmonic, mut alias) = (0.0f64, 0.0f64);
+        for (bin, &power) in power_spectrum(output).iter().enumerate().skip(8) {
+            let distance = bin % fundamental_bin;
+            if distance <= 4 || fundamental_bin - distance <= 4 {
+                harmonic += power;
+            } else {
+                alias += power;
+            }
+        }
+        10.0 * (alias / harmonic).log10() as f32
+    }
+
+    #[test]
+    fn test_oversampling_reduces_aliasing() {
+        // ~7 kHz, exactly on a bin - its harmonics above 22 kHz fold between the harmonics
+        const LEN: usize = 4096;
+        const FUNDAMENTAL_BIN: usize = 650;
+        let frequency = FUNDAMENTAL_BIN as f32 * 44100.0 / LEN as f32;
+        let input = sine(frequency, 44100.0, 2 * LEN);
+        let params = BlockParams::constant(1.0, 20.0, 1.0, 0.0, 0.0, 0.0);
+
+        let measure = |factor: OversamplingFactor| {
+            let mut processor = GuitarFxProcessor::new();
+            processor.initialize(44100.0);
+            processor.set_oversampling(factor, OversamplingPhase::Linear);
+
+            let mut output = input.clone();
+            for block in output.chunks_mut(MAX_BLOCK_SIZE) {
+                let key = block.to_vec();
+                processor.process_amp_block(block, &key, &params);
+            }
+            alias_level_db(&output[LEN..], FUNDAMENTAL_BIN)
+        };
+
+        let off = measure(OversamplingFactor::Off);
+        let x2 = measure(OversamplingFactor::X2);
+        let x8 = measure(OversamplingFactor::X8);
+        assert!(off > -20.0, "expected audible aliasing without oversampling, got {} dB", off);
+        assert!(x2 < off - 3.0, "2x only moved aliasing from {} dB to {} dB", off, x2);
+        assert!(x8 < off - 18.0, "8x only moved aliasing from {} dB to {} dB", off, x8);
+    }
+}
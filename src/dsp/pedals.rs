@@ -0,0 +1,342 @@
+use super::filters::BiquadFilter;
+use super::Prepare;
+
+/// Glide time for drive and level changes
+const CONTROL_SMOOTHING_MS: f32 = 20.0;
+
+/// Tube Screamer clipping stage: 4.7k to ground through 47n, 51k plus the 500k drive
+/// pot in the feedback loop, bridged by 51p and the clipping diodes
+const TS_GROUND_OHMS: f32 = 4.7e3;
+const TS_FEEDBACK_OHMS: f32 = 51e3;
+const TS_DRIVE_POT_OHMS: f32 = 500e3;
+const TS_FEEDBACK_FARADS: f32 = 51e-12;
+const TS_CLIP_HIGH_PASS_HZ: f32 = 720.0;
+
+/// Forward voltage of the clipping diodes relative to full scale
+const DIODE_LEVEL: f32 = 0.5;
+
+/// Op-amp rail headroom of the clean boost relative to full scale
+const BOOST_HEADROOM: f32 = 2.0;
+
+/// Which circuit the pedal models
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PedalModel {
+    /// Diode clipper in an op-amp feedback loop - only the mids above ~720 Hz are
+    /// driven, which is what tightens a high-gain amp
+    TubeScreamer,
+
+    /// Single germanium transistor with a small input cap - asymmetric and bright
+    TrebleBooster,
+
+    /// Flat op-amp gain with lots of headroom
+    CleanBoost,
+}
+
+impl nih_plug::prelude::Enum for PedalModel {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Tube Screamer",
+            "Treble Booster",
+            "Clean Boost",
+        ]
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "tube_screamer",
+            "treble_booster",
+            "clean_boost",
+        ])
+    }
+
+    fn to_index(self) -> usize {
+        match self {
+            PedalModel::TubeScreamer => 0,
+            PedalModel::TrebleBooster => 1,
+            PedalModel::CleanBoost => 2,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => PedalModel::TubeScreamer,
+            1 => PedalModel::TrebleBooster,
+            2 => PedalModel::CleanBoost,
+            _ => PedalModel::TubeScreamer, // Default fallback
+        }
+    }
+}
+
+/// How the drive pedal is set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PedalSettings {
+    /// Disabled pedals are true bypass
+    pub enabled: bool,
+
+    pub model: PedalModel,
+
+    /// 0-1 gain knob of the selected circuit
+    pub drive: f32,
+
+    /// 0-1: Tube Screamer treble cut, treble booster input cap, clean boost high shelf
+    pub tone: f32,
+
+    /// Output level, in dB
+    pub level_db: f32,
+}
+
+impl Default for PedalSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model: PedalModel::TubeScreamer,
+            drive: 0.5,
+            tone: 0.5,
+            level_db: 0.0,
+        }
+    }
+}
+
+/// Overdrive and boost pedal in front of the amp - O(1) per sample
+///
+/// Every model runs the same chain: input filter -> gain -> clip-path filter ->
+/// waveshaper -> coupling high-pass -> tone filter -> level. The models differ in how
+/// the filters are tuned and in the waveshaper. Runs inside the oversampler with the
+/// amp head, so it is prepared at the oversampled rate.
+pub struct OverdrivePedal {
+    settings: PedalSettings,
+    sample_rate: f32,
+
+    input_filter: BiquadFilter,
+    clip_filter: BiquadFilter,
+    output_filter: BiquadFilter,
+    tone_filter: BiquadFilter,
+
+    /// Targets and smoothed values of the clip-path gain and output level
+    target_gain: f32,
+    target_level: f32,
+    gain: f32,
+    level: f32,
+    control_coeff: f32,
+}
+
+impl OverdrivePedal {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut pedal = Self {
+            settings: PedalSettings::default(),
+            sample_rate,
+            input_filter: BiquadFilter::new(),
+            clip_filter: BiquadFilter::new(),
+            output_filter: BiquadFilter::new(),
+            tone_filter: BiquadFilter::new(),
+            target_gain: 1.0,
+            target_level: 1.0,
+            gain: 1.0,
+            level: 1.0,
+            control_coeff: 0.0,
+        };
+        pedal.update_coefficients();
+        pedal.gain = pedal.target_gain;
+        pedal.level = pedal.target_level;
+        pedal
+    }
+
+    /// Apply new settings - O(1), no allocation, skips the math when nothing changed
+    /// Drive and level glide; switching models clears the filters
+    pub fn set_settings(&mut self, settings: PedalSettings) {
+        if settings == self.settings {
+            return;
+        }
+        if settings.model != self.settings.model {
+            self.reset();
+        }
+        self.settings = settings;
+        self.update_coefficients();
+    }
+
+    fn reset(&mut self) {
+        self.input_filter.reset();
+        self.clip_filter.reset();
+        self.output_filter.reset();
+        self.tone_filter.reset();
+    }
+
+    fn update_coefficients(&mut self) {
+        let sample_rate = self.sample_rate;
+        let nyquist_limit = 0.45 * sample_rate;
+        let drive = self.settings.drive.clamp(0.0, 1.0);
+        let tone = self.settings.tone.clamp(0.0, 1.0);
+
+        // Identity unless the model uses the clip-path filter; set in place so turning
+        // a knob doesn't clear the filter state
+        self.clip_filter.set_coefficients(1.0, 0.0, 0.0, 0.0, 0.0);
+        match self.settings.model {
+            PedalModel::TubeScreamer => {
+                // Audio-taper drive pot in the feedback loop; its 51p cap lowers the
+                // clip-path corner as the gain goes up
+                let feedback_ohms = TS_FEEDBACK_OHMS + TS_DRIVE_POT_OHMS * drive * drive;
+                let feedback_corner = 1.0 / (2.0 * std::f32::consts::PI * feedback_ohms * TS_FEEDBACK_FARADS);
+                self.target_gain = feedback_ohms / TS_GROUND_OHMS;
+                self.input_filter.one_pole_high_pass(TS_CLIP_HIGH_PASS_HZ, sample_rate);
+                self.clip_filter.one_pole_low_pass(feedback_corner.min(nyquist_limit), sample_rate);
+                self.tone_filter.one_pole_low_pass(700.0 * 8f32.powf(tone), sample_rate);
+            }
+            PedalModel::TrebleBooster => {
+                // Smaller input caps cut more bass - tone sweeps the cut from 200 Hz to 2 kHz
+                self.target_gain = 1.0 + 30.0 * drive * drive;
+                self.input_filter.one_pole_high_pass(200.0 * 10f32.powf(tone), sample_rate);
+                self.tone_filter.low_pass(6000.0f32.min(nyquist_limit), 0.707, sample_rate);
+            }
+            PedalModel::CleanBoost => {
+                // Up to +20 dB, with a high shelf of +-6 dB at 3 kHz
+                self.target_gain = 10f32.powf(drive);
+                self.input_filter.one_pole_high_pass(40.0, sample_rate);
+                self.tone_filter.high_shelf(3000.0f32.min(nyquist_limit), (tone - 0.5) * 12.0, sample_rate);
+            }
+        }
+        self.output_filter.high_pass(20.0, 0.707, sample_rate);
+
+        self.target_level = 10f32.powf(self.settings.level_db / 20.0);
+        self.control_coeff = (-1.0 / (CONTROL_SMOOTHING_MS * 0.001 * sample_rate)).exp();
+    }
+
+    /// Waveshaper of the selected circuit applied to the driven clip-path signal
+    fn shape(model: PedalModel, dry: f32, driven: f32) -> f32 {
+        match model {
+            // The op-amp passes the dry signal at unity and adds the clipped gain path
+            PedalModel::TubeScreamer => dry + DIODE_LEVEL * (driven / DIODE_LEVEL).tanh(),
+            // Starved germanium stage: soft exponential squash on one side, earlier clip on the other
+            PedalModel::TrebleBooster => {
+                if driven >= 0.0 {
+                    1.0 - (-driven).exp()
+                } else {
+                    0.7 * (driven / 0.7).tanh()
+                }
+            }
+            PedalModel::CleanBoost => BOOST_HEADROOM * (driven / BOOST_HEADROOM).tanh(),
+        }
+    }
+
+    /// Run a block through the pedal in place - O(N), true bypass when disabled
+    /// The amp's detector key goes through the waveshaper at the same gain and level,
+    /// skipping the filters that would smear a rectified key, so linked channels keep
+    /// identical keys that still follow the level the amp sees
+    pub fn process_block(&mut self, samples: &mut [f32], key: &mut [f32]) {
+        if !self.settings.enabled {
+            return;
+        }
+        let model = self.settings.model;
+
+        for (sample, key) in samples.iter_mut().zip(key.iter_mut()) {
+            self.gain = self.target_gain + (self.gain - self.target_gain) * self.control_coeff;
+            self.level = self.target_level + (self.level - self.target_level) * self.control_coeff;
+
+            let driven = self.clip_filter.process(self.gain * self.input_filter.process(*sample));
+            let shaped = Self::shape(model, *sample, driven);
+            *sample = self.level * self.tone_filter.process(self.output_filter.process(shaped));
+            *key = self.level * Self::shape(model, *key, self.gain * *key);
+        }
+    }
+}
+
+impl Prepare for OverdrivePedal {
+    /// Retune the filters for the rate the pedal runs at and clear their state
+    fn prepare(&mut self, sample_rate: f32, _max_block: usize) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients();
+        self.reset();
+        self.gain = self.target_gain;
+        self.level = self.target_level;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pedal_with(settings: PedalSettings) -> OverdrivePedal {
+        let mut pedal = OverdrivePedal::new(44100.0);
+        pedal.set_settings(PedalSettings { enabled: true, ..settings });
+        pedal.prepare(44100.0, 64);
+        pedal
+    }
+
+    /// Output of half a second of sine, settled over the first half
+    fn sine_output(pedal: &mut OverdrivePedal, frequency: f32, amplitude: f32) -> Vec<f32> {
+        let mut samples: Vec<f32> = (0..22050)
+            .map(|n| amplitude * (2.0 * std::f32::consts::PI * frequency * n as f32 / 44100.0).sin())
+            .collect();
+        let mut key = samples.clone();
+        pedal.process_block(&mut samples, &mut key);
+        samples.split_off(11025)
+    }
+
+    /// Small-signal gain in dB at `frequency`
+    fn response_db(settings: PedalSettings, frequency: f32) -> f32 {
+        let output = sine_output(&mut pedal_with(settings), frequency, 1e-3);
+        let rms = (output.iter().map(|x| x * x).sum::<f32>() / output.len() as f32).sqrt();
+        20.0 * (rms / (1e-3 / 2.0f32.sqrt())).log10()
+    }
+
+    /// Amplitude of the `frequency` component of a settled output
+    fn harmonic_level(output: &[f32], frequency: f32) -> f32 {
+        let (re, im) = output.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, x)| {
+            let phase = 2.0 * std::f32::consts::PI * frequency * n as f32 / 44100.0;
+            (re + x * phase.cos(), im + x * phase.sin())
+        });
+        2.0 * (re * re + im * im).sqrt() / output.len() as f32
+    }
+
+    #[test]
+    fn test_tube_screamer_mid_hump() {
+        let settings = PedalSettings::default();
+        let bass = response_db(settings, 100.0);
+        let mid = response_db(settings, 1000.0);
+        let treble = response_db(settings, 8000.0);
+        assert!(mid > bass + 10.0, "mid {} dB vs bass {} dB", mid, bass);
+        assert!(mid > treble + 3.0, "mid {} dB vs treble {} dB", mid, treble);
+
+        // Cranked, the mids get far more gain than the low end - the tightening trick
+        let driven = PedalSettings { drive: 1.0, ..settings };
+        let driven_mid = response_db(driven, 1000.0);
+        assert!(driven_mid > mid + 6.0);
+        assert!(driven_mid > response_db(driven, 60.0) + 15.0);
+    }
+
+    #[test]
+    fn test_treble_booster_tone_and_asymmetry() {
+        let full = PedalSettings { model: PedalModel::TrebleBooster, tone: 0.0, ..PedalSettings::default() };
+        let bright = PedalSettings { tone: 1.0, ..full };
+        assert!(response_db(bright, 100.0) < response_db(full, 100.0) - 10.0);
+        assert!((response_db(bright, 4000.0) - response_db(full, 4000.0)).abs() < 3.0);
+
+        // Driven hard the two half waves clip differently, adding the even harmonics the
+        // symmetric clean boost doesn't, while the coupling high-pass keeps it DC free
+        let second_harmonic_db = |settings: PedalSettings| {
+            let output = sine_output(&mut pedal_with(PedalSettings { drive: 1.0, ..settings }), 500.0, 0.5);
+            20.0 * (harmonic_level(&output, 1000.0) / harmonic_level(&output, 500.0)).log10()
+        };
+        let booster = second_harmonic_db(bright);
+        let clean = second_harmonic_db(PedalSettings { model: PedalModel::CleanBoost, ..bright });
+        assert!(booster > -30.0, "booster 2nd harmonic at {} dB", booster);
+        assert!(clean < booster - 30.0, "clean boost 2nd harmonic at {} dB", clean);
+
+        let output = sine_output(&mut pedal_with(PedalSettings { drive: 1.0, ..bright }), 500.0, 0.5);
+        let mean = output.iter().sum::<f32>() / output.len() as f32;
+        assert!(mean.abs() < 0.01, "mean {}", mean);
+    }
+
+    #[test]
+    fn test_clean_boost_is_flat() {
+        let boost = PedalSettings {
+            model: PedalModel::CleanBoost,
+            drive: 1.0,
+            level_db: -6.0,
+            ..PedalSettings::default()
+        };
+        for frequency in [200.0, 1000.0, 5000.0] {
+            let gain = response_db(boost, frequency);
+            assert!((gain - 14.0).abs() < 0.5, "{} dB at {} Hz", gain, frequency);
+        }
+    }
+}
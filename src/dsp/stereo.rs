@@ -0,0 +1,513 @@
+use super::{
+    BlockParams, CabinetLoader, CabinetType, CompressorSettings, ConvolutionMode, DelaySettings, FuzzSettings,
+    GuitarFxProcessor, NoiseGateSettings, OversamplingFactor, OversamplingPhase, PedalSettings, Prepare,
+    ToneStackModel, UserIrSlot, WaveshaperMode, MAX_BLOCK_SIZE,
+};
+use super::delay::StereoDelay;
+use super::filters::BiquadFilter;
+
+/// Host channel configuration the processor chain is set up for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelLayout {
+    /// 1 in / 1 out - single chain, no stereo output stage
+    Mono,
+
+    /// 1 in / 2 out - one amp feeding two cabinets and the stereo widener
+    MonoToStereo,
+
+    /// 2 in / 2 out - one full chain per channel
+    Stereo,
+}
+
+impl ChannelLayout {
+    /// Pick the layout matching the host's main input/output channel counts
+    pub fn from_channel_counts(inputs: u32, outputs: u32) -> Self {
+        match (inputs, outputs) {
+            (1, 1) => ChannelLayout::Mono,
+            (1, 2) => ChannelLayout::MonoToStereo,
+            _ => ChannelLayout::Stereo,
+        }
+    }
+}
+
+/// Stereo processing modes for the two-channel signal path
+/// Both modes keep fully separate filter, tube and convolution state per channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelMode {
+    /// Two independent mono chains - each channel tracks its own dynamics
+    /// Best for re-amped doubles where left and right are different takes
+    DualMono,
+
+    /// Linked stereo - shared parameters and a shared dynamics detector
+    /// Keeps the stereo image of DI tracks stable when one side hits harder
+    Stereo,
+}
+
+impl nih_plug::prelude::Enum for ChannelMode {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Dual Mono",
+            "Stereo",
+        ]
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "dual_mono",
+            "stereo",
+        ])
+    }
+
+    fn to_index(self) -> usize {
+        match self {
+            ChannelMode::DualMono => 0,
+            ChannelMode::Stereo => 1,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => ChannelMode::DualMono,
+            1 => ChannelMode::Stereo,
+            _ => ChannelMode::DualMono, // Default fallback
+        }
+    }
+}
+
+/// Mid/side stereo widener for mono sources - O(1) per sample
+/// Synthesizes a side signal from a short, high-passed delay of the mono input.
+/// Since L = M + S and R = M - S, summing back to mono cancels the side signal
+/// completely, so widening never introduces comb filtering in mono playback.
+pub struct StereoWidener {
+    /// Circular delay line for the decorrelated side signal
+    delay_line: Vec<f32>,
+
+    /// Write position in the delay line
+    write_pos: usize,
+
+    /// Keeps low end centered - only content above ~300 Hz is widened
+    side_filter: BiquadFilter,
+
+    /// Side signal level: 0.0 = mono copy, 1.0 = full width
+    width: f32,
+}
+
+impl StereoWidener {
+    /// Side signal delay - short enough to read as width rather than echo
+    const DELAY_MS: f32 = 12.0;
+
+    /// Create widener for given sample rate - allocates the delay line once
+    pub fn new(sample_rate: f32) -> Self {
+        let delay_samples = ((Self::DELAY_MS * 0.001 * sample_rate) as usize).max(1);
+        let mut side_filter = BiquadFilter::new();
+        side_filter.high_pass(300.0, 0.707, sample_rate);
+
+        Self {
+            delay_line: vec![0.0; delay_samples],
+            write_pos: 0,
+            side_filter,
+            width: 0.0,
+        }
+    }
+
+    /// Set stereo width - O(1) parameter update
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 1.0);
+    }
+
+    /// Spread a mono sample into a stereo pair - O(1) complexity
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        let delayed = self.delay_line[self.write_pos];
+        self.delay_line[self.write_pos] = input;
+        self.write_pos = (self.write_pos + 1) % self.delay_line.len();
+
+        let side = self.side_filter.process(delayed) * self.width;
+        (input + side, input - side)
+    }
+}
+
+impl Prepare for StereoWidener {
+    /// Resize the delay line and retune the side filter for the new rate
+    fn prepare(&mut self, sample_rate: f32, _max_block: usize) {
+        let delay_samples = ((Self::DELAY_MS * 0.001 * sample_rate) as usize).max(1);
+        self.delay_line = vec![0.0; delay_samples];
+        self.write_pos = 0;
+        self.side_filter.high_pass(300.0, 0.707, sample_rate);
+        self.side_filter.reset();
+    }
+}
+
+/// Two-channel wrapper around `GuitarFxProcessor` - one processor per channel
+/// Parameters are applied to both channels identically, state is never shared
+/// between the chains - only the stereo delay after them sees both sides
+pub struct StereoProcessor {
+    /// Per-channel processing chains: index 0 = left, 1 = right
+    channels: [GuitarFxProcessor; 2],
+
+    /// Current channel linking mode
+    mode: ChannelMode,
+
+    /// Host channel configuration selected at initialization
+    layout: ChannelLayout,
+
+    /// Stereo output stage used in mono-to-stereo layouts
+    widener: StereoWidener,
+
+    /// Delay after the cabinets - shared by both channels so ping-pong can cross them
+    delay: StereoDelay,
+}
+
+impl StereoProcessor {
+    /// Create new stereo processor with two independent chains
+    pub fn new() -> Self {
+        Self {
+            channels: [GuitarFxProcessor::new(), GuitarFxProcessor::new()],
+            mode: ChannelMode::DualMono,
+            layout: ChannelLayout::Stereo,
+            widener: StereoWidener::new(44100.0),
+            delay: StereoDelay::new(44100.0),
+        }
+    }
+
+    /// Initialize the chain for given sample rate and host channel layout
+    pub fn initialize(&mut self, sample_rate: f32, layout: ChannelLayout) {
+        for channel in &mut self.channels {
+            channel.initialize(sample_rate);
+        }
+        
+        // Cabinet slot panning only applies when there are two outputs
+        let [left, right] = &mut self.channels;
+        if layout == ChannelLayout::Mono {
+            left.set_output_channel(None);
+        } else {
+            left.set_output_channel(Some(0));
+            right.set_output_channel(Some(1));
+        }
+        self.layout = layout;
+        self.widener.prepare(sample_rate, MAX_BLOCK_SIZE);
+        self.delay.prepare(sample_rate, MAX_BLOCK_SIZE);
+    }
+
+    /// Get the channel layout selected at initialization
+    pub fn layout(&self) -> ChannelLayout {
+        self.layout
+    }
+
+    /// Select dual-mono or linked stereo processing - O(1)
+    pub fn set_channel_mode(&mut self, mode: ChannelMode) {
+        self.mode = mode;
+    }
+
+    /// Select the cabinet convolution engine on both channels
+    pub fn set_convolution_mode(&mut self, mode: ConvolutionMode) {
+        for channel in &mut self.channels {
+            channel.set_convolution_mode(mode);
+        }
+    }
+
+    /// Select the amp's oversampling ratio and filter phase on both channels
+    pub fn set_oversampling(&mut self, factor: OversamplingFactor, phase: OversamplingPhase) {
+        for channel in &mut self.channels {
+            channel.set_oversampling(factor, phase);
+        }
+    }
+
+    /// Select plain or ADAA waveshaping on both channels
+    pub fn set_waveshaper_mode(&mut self, mode: WaveshaperMode) {
+        for channel in &mut self.channels {
+            channel.set_waveshaper_mode(mode);
+        }
+    }
+
+    /// Apply noise gate settings on both channels
+    pub fn set_noise_gate(&mut self, settings: NoiseGateSettings) {
+        for channel in &mut self.channels {
+            channel.set_noise_gate(settings);
+        }
+    }
+
+    /// Apply compressor settings on both channels
+    /// Both detectors see the same linked key, so the channels compress together
+    pub fn set_compressor(&mut self, settings: CompressorSettings) {
+        for channel in &mut self.channels {
+            channel.set_compressor(settings);
+        }
+    }
+
+    /// Apply fuzz pedal settings on both channels
+    pub fn set_fuzz(&mut self, settings: FuzzSettings) {
+        for channel in &mut self.channels {
+            channel.set_fuzz(settings);
+        }
+    }
+
+    /// Apply drive pedal settings on both channels
+    pub fn set_pedal(&mut self, settings: PedalSettings) {
+        for channel in &mut self.channels {
+            channel.set_pedal(settings);
+        }
+    }
+
+    /// Configure the delay after the cabinets
+    pub fn set_delay(&mut self, settings: DelaySettings) {
+        self.delay.set_settings(settings);
+    }
+
+    /// Select the tone stack circuit on both channels
+    pub fn set_tone_stack_model(&mut self, model: ToneStackModel) {
+        for channel in &mut self.channels {
+            channel.set_tone_stack_model(model);
+        }
+    }
+
+    /// Load the main and blend cabinets on both channels in place
+    /// Switches instantly - for `initialize`, later switches go through `install_cabinets`
+    pub fn load_cabinets(&mut self, cabinet_types: [CabinetType; 2]) {
+        for channel in &mut self.channels {
+            channel.load_cabinets(cabinet_types);
+        }
+    }
+
+    /// Set the crossfade time for cabinet switches on both channels
+    pub fn set_cabinet_crossfade(&mut self, crossfade_ms: f32) {
+        for channel in &mut self.channels {
+            channel.set_cabinet_crossfade(crossfade_ms);
+        }
+    }
+
+    /// Whether either channel is still crossfading between two cabinets
+    fn is_cabinet_crossfading(&self) -> bool {
+        self.channels.iter().any(GuitarFxProcessor::is_cabinet_crossfading)
+    }
+
+    /// Pick up cabinets prepared by the background loader and crossfade to them
+    /// Waits while a crossfade is still running, so switches never cut one short.
+    /// Cabinets prepared for an outdated convolution mode are left to `CabinetLoader::take_stale`.
+    /// Real-time safe: never blocks and never allocates
+    pub fn install_cabinets(&mut self, loader: &CabinetLoader) -> bool {
+        if self.is_cabinet_crossfading() {
+            return false;
+        }
+
+        let channels = &mut self.channels;
+        loader.install_pending(|prepared| {
+            let (slot, cabinet_type, passthrough) = (prepared.slot, prepared.cabinet_type, prepared.passthrough);
+            let mut installed = true;
+            for (channel, prepared_channel) in channels.iter_mut().zip(prepared.channels.iter_mut()) {
+                installed &= channel.install_cabinet(slot, cabinet_type, passthrough, prepared_channel);
+            }
+            installed
+        })
+    }
+
+    /// Pick up a user IR prepared by the background loader, if one is waiting
+    /// Waits while a cabinet crossfade is still running.
+    /// Real-time safe: never blocks and never allocates
+    pub fn install_user_ir(&mut self, slot: &UserIrSlot) -> bool {
+        if self.is_cabinet_crossfading() {
+            return false;
+        }
+
+        let channels = &mut self.channels;
+        slot.install_pending(|prepared| {
+            let mut installed = true;
+            let prepared_channels = prepared.channels.iter_mut().zip(prepared.blend_engines.iter_mut());
+            for (channel, (prepared, blend)) in channels.iter_mut().zip(prepared_channels) {
+                installed &= channel.install_user_ir(prepared, blend);
+            }
+            installed
+        })
+    }
+
+    /// Process a mono block through the left chain only
+    /// Used when the host gives us a single channel
+    pub fn process_mono_block(&mut self, samples: &mut [f32], params: &BlockParams) {
+        self.channels[0].process_block(samples, params);
+        self.delay.process_mono_block(samples);
+    }
+
+    /// Process one stereo block - each channel runs through its own chain
+    /// Parameters are linked: both channels see the same `BlockParams`.
+    /// In linked stereo mode both detectors are keyed from the louder channel.
+    /// In mono-to-stereo layouts only the left input is used.
+    /// Stereo IRs give each output its own IR channel; 4-channel IRs also mix in
+    /// the opposite amp output (true stereo). The delay runs last, on both outputs.
+    pub fn process_block(&mut self, left: &mut [f32], right: &mut [f32], params: &BlockParams) {
+        debug_assert_eq!(left.len(), right.len());
+        let len = left.len();
+        let [left_chain, right_chain] = &mut self.channels;
+
+        match (self.layout, self.mode) {
+            (ChannelLayout::MonoToStereo, _) => {
+                // One amp, two cabinets - the right chain's amp section stays idle
+                let mut key = [0.0; MAX_BLOCK_SIZE];
+                key[..len].copy_from_slice(left);
+                left_chain.process_amp_block(left, &key[..len], params);
+                right.copy_from_slice(left);
+                left_chain.process_output_block(left, params);
+                right_chain.process_output_block(right, params);
+
+                for i in 0..len {
+                    self.widener.set_width(params.stereo_width[i]);
+                    let mid = 0.5 * (left[i] + right[i]);
+                    let side = 0.5 * (left[i] - right[i]);
+                    let (wide_left, wide_right) = self.widener.process(mid);
+                    left[i] = wide_left + side;
+                    right[i] = wide_right - side;
+                }
+            }
+            (_, mode) => {
+                let mut left_key = [0.0; MAX_BLOCK_SIZE];
+                let mut right_key = [0.0; MAX_BLOCK_SIZE];
+                left_key[..len].copy_from_slice(left);
+                right_key[..len].copy_from_slice(right);
+                if mode == ChannelMode::Stereo {
+                    for (left_key, right_key) in left_key.iter_mut().zip(right_key.iter_mut()) {
+                        *left_key = left_key.abs().max(right_key.abs());
+                        *right_key = *left_key;
+                    }
+                }
+                left_chain.process_amp_block(left, &left_key[..len], params);
+                right_chain.process_amp_block(right, &right_key[..len], params);
+
+                // True-stereo cabinet IRs feed each output from both amps
+                let mut left_amp = [0.0; MAX_BLOCK_SIZE];
+                left_amp[..len].copy_from_slice(left);
+                left_chain.process_output_block_with_cross(left, right, params);
+                right_chain.process_output_block_with_cross(right, &left_amp[..len], params);
+            }
+        }
+        self.delay.process_block(left, right);
+    }
+
+    /// Get processing latency - identical for both channels
+    pub fn get_latency(&self) -> usize {
+        self.channels[0].get_latency()
+    }
+}
+
+impl Default for StereoProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Guitar-like test signal: decaying plucked tone with some harmonics
+    fn test_signal(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / 44100.0;
+                let envelope = (-t * 3.0).exp();
+                envelope * (0.6 * (2.0 * std::f32::consts::PI * 110.0 * t).sin()
+                    + 0.3 * (2.0 * std::f32::consts::PI * 330.0 * t).sin())
+            })
+            .collect()
+    }
+
+    fn test_params() -> BlockParams {
+        BlockParams::constant(2.0, 8.0, 1.0, 3.0, -2.0, 4.0)
+    }
+
+    fn assert_identical_channels(mode: ChannelMode) {
+        let mut processor = StereoProcessor::new();
+        processor.initialize(44100.0, ChannelLayout::Stereo);
+        processor.set_channel_mode(mode);
+        let params = test_params();
+
+        for block in test_signal(4096).chunks(MAX_BLOCK_SIZE) {
+            let mut left = block.to_vec();
+            let mut right = block.to_vec();
+            processor.process_block(&mut left, &mut right, &params);
+            assert_eq!(left, right);
+        }
+    }
+
+    #[test]
+    fn test_dual_mono_identical_channels() {
+        assert_identical_channels(ChannelMode::DualMono);
+    }
+
+    #[test]
+    fn test_linked_stereo_identical_channels() {
+        assert_identical_channels(ChannelMode::Stereo);
+    }
+
+    #[test]
+    fn test_channels_match_mono_processor() {
+        // Each channel must behave exactly like a standalone mono chain
+        let mut stereo = StereoProcessor::new();
+        let mut mono = GuitarFxProcessor::new();
+        stereo.initialize(44100.0, ChannelLayout::Stereo);
+        mono.initialize(44100.0);
+        let params = test_params();
+
+        for block in test_signal(2048).chunks(MAX_BLOCK_SIZE) {
+            let mut left = block.to_vec();
+            let mut right: Vec<f32> = block.iter().map(|x| -x).collect();
+            let mut expected = block.to_vec();
+            stereo.process_block(&mut left, &mut right, &params);
+            mono.process_block(&mut expected, &params);
+            assert_eq!(left, expected);
+        }
+    }
+
+    #[test]
+    fn test_block_matches_per_sample_processing() {
+        let mut block_processor = GuitarFxProcessor::new();
+        let mut sample_processor = GuitarFxProcessor::new();
+        let params = test_params();
+        sample_processor.update_tone_controls(3.0, -2.0, 4.0);
+
+        for block in test_signal(2048).chunks(MAX_BLOCK_SIZE) {
+            let mut samples = block.to_vec();
+            block_processor.process_block(&mut samples, &params);
+            for (&input, &output) in block.iter().zip(&samples) {
+                let expected = sample_processor.process_sample(input, 2.0, 8.0, 1.0);
+                assert!((output - expected).abs() <= 1e-6 * expected.abs().max(1.0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_mono_to_stereo_width() {
+        let signal = test_signal(4096);
+
+        // Zero width is a plain copy of the mono chain
+        let mut narrow = StereoProcessor::new();
+        narrow.initialize(44100.0, ChannelLayout::MonoToStereo);
+        let params = BlockParams::constant(1.0, 4.0, 1.0, 0.0, 0.0, 0.0);
+        for block in signal.chunks(MAX_BLOCK_SIZE) {
+            let mut left = block.to_vec();
+            let mut right = vec![0.0; block.len()];
+            narrow.process_block(&mut left, &mut right, &params);
+            assert_eq!(left, right);
+        }
+
+        // Widened output differs between sides but still folds back to the mono chain
+        let mut wide = StereoProcessor::new();
+        let mut mono = GuitarFxProcessor::new();
+        wide.initialize(44100.0, ChannelLayout::MonoToStereo);
+        mono.initialize(44100.0);
+        let mut params = params;
+        params.stereo_width = [1.0; MAX_BLOCK_SIZE];
+
+        let mut side_energy = 0.0;
+        for block in signal.chunks(MAX_BLOCK_SIZE) {
+            let mut left = block.to_vec();
+            let mut right = vec![0.0; block.len()];
+            let mut expected = block.to_vec();
+            wide.process_block(&mut left, &mut right, &params);
+            mono.process_block(&mut expected, &params);
+            for i in 0..block.len() {
+                let tolerance = 1e-5 * left[i].abs().max(right[i].abs()).max(1.0);
+                assert!((0.5 * (left[i] + right[i]) - expected[i]).abs() <= tolerance);
+                side_energy += (left[i] - right[i]) * (left[i] - right[i]);
+            }
+        }
+        assert!(side_energy > 0.0, "widener produced no stereo content");
+    }
+}
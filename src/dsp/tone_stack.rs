@@ -0,0 +1,553 @@
+use num_complex::Complex;
+
+/// Tone stack circuit the bass/mid/treble controls drive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneStackModel {
+    /// Three independent peaking EQs at 100 Hz, 500 Hz and 3 kHz - no interaction, no scoop
+    Modern,
+
+    /// Fender '59 Bassman treble/bass/mid network
+    Fender,
+
+    /// Marshall JCM800 - the Fender topology with a bigger treble cap and a smaller slope resistor
+    Marshall,
+
+    /// Vox AC30 top boost, treated as a TMB network with a fixed 10k mid resistor - the mid control is inactive
+    Vox,
+
+    /// Passive James/Baxandall stack - separate bass and treble shelves with a flat middle
+    James,
+}
+
+impl nih_plug::prelude::Enum for ToneStackModel {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Modern",
+            "Fender",
+            "Marshall",
+            "Vox",
+            "James",
+        ]
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "modern",
+            "fender",
+            "marshall",
+            "vox",
+            "james",
+        ])
+    }
+
+    fn to_index(self) -> usize {
+        match self {
+            ToneStackModel::Modern => 0,
+            ToneStackModel::Fender => 1,
+            ToneStackModel::Marshall => 2,
+            ToneStackModel::Vox => 3,
+            ToneStackModel::James => 4,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => ToneStackModel::Modern,
+            1 => ToneStackModel::Fender,
+            2 => ToneStackModel::Marshall,
+            3 => ToneStackModel::Vox,
+            4 => ToneStackModel::James,
+            _ => ToneStackModel::Modern, // Default fallback
+        }
+    }
+}
+
+/// Highest filter order of any circuit model - one pole per capacitor
+const MAX_ORDER: usize = 4;
+
+/// Most internal nodes of any circuit model
+const MAX_NODES: usize = 6;
+
+/// Most components of any circuit model, counting each pot half separately
+const MAX_ELEMENTS: usize = 12;
+
+/// Admittance and frequency scales for the nodal analysis
+/// Conductances are expressed relative to 100k and s relative to 10^4 rad/s, so the
+/// matrix entries of every model stay near 1 and the determinants don't lose precision
+const CONDUCTANCE_SCALE: f64 = 1e-5;
+const FREQUENCY_SCALE: f64 = 1e4;
+
+/// Pot sections never go below this resistance so a fully turned pot doesn't short a node
+const MIN_RESISTANCE: f64 = 1.0;
+
+/// Polynomial in the normalized Laplace variable, lowest power first
+type Polynomial = [f64; MAX_NODES + 1];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Node {
+    /// Driving source - the preamp plate, 1 V
+    Input,
+    Ground,
+    Internal(usize),
+}
+
+#[derive(Clone, Copy)]
+enum Part {
+    Resistor(f64),
+    Capacitor(f64),
+}
+
+#[derive(Clone, Copy)]
+struct Element {
+    from: Node,
+    to: Node,
+    part: Part,
+}
+
+/// Fixed-capacity component list - built on the audio thread whenever a control moves
+struct Netlist {
+    elements: [Element; MAX_ELEMENTS],
+    len: usize,
+    /// Number of internal nodes
+    nodes: usize,
+    output: usize,
+}
+
+impl Netlist {
+    fn new(nodes: usize, output: usize) -> Self {
+        let empty = Element { from: Node::Ground, to: Node::Ground, part: Part::Resistor(1.0) };
+        Self { elements: [empty; MAX_ELEMENTS], len: 0, nodes, output }
+    }
+
+    fn add(&mut self, from: Node, to: Node, part: Part) {
+        self.elements[self.len] = Element { from, to, part };
+        self.len += 1;
+    }
+
+    fn resistor(&mut self, from: Node, to: Node, ohms: f64) {
+        self.add(from, to, Part::Resistor(ohms.max(MIN_RESISTANCE)));
+    }
+
+    fn capacitor(&mut self, from: Node, to: Node, farads: f64) {
+        self.add(from, to, Part::Capacitor(farads));
+    }
+
+    /// Pot with its wiper `position` (0-1) of the way from `bottom` to `top`
+    fn pot(&mut self, top: Node, wiper: Node, bottom: Node, ohms: f64, position: f64) {
+        self.resistor(top, wiper, (1.0 - position) * ohms);
+        self.resistor(wiper, bottom, position * ohms);
+    }
+
+    /// Number of capacitors - the order of the network's transfer function
+    fn order(&self) -> usize {
+        self.elements[..self.len].iter().filter(|element| matches!(element.part, Part::Capacitor(_))).count()
+    }
+
+    /// Transfer function from the input to the output node as numerator and
+    /// denominator polynomials in the normalized s - O(N 2^N) for N nodes
+    ///
+    /// Nodal analysis: Y(s) V = I(s), every entry a first-order polynomial G + sC.
+    /// By Cramer's rule V_out = det(Y with the output column replaced by I) / det(Y).
+    fn transfer_function(&self) -> (Polynomial, Polynomial) {
+        let mut admittance = [[[0.0; 2]; MAX_NODES]; MAX_NODES];
+        let mut source = [[0.0; 2]; MAX_NODES];
+
+        for element in &self.elements[..self.len] {
+            let value = match element.part {
+                Part::Resistor(ohms) => [1.0 / (ohms * CONDUCTANCE_SCALE), 0.0],
+                Part::Capacitor(farads) => [0.0, farads * FREQUENCY_SCALE / CONDUCTANCE_SCALE],
+            };
+            let stamp = |entry: &mut [f64; 2], sign: f64| {
+                entry[0] += sign * value[0];
+                entry[1] += sign * value[1];
+            };
+            match (element.from, element.to) {
+                (Node::Internal(a), Node::Internal(b)) => {
+                    stamp(&mut admittance[a][a], 1.0);
+                    stamp(&mut admittance[b][b], 1.0);
+                    stamp(&mut admittance[a][b], -1.0);
+                    stamp(&mut admittance[b][a], -1.0);
+                }
+                (Node::Internal(a), Node::Input) | (Node::Input, Node::Internal(a)) => {
+                    stamp(&mut admittance[a][a], 1.0);
+                    stamp(&mut source[a], 1.0);
+                }
+                (Node::Internal(a), Node::Ground) | (Node::Ground, Node::Internal(a)) => {
+                    stamp(&mut admittance[a][a], 1.0);
+                }
+                _ => {}
+            }
+        }
+
+        let denominator = determinant(&admittance, self.nodes);
+        for (row, entry) in admittance.iter_mut().zip(source) {
+            row[self.output] = entry;
+        }
+        let numerator = determinant(&admittance, self.nodes);
+        (numerator, denominator)
+    }
+}
+
+/// Determinant of the leading `n`x`n` block of a first-order polynomial matrix
+/// Expands along rows over column subsets, so it needs no division or pivoting
+fn determinant(matrix: &[[[f64; 2]; MAX_NODES]; MAX_NODES], n: usize) -> Polynomial {
+    // minors[mask] = determinant of the first popcount(mask) rows in the columns of `mask`
+    let mut minors = [[0.0; MAX_NODES + 1]; 1 << MAX_NODES];
+    minors[0][0] = 1.0;
+
+    for mask in 1usize..(1 << n) {
+        let row = mask.count_ones() as usize - 1;
+        let mut minor = [0.0; MAX_NODES + 1];
+        for (position, column) in (0..n).filter(|column| mask & (1 << column) != 0).enumerate() {
+            let entry = matrix[row][column];
+            if entry == [0.0, 0.0] {
+                continue;
+            }
+            let sign = [1.0, -1.0][(row + position) % 2];
+            let rest = &minors[mask & !(1 << column)];
+            for power in 0..=row {
+                minor[power] += sign * entry[0] * rest[power];
+                minor[power + 1] += sign * entry[1] * rest[power];
+            }
+        }
+        minors[mask] = minor;
+    }
+    minors[(1 << n) - 1]
+}
+
+/// Component values of a treble/bass/mid network
+struct TmbComponents {
+    /// Treble pot, bass pot, mid pot (or fixed mid resistor), slope resistor
+    r1: f64,
+    r2: f64,
+    r3: f64,
+    r4: f64,
+    /// Treble, bass and mid caps
+    c1: f64,
+    c2: f64,
+    c3: f64,
+    /// Whether R3 is a pot - the Vox has a fixed resistor there
+    mid_pot: bool,
+}
+
+const FENDER: TmbComponents = TmbComponents {
+    r1: 250e3, r2: 1e6, r3: 25e3, r4: 56e3, c1: 250e-12, c2: 20e-9, c3: 20e-9, mid_pot: true,
+};
+
+const MARSHALL: TmbComponents = TmbComponents {
+    r1: 220e3, r2: 1e6, r3: 22e3, r4: 33e3, c1: 470e-12, c2: 22e-9, c3: 22e-9, mid_pot: true,
+};
+
+const VOX: TmbComponents = TmbComponents {
+    r1: 1e6, r2: 1e6, r3: 10e3, r4: 100e3, c1: 50e-12, c2: 22e-9, c3: 22e-9, mid_pot: false,
+};
+
+/// Treble/bass/mid network as analysed by Yeh and Smith
+/// The input feeds the treble pot through C1 and, through the slope resistor, the bass
+/// and mid caps. Treble pot, bass pot and mid pot are in series to ground, so every
+/// control shifts the others' corner frequencies - the source of the mid scoop.
+fn tmb_netlist(components: &TmbComponents, treble: f64, mid: f64, bass: f64) -> Netlist {
+    const TREBLE_TOP: Node = Node::Internal(0);
+    const OUTPUT: Node = Node::Internal(1);
+    const BASS_TOP: Node = Node::Internal(2);
+    const MID_TOP: Node = Node::Internal(3);
+    const MID_WIPER: Node = Node::Internal(4);
+    const SLOPE: Node = Node::Internal(5);
+
+    let mid = if components.mid_pot { mid } else { 1.0 };
+    let mut netlist = Netlist::new(6, 1);
+    netlist.capacitor(Node::Input, TREBLE_TOP, components.c1);
+    netlist.pot(TREBLE_TOP, OUTPUT, BASS_TOP, components.r1, treble);
+    netlist.resistor(BASS_TOP, MID_TOP, audio_taper(bass) * components.r2);
+    netlist.pot(MID_TOP, MID_WIPER, Node::Ground, components.r3, mid);
+    netlist.resistor(Node::Input, SLOPE, components.r4);
+    netlist.capacitor(SLOPE, BASS_TOP, components.c2);
+    netlist.capacitor(SLOPE, MID_WIPER, components.c3);
+    netlist
+}
+
+/// Passive James stack: a resistive bass shelf and a capacitive treble shelf,
+/// each dividing by about 11 in the middle band, joined at the output
+/// With audio taper pots that same division holds at both ends at noon, so the response is flat there
+fn james_netlist(treble: f64, bass: f64) -> Netlist {
+    const BASS_TOP: Node = Node::Internal(0);
+    const BASS_WIPER: Node = Node::Internal(1);
+    const BASS_BOTTOM: Node = Node::Internal(2);
+    const TREBLE_TOP: Node = Node::Internal(3);
+    const TREBLE_BOTTOM: Node = Node::Internal(4);
+    const OUTPUT: Node = Node::Internal(5);
+
+    let mut netlist = Netlist::new(6, 5);
+    netlist.resistor(Node::Input, BASS_TOP, 100e3);
+    netlist.pot(BASS_TOP, BASS_WIPER, BASS_BOTTOM, 1e6, audio_taper(bass));
+    netlist.capacitor(BASS_TOP, BASS_WIPER, 2.2e-9);
+    netlist.capacitor(BASS_WIPER, BASS_BOTTOM, 22e-9);
+    netlist.resistor(BASS_BOTTOM, Node::Ground, 10e3);
+    netlist.resistor(BASS_WIPER, OUTPUT, 47e3);
+
+    netlist.capacitor(Node::Input, TREBLE_TOP, 1e-9);
+    netlist.pot(TREBLE_TOP, OUTPUT, TREBLE_BOTTOM, 1e6, audio_taper(treble));
+    netlist.capacitor(TREBLE_BOTTOM, Node::Ground, 10e-9);
+    netlist
+}
+
+/// Netlist for a circuit model with pot positions (0-1) - `None` for the EQ model
+fn model_netlist(model: ToneStackModel, treble: f64, mid: f64, bass: f64) -> Option<Netlist> {
+    match model {
+        ToneStackModel::Modern => None,
+        ToneStackModel::Fender => Some(tmb_netlist(&FENDER, treble, mid, bass)),
+        ToneStackModel::Marshall => Some(tmb_netlist(&MARSHALL, treble, mid, bass)),
+        ToneStackModel::Vox => Some(tmb_netlist(&VOX, treble, mid, bass)),
+        ToneStackModel::James => Some(james_netlist(treble, bass)),
+    }
+}
+
+/// Audio (log) taper: 10% of the track at noon, like the A-taper bass pots in these circuits
+fn audio_taper(position: f64) -> f64 {
+    const TAPER_BASE: f64 = 81.0;
+    (TAPER_BASE.powf(position) - 1.0) / (TAPER_BASE - 1.0)
+}
+
+/// Map a tone control in dB (-12 to +12, 0 = noon) to a pot position
+fn pot_position(control_db: f32) -> f64 {
+    ((control_db as f64 + 12.0) / 24.0).clamp(0.0, 1.0)
+}
+
+/// Analog response of a transfer function at `frequency` Hz
+fn analog_response(numerator: &Polynomial, denominator: &Polynomial, frequency: f64) -> Complex<f64> {
+    let s = Complex::new(0.0, 2.0 * std::f64::consts::PI * frequency / FREQUENCY_SCALE);
+    let evaluate = |polynomial: &Polynomial| polynomial.iter().rev().fold(Complex::new(0.0, 0.0), |sum, &c| sum * s + c);
+    evaluate(numerator) / evaluate(denominator)
+}
+
+/// Passive tone stack circuit discretized with the bilinear transform - O(order) per sample
+///
+/// Whenever a control moves, the netlist is rebuilt with the new pot positions, its
+/// transfer function is derived by nodal analysis and mapped to the z-domain, like Yeh
+/// and Smith's closed-form Bassman coefficients but for any of the circuits above.
+/// Runs in f64: the poles sit close to z = 1, especially when oversampled.
+pub struct CircuitToneStack {
+    model: ToneStackModel,
+    sample_rate: f32,
+
+    /// Numerator and denominator coefficients, a[0] normalized to 1
+    b: [f64; MAX_ORDER + 1],
+    a: [f64; MAX_ORDER + 1],
+    order: usize,
+
+    /// Direct form II transposed state
+    state: [f64; MAX_ORDER],
+
+    /// Gain that brings the loudest band at noon settings to unity
+    /// Passive stacks lose 10-20 dB, which the real amp makes up in the next stage
+    makeup_gain: f64,
+}
+
+impl CircuitToneStack {
+    pub fn new(model: ToneStackModel, sample_rate: f32) -> Self {
+        let mut stack = Self {
+            model,
+            sample_rate,
+            b: [0.0; MAX_ORDER + 1],
+            a: [0.0; MAX_ORDER + 1],
+            order: 0,
+            state: [0.0; MAX_ORDER],
+            makeup_gain: 1.0,
+        };
+        stack.makeup_gain = stack.noon_makeup_gain();
+        stack.update_controls(0.0, 0.0, 0.0);
+        stack
+    }
+
+    /// Loudest band of the noon response, searched on a log grid from 20 Hz to 20 kHz
+    fn noon_makeup_gain(&self) -> f64 {
+        let Some(netlist) = model_netlist(self.model, 0.5, 0.5, 0.5) else {
+            return 1.0;
+        };
+        let (numerator, denominator) = netlist.transfer_function();
+        let peak = (0..64)
+            .map(|step| 20.0 * 1000f64.powf(step as f64 / 63.0))
+            .map(|frequency| analog_response(&numerator, &denominator, frequency).norm())
+            .fold(0.0, f64::max);
+        if peak > 0.0 { 1.0 / peak } else { 1.0 }
+    }
+
+    /// Recompute the filter for new control values in dB - O(1), no allocation
+    pub fn update_controls(&mut self, bass_db: f32, mid_db: f32, treble_db: f32) {
+        let Some(netlist) = model_netlist(self.model, pot_position(treble_db), pot_position(mid_db), pot_position(bass_db)) else {
+            return;
+        };
+        let order = netlist.order();
+        let (numerator, denominator) = netlist.transfer_function();
+
+        // Bilinear transform: s = k (1 - z^-1) / (1 + z^-1), scaled like the analysis
+        // Multiplying through by (1 + z^-1)^N turns every s^i into k^i (1 - z^-1)^i (1 + z^-1)^(N - i)
+        let k = 2.0 * self.sample_rate as f64 / FREQUENCY_SCALE;
+        let mut b = [0.0; MAX_ORDER + 1];
+        let mut a = [0.0; MAX_ORDER + 1];
+        for power in 0..=order {
+            let mut term = [0.0; MAX_ORDER + 1];
+            term[0] = k.powi(power as i32);
+            for factor in 0..order {
+                let sign = if factor < power { -1.0 } else { 1.0 };
+                for i in (1..=factor + 1).rev() {
+                    term[i] += sign * term[i - 1];
+                }
+            }
+            for i in 0..=order {
+                b[i] += numerator[power] * term[i];
+                a[i] += denominator[power] * term[i];
+            }
+        }
+
+        let a0 = a[0];
+        for i in 0..=order {
+            self.b[i] = b[i] / a0 * self.makeup_gain;
+            self.a[i] = a[i] / a0;
+        }
+        if order != self.order {
+            self.state = [0.0; MAX_ORDER];
+            self.order = order;
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let x = input as f64;
+        let y = self.b[0] * x + self.state[0];
+        for i in 0..self.order {
+            let next = if i + 1 < self.order { self.state[i + 1] } else { 0.0 };
+            self.state[i] = self.b[i + 1] * x - self.a[i + 1] * y + next;
+        }
+        y as f32
+    }
+
+    pub fn reset(&mut self) {
+        self.state = [0.0; MAX_ORDER];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Magnitude in dB of the discrete filter at `frequency`, from its coefficients
+    fn digital_response_db(stack: &CircuitToneStack, frequency: f64) -> f64 {
+        let w = 2.0 * std::f64::consts::PI * frequency / stack.sample_rate as f64;
+        let z_inv = Complex::from_polar(1.0, -w);
+        let evaluate = |coefficients: &[f64]| coefficients.iter().rev().fold(Complex::new(0.0, 0.0), |sum, &c| sum * z_inv + c);
+        let order = stack.order;
+        20.0 * (evaluate(&stack.b[..=order]) / evaluate(&stack.a[..=order])).norm().log10()
+    }
+
+    /// Node voltages solved directly at one frequency with complex Gaussian elimination
+    fn ac_analysis(netlist: &Netlist, frequency: f64) -> Complex<f64> {
+        let n = netlist.nodes;
+        let s = Complex::new(0.0, 2.0 * std::f64::consts::PI * frequency);
+        let mut matrix = vec![vec![Complex::new(0.0, 0.0); n + 1]; n];
+        for element in &netlist.elements[..netlist.len] {
+            let y = match element.part {
+                Part::Resistor(ohms) => Complex::new(1.0 / ohms, 0.0),
+                Part::Capacitor(farads) => s * farads,
+            };
+            for (node, other) in [(element.from, element.to), (element.to, element.from)] {
+                if let Node::Internal(a) = node {
+                    matrix[a][a] += y;
+                    match other {
+                        Node::Internal(b) => matrix[a][b] -= y,
+                        Node::Input => matrix[a][n] += y,
+                        Node::Ground => {}
+                    }
+                }
+            }
+        }
+        for pivot in 0..n {
+            let best = (pivot..n).max_by(|&i, &j| matrix[i][pivot].norm().total_cmp(&matrix[j][pivot].norm())).unwrap();
+            matrix.swap(pivot, best);
+            for row in 0..n {
+                if row != pivot {
+                    let factor = matrix[row][pivot] / matrix[pivot][pivot];
+                    let pivot_row = matrix[pivot].clone();
+                    for (entry, value) in matrix[row].iter_mut().zip(pivot_row).skip(pivot) {
+                        *entry -= factor * value;
+                    }
+                }
+            }
+        }
+        matrix[netlist.output][n] / matrix[netlist.output][netlist.output]
+    }
+
+    #[test]
+    fn test_transfer_functions_match_ac_analysis() {
+        let settings = [(0.5, 0.5, 0.5), (0.0, 1.0, 0.2), (1.0, 0.0, 0.9), (0.3, 0.7, 1.0)];
+        for model in [ToneStackModel::Fender, ToneStackModel::Marshall, ToneStackModel::Vox, ToneStackModel::James] {
+            for &(treble, mid, bass) in &settings {
+                let netlist = model_netlist(model, treble, mid, bass).unwrap();
+                let (numerator, denominator) = netlist.transfer_function();
+                for frequency in [30.0, 200.0, 700.0, 2500.0, 9000.0] {
+                    let expected = ac_analysis(&netlist, frequency);
+                    let actual = analog_response(&numerator, &denominator, frequency);
+                    assert!(
+                        (actual - expected).norm() < 1e-6 * expected.norm().max(1e-3),
+                        "{:?} at {} Hz: {} vs {}", model, frequency, actual, expected
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tmb_mid_scoop_and_interaction() {
+        let noon = CircuitToneStack::new(ToneStackModel::Fender, 44100.0);
+        let low = digital_response_db(&noon, 80.0);
+        let middle = digital_response_db(&noon, 1000.0);
+        let high = digital_response_db(&noon, 8000.0);
+        assert!(middle < low - 4.0 && middle < high - 4.0, "no scoop: {} / {} / {} dB", low, middle, high);
+
+        // The makeup gain puts the loudest band at unity
+        assert!(low.max(high) < 0.5 && low.max(high) > -3.0);
+
+        let mut more_bass = CircuitToneStack::new(ToneStackModel::Fender, 44100.0);
+        more_bass.update_controls(12.0, 0.0, 0.0);
+        assert!(digital_response_db(&more_bass, 40.0) > digital_response_db(&noon, 40.0) + 3.0);
+
+        // Turning the treble up lifts the mids as well - the controls interact
+        let mut more_treble = CircuitToneStack::new(ToneStackModel::Fender, 44100.0);
+        more_treble.update_controls(0.0, 0.0, 12.0);
+        assert!(digital_response_db(&more_treble, 1000.0) > middle + 2.0);
+
+        // The James stack is flat at noon and shelves both ends
+        let james = CircuitToneStack::new(ToneStackModel::James, 44100.0);
+        for frequency in [30.0, 200.0, 1000.0, 8000.0] {
+            assert!(digital_response_db(&james, frequency).abs() < 1.0, "{} Hz", frequency);
+        }
+        let mut james_boost = CircuitToneStack::new(ToneStackModel::James, 44100.0);
+        james_boost.update_controls(12.0, 0.0, 12.0);
+        assert!(digital_response_db(&james_boost, 60.0) > 10.0);
+        assert!(digital_response_db(&james_boost, 8000.0) > 10.0);
+    }
+
+    #[test]
+    fn test_discrete_filter_follows_analog_response() {
+        let mut stack = CircuitToneStack::new(ToneStackModel::Marshall, 48000.0);
+        stack.update_controls(4.0, -6.0, 2.0);
+        let netlist = model_netlist(ToneStackModel::Marshall, pot_position(2.0), pot_position(-6.0), pot_position(4.0)).unwrap();
+        let (numerator, denominator) = netlist.transfer_function();
+
+        // Bilinear warping is negligible well below Nyquist
+        for frequency in [50.0, 300.0, 1000.0, 3000.0] {
+            let analog = 20.0 * (analog_response(&numerator, &denominator, frequency).norm() * stack.makeup_gain).log10();
+            assert!((digital_response_db(&stack, frequency) - analog).abs() < 0.3, "{} Hz", frequency);
+        }
+
+        // And the sample loop runs the same filter: a 1 kHz sine settles at that level
+        let expected = 10f64.powf(digital_response_db(&stack, 1000.0) / 20.0);
+        let mut peak = 0.0f32;
+        for n in 0..9600 {
+            let output = stack.process((2.0 * std::f32::consts::PI * 1000.0 * n as f32 / 48000.0).sin());
+            if n > 4800 {
+                peak = peak.max(output.abs());
+            }
+        }
+        assert!((peak as f64 / expected - 1.0).abs() < 0.01);
+    }
+}
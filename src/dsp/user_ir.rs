@@ -0,0 +1,316 @@
+use super::convolution::{ConvolutionEngine, ConvolutionMode};
+use super::ir_loader::{ImpulseResponse, IrLoader};
+use super::ir_prepare::IrPrepSettings;
+use super::CABINET_BLOCK_SIZE;
+use nih_plug::prelude::Enum;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Load state of the user impulse response - readable from any thread
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserIrState {
+    /// No IR file has been requested
+    Empty,
+
+    /// IR file loaded and handed to the audio thread
+    Loaded { path: PathBuf, length: usize, channels: usize },
+
+    /// IR file could not be loaded - the user IR cabinet stays bypassed
+    Failed { path: PathBuf, error: String },
+}
+
+/// One output channel's share of a prepared user IR
+pub struct PreparedChannelIr {
+    /// This output's row of the IR as read from the file, kept so the cabinet can
+    /// re-prepare it for a new sample rate
+    pub source: ImpulseResponse,
+    /// Same-side IR resampled to the host rate
+    pub samples: Vec<f32>,
+    /// Opposite-side IR of a true-stereo matrix - empty for mono and stereo IRs
+    pub cross_samples: Vec<f32>,
+    pub engine: ConvolutionEngine,
+    pub cross_engine: ConvolutionEngine,
+}
+
+impl PreparedChannelIr {
+    /// Resample one output row and load it into fresh engines - allocates, background only
+    pub fn new(
+        source: ImpulseResponse,
+        sample_rate: f32,
+        mode: ConvolutionMode,
+        block_size: usize,
+    ) -> Result<Self, String> {
+        let mut channels = source.resampled(sample_rate).channels.into_iter();
+        let samples = channels.next().unwrap_or_default();
+        let cross_samples = channels.next().unwrap_or_default();
+
+        let PreparedEngines { engine, cross_engine } =
+            PreparedEngines::new(&samples, &cross_samples, mode, block_size)?;
+        Ok(Self { source, samples, cross_samples, engine, cross_engine })
+    }
+}
+
+/// A second set of engines loaded with one output's IR, for another blend slot
+pub struct PreparedEngines {
+    pub engine: ConvolutionEngine,
+    pub cross_engine: ConvolutionEngine,
+}
+
+impl PreparedEngines {
+    /// Load fresh engines with an IR and its true-stereo cross half - allocates, background only
+    pub fn new(
+        samples: &[f32],
+        cross_samples: &[f32],
+        mode: ConvolutionMode,
+        block_size: usize,
+    ) -> Result<Self, String> {
+        let mut engine = ConvolutionEngine::new(mode, block_size);
+        let mut cross_engine = ConvolutionEngine::new(mode, block_size);
+        engine.load_impulse_response(samples).map_err(|e| e.to_string())?;
+        if !cross_samples.is_empty() {
+            cross_engine.load_impulse_response(cross_samples).map_err(|e| e.to_string())?;
+        }
+        Ok(Self { engine, cross_engine })
+    }
+}
+
+/// User IR fully prepared off the audio thread - one set of convolution engines per
+/// output and blend slot, so either or both slots can show it without loading on the
+/// audio thread. The main slot takes each channel's own engines.
+pub struct PreparedUserIr {
+    pub channels: [PreparedChannelIr; 2],
+    pub blend_engines: [PreparedEngines; 2],
+}
+
+/// Hand-off point between the background loader and the audio thread
+///
+/// The background thread does all file IO, allocation and FFT partitioning, then
+/// publishes the result. The audio thread only ever uses `try_lock`, so it never
+/// blocks: it swaps the prepared data into its cabinets and parks the previous data
+/// in `retired`, which the background thread drops on its next load.
+pub struct UserIrSlot {
+    /// Latest load result for logging and editors
+    state: Mutex<UserIrState>,
+
+    /// Prepared IR waiting for the audio thread
+    pending: Mutex<Option<Box<PreparedUserIr>>>,
+
+    /// Previous IR handed back by the audio thread, freed off the audio thread
+    retired: Mutex<Option<Box<PreparedUserIr>>>,
+
+    /// Last IR file loaded successfully, for preparing cabinet switches to the user IR
+    source: Mutex<Option<ImpulseResponse>>,
+
+    /// Cheap check so the audio thread doesn't touch the locks when nothing is pending
+    has_pending: AtomicBool,
+
+    /// Set when the audio thread dropped an IR prepared for an outdated convolution mode
+    stale: AtomicBool,
+
+    /// Convolution mode the engines should be prepared for
+    convolution_mode: AtomicUsize,
+    
+    /// Host sample rate the IR is resampled to, stored as f32 bits
+    sample_rate: AtomicU32,
+}
+
+impl UserIrSlot {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(UserIrState::Empty),
+            pending: Mutex::new(None),
+            retired: Mutex::new(None),
+            source: Mutex::new(None),
+            has_pending: AtomicBool::new(false),
+            stale: AtomicBool::new(false),
+            convolution_mode: AtomicUsize::new(ConvolutionMode::LowCpu.to_index()),
+            sample_rate: AtomicU32::new(44100.0f32.to_bits()),
+        }
+    }
+
+    /// Load and prepare an IR file - heavy, call from a background thread only
+    /// The outcome is recorded in `state()` and returned for logging
+    pub fn load(&self, path: &Path, settings: &IrPrepSettings) -> UserIrState {
+        // Free whatever the audio thread handed back since the last load
+        if let Ok(mut retired) = self.retired.lock() {
+            retired.take();
+        }
+
+        let state = match self.prepare(path, settings) {
+            Ok((prepared, source)) => {
+                let length = prepared.channels[0].samples.len();
+                let channels = source.channels.len();
+                if let Ok(mut current) = self.source.lock() {
+                    *current = Some(source);
+                }
+                if let Ok(mut pending) = self.pending.lock() {
+                    *pending = Some(Box::new(prepared));
+                    self.has_pending.store(true, Ordering::Release);
+                }
+                UserIrState::Loaded { path: path.to_path_buf(), length, channels }
+            }
+            Err(error) => UserIrState::Failed { path: path.to_path_buf(), error },
+        };
+
+        if let Ok(mut current) = self.state.lock() {
+            *current = state.clone();
+        }
+        state
+    }
+
+    /// Read file, resample to the host rate and build per-output convolution engines
+    /// Returns the prepared IR and the IR as read from the file
+    fn prepare(&self, path: &Path, settings: &IrPrepSettings) -> Result<(PreparedUserIr, ImpulseResponse), String> {
+        let source = IrLoader::read_ir_file(path, settings).map_err(|e| e.to_string())?;
+        let sample_rate = f32::from_bits(self.sample_rate.load(Ordering::Relaxed));
+        let mode = ConvolutionMode::from_index(self.convolution_mode.load(Ordering::Relaxed));
+
+        let prepare_channel = |output_channel: usize| {
+            PreparedChannelIr::new(source.output_row(output_channel), sample_rate, mode, CABINET_BLOCK_SIZE)
+        };
+        let channels = [prepare_channel(0)?, prepare_channel(1)?];
+        let prepare_blend = |channel: &PreparedChannelIr| {
+            PreparedEngines::new(&channel.samples, &channel.cross_samples, mode, CABINET_BLOCK_SIZE)
+        };
+        let blend_engines = [prepare_blend(&channels[0])?, prepare_blend(&channels[1])?];
+
+        Ok((PreparedUserIr { channels, blend_engines }, source))
+    }
+
+    /// Last successfully loaded IR at its file sample rate - locks, so not for the audio thread
+    pub fn source(&self) -> Option<ImpulseResponse> {
+        self.source.lock().ok().and_then(|source| source.clone())
+    }
+
+    /// Current load state - locks, so not for the audio thread
+    pub fn state(&self) -> UserIrState {
+        self.state
+            .lock()
+            .map(|state| state.clone())
+            .unwrap_or(UserIrState::Empty)
+    }
+
+    /// Tell the loader which engine type the audio thread is running - lock-free
+    pub fn set_convolution_mode(&self, mode: ConvolutionMode) {
+        self.convolution_mode.store(mode.to_index(), Ordering::Relaxed);
+    }
+
+    /// Tell the loader the host sample rate - call before queueing a load
+    pub fn set_sample_rate(&self, sample_rate: f32) {
+        self.sample_rate.store(sample_rate.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Whether the audio thread dropped an IR prepared for an outdated convolution mode
+    /// since the last call - lock-free. The IR needs loading again for the current mode.
+    pub fn take_stale(&self) -> bool {
+        self.stale.swap(false, Ordering::AcqRel)
+    }
+
+    /// Install a pending IR from the audio thread - never blocks, never allocates
+    /// `install` swaps the prepared data with the processor's current data, which is
+    /// then kept in the retired slot until the background thread frees it. When it
+    /// returns false the IR is retired unused and reported by `take_stale`.
+    /// Returns true if an IR was installed.
+    pub fn install_pending<F: FnOnce(&mut PreparedUserIr) -> bool>(&self, install: F) -> bool {
+        if !self.has_pending.load(Ordering::Acquire) {
+            return false;
+        }
+
+        // Only take the new IR if there's room to park the old one
+        let Ok(mut retired) = self.retired.try_lock() else {
+            return false;
+        };
+        if retired.is_some() {
+            return false;
+        }
+        let Ok(mut pending) = self.pending.try_lock() else {
+            return false;
+        };
+        let Some(mut prepared) = pending.take() else {
+            return false;
+        };
+        self.has_pending.store(false, Ordering::Release);
+
+        let installed = install(&mut prepared);
+        if !installed {
+            self.stale.store(true, Ordering::Release);
+        }
+        *retired = Some(prepared);
+        installed
+    }
+}
+
+impl Default for UserIrSlot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::{BlockParams, CabinetType, ChannelLayout, StereoProcessor};
+
+    /// Minimal 16-bit mono WAV file
+    fn write_wav(path: &Path, samples: &[i16]) {
+        let data_len = (samples.len() * 2) as u32;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+        bytes.extend_from_slice(&44100u32.to_le_bytes());
+        bytes.extend_from_slice(&88200u32.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for sample in samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn test_missing_file_reports_error() {
+        let slot = UserIrSlot::new();
+        let path = Path::new("/nonexistent/cab.wav");
+
+        let state = slot.load(path, &IrPrepSettings::default());
+        assert!(matches!(state, UserIrState::Failed { .. }));
+        assert_eq!(slot.state(), state);
+
+        // Nothing is handed to the audio thread
+        let mut processor = StereoProcessor::new();
+        assert!(!processor.install_user_ir(&slot));
+    }
+
+    #[test]
+    fn test_loaded_ir_reaches_audio_thread() {
+        let path = std::env::temp_dir().join(format!("user_ir_test_{}.wav", std::process::id()));
+        write_wav(&path, &[16384, 8192, 4096, 2048]);
+
+        let slot = UserIrSlot::new();
+        let state = slot.load(&path, &IrPrepSettings::default());
+        std::fs::remove_file(&path).ok();
+        assert_eq!(state, UserIrState::Loaded { path: path.clone(), length: 4, channels: 1 });
+
+        assert_eq!(slot.source().map(|source| source.len()), Some(4));
+
+        let mut processor = StereoProcessor::new();
+        processor.initialize(44100.0, ChannelLayout::Stereo);
+        processor.load_cabinets([CabinetType::UserIr, CabinetType::Direct]);
+        assert!(processor.install_user_ir(&slot));
+        assert!(!processor.install_user_ir(&slot)); // Consumed exactly once
+
+        // The user IR cabinet now has latency from the convolution engine
+        let params = BlockParams::default();
+        let mut left = [0.0; 32];
+        let mut right = [0.0; 32];
+        processor.process_block(&mut left, &mut right, &params);
+        assert!(processor.get_latency() > 0);
+    }
+}
@@ -1,4 +1,5 @@
 use nih_plug::prelude::*;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 mod dsp;
@@ -6,56 +7,201 @@ mod parameters;
 
 #[cfg(test)]
 mod test_ir;
+#[cfg(test)]
+mod test_alloc;
 
-use dsp::GuitarFxProcessor;
+use dsp::{
+    BlockParams, CabinetLoader, CabinetType, ChannelLayout, ConvolutionMode, IrPrepSettings, StereoProcessor,
+    UserIrSlot, UserIrState, DEFAULT_TEMPO_BPM, MAX_BLOCK_SIZE,
+};
 use parameters::GuitarFxParams;
 
+// IR file parsing is public for the fuzz targets in fuzz/
+pub use dsp::{ImpulseResponse, IrLoadError, IrLoader};
+
+/// Work that must stay off the audio thread
+pub enum GuitarFxTask {
+    /// Read, prepare and publish a user impulse response file
+    LoadUserIr(PathBuf),
+    
+    /// Prepare the current user impulse response file again with the latest IR settings,
+    /// or load the file just picked through `GuitarFxParams::request_user_ir`
+    ReloadUserIr,
+    
+    /// Build the convolution engines for a cabinet switch in one blend slot
+    PrepareCabinet { slot: usize, cabinet_type: CabinetType },
+}
+
+const SIDECHAIN_PORT_NAMES: PortNames = PortNames {
+    aux_inputs: &["Gate Sidechain"],
+    ..PortNames::const_default()
+};
+
 pub struct GuitarFx {
     params: Arc<GuitarFxParams>,
-    processor: GuitarFxProcessor,
+    /// One processing chain per channel so filter and convolution state never mixes
+    processor: StereoProcessor,
+    /// Per-block smoothed parameter values, filled before each processing block
+    block_params: BlockParams,
+    /// Latency last reported to the host, in samples
+    reported_latency: usize,
+    /// Non-blocking hand-off of user IRs prepared on the background thread
+    user_ir: Arc<UserIrSlot>,
+    /// IR preparation settings the user IR was last requested with
+    ir_prep_settings: IrPrepSettings,
+    /// Non-blocking hand-off of cabinet switches prepared on the background thread
+    cabinet_loader: Arc<CabinetLoader>,
+    /// Cabinets last requested for the main and blend slots
+    requested_cabinets: [CabinetType; 2],
+    /// Convolution engine type the cabinets were last requested with
+    requested_convolution_mode: ConvolutionMode,
 }
 
 impl Default for GuitarFx {
     fn default() -> Self {
         Self {
             params: Arc::new(GuitarFxParams::default()),
-            processor: GuitarFxProcessor::new(),
+            processor: StereoProcessor::new(),
+            block_params: BlockParams::default(),
+            reported_latency: 0,
+            user_ir: Arc::new(UserIrSlot::new()),
+            ir_prep_settings: IrPrepSettings::default(),
+            cabinet_loader: Arc::new(CabinetLoader::new()),
+            requested_cabinets: [CabinetType::Marshall4x12V30, CabinetType::Direct],
+            requested_convolution_mode: ConvolutionMode::LowCpu,
         }
     }
 }
 
+impl GuitarFx {
+    /// Load a new user impulse response file - safe to call from any non-audio thread
+    /// The path is saved with the plugin state and the file is prepared on the
+    /// background thread, then crossfaded in by the audio thread
+    pub fn load_user_ir(&self, path: PathBuf) {
+        self.params.request_user_ir(path);
+    }
+}
+
 impl Plugin for GuitarFx {
     const NAME: &'static str = "BIAS FX Rust";
     const VENDOR: &'static str = "Rust Audio";
     const URL: &'static str = "https://github.com/rust-audio/bias-fx-rust";
     const EMAIL: &'static str = "rust@audio.dev";
     const VERSION: &'static str = env!("CARGO_PKG_VERSION");
-    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
-        main_input_channels: NonZeroU32::new(2),
-        main_output_channels: NonZeroU32::new(2),
-        ..AudioIOLayout::const_default()
-    }];
+    // Every layout has a sidechain input that can key the noise gate
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        // Stereo in / stereo out - one chain per channel
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            aux_input_ports: &[new_nonzero_u32(2)],
+            names: SIDECHAIN_PORT_NAMES,
+            ..AudioIOLayout::const_default()
+        },
+        // Mono guitar in / stereo out - one amp into the stereo output stage
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(2),
+            aux_input_ports: &[new_nonzero_u32(1)],
+            names: SIDECHAIN_PORT_NAMES,
+            ..AudioIOLayout::const_default()
+        },
+        // Mono in / mono out
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(1),
+            aux_input_ports: &[new_nonzero_u32(1)],
+            names: SIDECHAIN_PORT_NAMES,
+            ..AudioIOLayout::const_default()
+        },
+    ];
     const MIDI_INPUT: MidiConfig = MidiConfig::None;
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
     type SysExMessage = ();
-    type BackgroundTask = ();
+    type BackgroundTask = GuitarFxTask;
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let params = self.params.clone();
+        let user_ir = self.user_ir.clone();
+        let cabinet_loader = self.cabinet_loader.clone();
+        
+        Box::new(move |task| {
+            let path = match task {
+                GuitarFxTask::LoadUserIr(path) => {
+                    if let Ok(mut persisted) = params.user_ir_path.write() {
+                        *persisted = Some(path.clone());
+                    }
+                    path
+                }
+                GuitarFxTask::ReloadUserIr => {
+                    let persisted = params.user_ir_path.read().ok().and_then(|path| path.clone());
+                    let Some(path) = persisted else {
+                        return;
+                    };
+                    path
+                }
+                GuitarFxTask::PrepareCabinet { slot, cabinet_type } => {
+                    if let Err(error) = cabinet_loader.prepare(slot, cabinet_type, user_ir.source().as_ref()) {
+                        nih_error!("Failed to prepare cabinet {:?}: {}", cabinet_type, error);
+                    }
+                    return;
+                }
+            };
+            
+            // A missing or invalid file leaves the user IR cabinet bypassed
+            // and the failure visible through the slot state
+            if let UserIrState::Failed { path, error } = user_ir.load(&path, &params.ir_prep_settings()) {
+                nih_error!("Failed to load impulse response {}: {}", path.display(), error);
+            }
+        })
+    }
+
     fn initialize(
         &mut self,
-        _audio_io_layout: &AudioIOLayout,
+        audio_io_layout: &AudioIOLayout,
         buffer_config: &BufferConfig,
         context: &mut impl InitContext<Self>,
     ) -> bool {
-        self.processor.initialize(buffer_config.sample_rate);
+        let layout = ChannelLayout::from_channel_counts(
+            audio_io_layout.main_input_channels.map(NonZeroU32::get).unwrap_or(0),
+            audio_io_layout.main_output_channels.map(NonZeroU32::get).unwrap_or(0),
+        );
+        self.processor.initialize(buffer_config.sample_rate, layout);
+        self.requested_convolution_mode = self.params.convolution_mode.value();
+        self.processor.set_convolution_mode(self.requested_convolution_mode);
+        self.processor.set_oversampling(self.params.oversampling.value(), self.params.oversampling_phase.value());
+        self.processor.set_waveshaper_mode(self.params.waveshaper_mode.value());
+        self.processor.set_tone_stack_model(self.params.tone_stack_model.value());
+        self.processor.set_noise_gate(self.params.noise_gate_settings());
+        self.processor.set_compressor(self.params.compressor_settings());
+        self.processor.set_fuzz(self.params.fuzz_settings());
+        self.processor.set_pedal(self.params.pedal_settings());
+        self.processor.set_delay(self.params.delay_settings(DEFAULT_TEMPO_BPM));
+        self.user_ir.set_convolution_mode(self.params.convolution_mode.value());
+        self.user_ir.set_sample_rate(buffer_config.sample_rate);
+        self.cabinet_loader.set_convolution_mode(self.params.convolution_mode.value());
+        self.cabinet_loader.set_sample_rate(buffer_config.sample_rate);
+        
+        // Start on the saved cabinets straight away - only later changes crossfade
+        self.requested_cabinets = [self.params.cabinet_type.value(), self.params.cabinet_b_type.value()];
+        self.processor.load_cabinets(self.requested_cabinets);
+        self.ir_prep_settings = self.params.ir_prep_settings();
+        
+        // Restore the user IR saved with the plugin state - the reload also replaces
+        // an IR that was still pending for the previous sample rate
+        let user_ir_path = self.params.user_ir_path.read().ok().and_then(|path| path.clone());
+        if let Some(path) = user_ir_path {
+            context.execute(GuitarFxTask::LoadUserIr(path));
+        }
         
         // Report processing latency to host for proper delay compensation
-        let latency_samples = self.processor.get_latency();
-        context.set_latency_samples(latency_samples as u32);
+        self.reported_latency = self.processor.get_latency();
+        context.set_latency_samples(self.reported_latency as u32);
         
         true
     }
@@ -63,32 +209,140 @@ impl Plugin for GuitarFx {
     fn process(
         &mut self,
         buffer: &mut Buffer,
-        _aux: &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext<Self>,
+        aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        // Functional processing pipeline
-        for channel_samples in buffer.iter_samples() {
-            let input_gain = self.params.input_gain.smoothed.next();
-            let output_gain = self.params.output_gain.smoothed.next();
-            let drive = self.params.drive.smoothed.next();
-            let bass = self.params.bass.smoothed.next();
-            let mid = self.params.mid.smoothed.next();
-            let treble = self.params.treble.smoothed.next();
-            let cabinet_type = self.params.cabinet_type.value();
-            let cabinet_mix = self.params.cabinet_mix.smoothed.next();
+        // Engine and cabinet changes can change latency - keep host delay compensation in sync.
+        // Engines for a new convolution mode are built on the background thread and
+        // crossfaded in like a cabinet switch; the audio thread only records the mode
+        let convolution_mode = self.params.convolution_mode.value();
+        if convolution_mode != self.requested_convolution_mode {
+            self.requested_convolution_mode = convolution_mode;
+            self.processor.set_convolution_mode(convolution_mode);
+            self.user_ir.set_convolution_mode(convolution_mode);
+            self.cabinet_loader.set_convolution_mode(convolution_mode);
+            for (slot, cabinet_type) in self.requested_cabinets.into_iter().enumerate() {
+                context.execute_background(GuitarFxTask::PrepareCabinet { slot, cabinet_type });
+            }
+        }
+        self.processor.set_oversampling(self.params.oversampling.value(), self.params.oversampling_phase.value());
+        self.processor.set_waveshaper_mode(self.params.waveshaper_mode.value());
+        self.processor.set_tone_stack_model(self.params.tone_stack_model.value());
+        self.processor.set_noise_gate(self.params.noise_gate_settings());
+        self.processor.set_compressor(self.params.compressor_settings());
+        self.processor.set_fuzz(self.params.fuzz_settings());
+        self.processor.set_pedal(self.params.pedal_settings());
+        
+        // Synced delay times follow the host tempo, falling back to 120 bpm when there is none
+        let tempo = context.transport().tempo.map(|tempo| tempo as f32).unwrap_or(DEFAULT_TEMPO_BPM);
+        self.processor.set_delay(self.params.delay_settings(tempo));
+        
+        // Cabinet switches are prepared on the background thread and crossfaded in,
+        // so automation and preset changes never load IRs on the audio thread
+        let cabinet_types = [self.params.cabinet_type.value(), self.params.cabinet_b_type.value()];
+        for (slot, cabinet_type) in cabinet_types.into_iter().enumerate() {
+            if cabinet_type != self.requested_cabinets[slot] {
+                self.requested_cabinets[slot] = cabinet_type;
+                context.execute_background(GuitarFxTask::PrepareCabinet { slot, cabinet_type });
+            }
+        }
+        self.processor.set_cabinet_crossfade(self.params.cabinet_crossfade.value());
+        self.processor.install_cabinets(&self.cabinet_loader);
+        
+        // IRs are trimmed and normalized while loading - prepare the file again when
+        // those settings change, the audio thread only queues the task
+        let ir_prep_settings = self.params.ir_prep_settings();
+        if ir_prep_settings != self.ir_prep_settings {
+            self.ir_prep_settings = ir_prep_settings;
+            context.execute_background(GuitarFxTask::ReloadUserIr);
+        }
+        
+        // A new IR file picked through `GuitarFx::load_user_ir` - its path is already saved,
+        // so the background task loads it without the audio thread copying the path
+        if self.params.take_user_ir_request() {
+            context.execute_background(GuitarFxTask::ReloadUserIr);
+        }
+        
+        // Pick up a freshly loaded user IR - try_lock only, never waits on the loader
+        self.processor.install_user_ir(&self.user_ir);
+        
+        // Engines prepared for a convolution mode that changed in the meantime are
+        // dropped unused - prepare them again for the current mode
+        for (slot, cabinet_type) in self.requested_cabinets.into_iter().enumerate() {
+            if self.cabinet_loader.take_stale(slot) {
+                context.execute_background(GuitarFxTask::PrepareCabinet { slot, cabinet_type });
+            }
+        }
+        if self.user_ir.take_stale() {
+            context.execute_background(GuitarFxTask::ReloadUserIr);
+        }
+        
+        // Block-based processing pipeline - parameters are smoothed once per sample
+        // into the shared block buffers and both channels see the same values
+        let sidechain = aux.inputs.first().map(|sidechain| sidechain.as_slice_immutable());
+        for (block_start, block) in buffer.iter_blocks(MAX_BLOCK_SIZE) {
+            let block_len = block.samples();
+            let params = &mut self.block_params;
+            
+            self.params.input_gain.smoothed.next_block(&mut params.input_gain, block_len);
+            self.params.output_gain.smoothed.next_block(&mut params.output_gain, block_len);
+            self.params.drive.smoothed.next_block(&mut params.drive, block_len);
+            self.params.bass.smoothed.next_block(&mut params.bass, block_len);
+            self.params.mid.smoothed.next_block(&mut params.mid, block_len);
+            self.params.treble.smoothed.next_block(&mut params.treble, block_len);
+            self.params.master.smoothed.next_block(&mut params.master, block_len);
+            self.params.presence.smoothed.next_block(&mut params.presence, block_len);
+            self.params.resonance.smoothed.next_block(&mut params.resonance, block_len);
+            self.params.sag.smoothed.next_block(&mut params.sag, block_len);
+            self.params.cabinet_mix.smoothed.next_block(&mut params.cabinet_mix, block_len);
+            self.params.stereo_width.smoothed.next_block(&mut params.stereo_width, block_len);
             
-            // Update tone controls - O(1) per-sample update
-            self.processor.update_tone_controls(bass, mid, treble);
+            // Cabinet blend slots - A is the main cabinet, B the optional second IR
+            let [slot_a, slot_b] = &mut params.cabinet_slots;
+            self.params.cabinet_a_level.smoothed.next_block(&mut slot_a.level, block_len);
+            self.params.cabinet_a_pan.smoothed.next_block(&mut slot_a.pan, block_len);
+            self.params.cabinet_a_delay.smoothed.next_block(&mut slot_a.delay_ms, block_len);
+            slot_a.invert = self.params.cabinet_a_invert.value();
+            self.params.cabinet_b_level.smoothed.next_block(&mut slot_b.level, block_len);
+            self.params.cabinet_b_pan.smoothed.next_block(&mut slot_b.pan, block_len);
+            self.params.cabinet_b_delay.smoothed.next_block(&mut slot_b.delay_ms, block_len);
+            slot_b.invert = self.params.cabinet_b_invert.value();
+            params.blend_enabled = self.params.cabinet_b_enabled.value();
+            
+            // The gate key is the louder sidechain channel - silence when the host routes nothing
+            params.gate_sidechain = self.params.gate_sidechain.value();
+            if params.gate_sidechain {
+                params.gate_key[..block_len].fill(0.0);
+                for channel in sidechain.unwrap_or_default() {
+                    let block_range = block_start..block_start + block_len;
+                    for (key, sample) in params.gate_key.iter_mut().zip(&channel[block_range]) {
+                        *key = key.max(sample.abs());
+                    }
+                }
+            }
             
-            // Update cabinet parameters - O(1) for mix, expensive for type change
-            self.processor.update_cabinet(cabinet_type, cabinet_mix);
+            self.processor.set_channel_mode(self.params.channel_mode.value());
             
-            // Apply functional DSP chain
-            for sample in channel_samples {
-                *sample = self.processor.process_sample(*sample, input_gain, drive, output_gain);
+            // Apply DSP chain - in mono-to-stereo layouts the host's mono input
+            // arrives in the first channel and the second is overwritten
+            let mut channels = block.into_iter();
+            match (channels.next(), channels.next()) {
+                (Some(left), Some(right)) => {
+                    self.processor.process_block(left, right, &self.block_params);
+                }
+                (Some(mono), None) => {
+                    self.processor.process_mono_block(mono, &self.block_params);
+                }
+                _ => {}
             }
         }
         
+        let latency = self.processor.get_latency();
+        if latency != self.reported_latency {
+            self.reported_latency = latency;
+            context.set_latency_samples(latency as u32);
+        }
+        
         ProcessStatus::Normal
     }
 }
@@ -102,4 +356,24 @@ impl Vst3Plugin for GuitarFx {
     ];
 }
 
+impl ClapPlugin for GuitarFx {
+    const CLAP_ID: &'static str = "com.rust-audio.bias-fx-rust";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("Guitar amp and cabinet simulator");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Distortion,
+        ClapFeature::Custom("rust-audio:guitar"),
+        ClapFeature::Mono,
+        ClapFeature::Stereo,
+    ];
+    
+    // This is an effect without voices, so hosts must not send per-voice modulation.
+    // Monophonic CLAP modulation is applied by nih_plug on top of the automation value
+    // and reaches the DSP through the smoothers, read into `BlockParams` per block in `process`
+    const CLAP_POLY_MODULATION_CONFIG: Option<PolyModulationConfig> = None;
+}
+
+nih_export_clap!(GuitarFx);
 nih_export_vst3!(GuitarFx);
\ No newline at end of file
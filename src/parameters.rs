@@ -1,5 +1,13 @@
 use nih_plug::prelude::*;
-use crate::dsp::CabinetType;
+use crate::dsp::{
+    CabinetType, ChannelMode, CompressorSettings, ConvolutionMode, DelayMode, DelaySettings, DelaySync, FuzzModel,
+    FuzzSettings, IrNormalization, IrPrepSettings, NoiseGateSettings, OversamplingFactor, OversamplingPhase,
+    PedalModel, PedalSettings, ToneStackModel, WaveshaperMode, DEFAULT_SAG, MAX_DELAY_MS, MAX_GATE_LOOKAHEAD_MS,
+    MAX_SLOT_DELAY_MS,
+};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 
 #[derive(Params)]
 pub struct GuitarFxParams {
@@ -11,6 +19,122 @@ pub struct GuitarFxParams {
     #[id = "drive"]  
     pub drive: FloatParam,
     
+    /// Noise gate keyed from the clean input, gating after the preamp
+    #[id = "gate_enabled"]
+    pub gate_enabled: BoolParam,
+    
+    /// Input level that opens the gate
+    #[id = "gate_threshold"]
+    pub gate_threshold: FloatParam,
+    
+    /// How far below the threshold the input must fall before the gate closes
+    #[id = "gate_hysteresis"]
+    pub gate_hysteresis: FloatParam,
+    
+    /// Gate opening time
+    #[id = "gate_attack"]
+    pub gate_attack: FloatParam,
+    
+    /// Time the gate stays open after the input falls below the close level
+    #[id = "gate_hold"]
+    pub gate_hold: FloatParam,
+    
+    /// Gate closing time
+    #[id = "gate_release"]
+    pub gate_release: FloatParam,
+    
+    /// Delays the amp so the gate is open before the pick attack - adds latency
+    #[id = "gate_lookahead"]
+    pub gate_lookahead: FloatParam,
+    
+    /// Key the gate from the sidechain input instead of the guitar
+    #[id = "gate_sidechain"]
+    pub gate_sidechain: BoolParam,
+    
+    /// Compressor pedal in front of the preamp
+    #[id = "comp_enabled"]
+    pub comp_enabled: BoolParam,
+    
+    /// Amount of compression - lower threshold and higher ratio together
+    #[id = "comp_sustain"]
+    pub comp_sustain: FloatParam,
+    
+    /// How long the pick attack passes before the compressor clamps down
+    #[id = "comp_attack"]
+    pub comp_attack: FloatParam,
+    
+    /// Compressor output level
+    #[id = "comp_level"]
+    pub comp_level: FloatParam,
+    
+    /// Mix of compressed and dry signal
+    #[id = "comp_blend"]
+    pub comp_blend: FloatParam,
+    
+    /// Fuzz pedal, first in line after the compressor
+    #[id = "fuzz_enabled"]
+    pub fuzz_enabled: BoolParam,
+    
+    /// Which fuzz circuit is modeled
+    #[id = "fuzz_model"]
+    pub fuzz_model: EnumParam<FuzzModel>,
+    
+    /// Fuzz or sustain knob
+    #[id = "fuzz_amount"]
+    pub fuzz_amount: FloatParam,
+    
+    /// Big Muff tone stack
+    #[id = "fuzz_tone"]
+    pub fuzz_tone: FloatParam,
+    
+    /// Supply voltage - lower values starve the transistors for a sputtery fuzz
+    #[id = "fuzz_bias"]
+    pub fuzz_bias: FloatParam,
+    
+    /// Input dead zone that gates quiet playing
+    #[id = "fuzz_gate"]
+    pub fuzz_gate: FloatParam,
+    
+    /// Fuzz output level
+    #[id = "fuzz_level"]
+    pub fuzz_level: FloatParam,
+    
+    /// Overdrive or boost pedal between the fuzz and the amp
+    #[id = "pedal_enabled"]
+    pub pedal_enabled: BoolParam,
+    
+    /// Which pedal circuit is modeled
+    #[id = "pedal_model"]
+    pub pedal_model: EnumParam<PedalModel>,
+    
+    /// Pedal gain
+    #[id = "pedal_drive"]
+    pub pedal_drive: FloatParam,
+    
+    /// Pedal tone - what it shapes depends on the model
+    #[id = "pedal_tone"]
+    pub pedal_tone: FloatParam,
+    
+    /// Pedal output level
+    #[id = "pedal_level"]
+    pub pedal_level: FloatParam,
+    
+    /// Oversampling quality for the preamp and clipper - higher ratios alias less but cost more CPU
+    #[id = "oversampling"]
+    pub oversampling: EnumParam<OversamplingFactor>,
+    
+    /// Oversampling filter phase: linear phase adds latency, minimum phase almost none
+    #[id = "oversampling_phase"]
+    pub oversampling_phase: EnumParam<OversamplingPhase>,
+    
+    /// Waveshaper evaluation: plain, or antiderivative anti-aliasing as a cheaper alternative to oversampling
+    #[id = "waveshaper_mode"]
+    pub waveshaper_mode: EnumParam<WaveshaperMode>,
+    
+    /// Tone stack circuit - the modern EQ or a passive stack modeled from its component values
+    #[id = "tone_stack_model"]
+    pub tone_stack_model: EnumParam<ToneStackModel>,
+    
     /// Low frequency control (bass)
     #[id = "bass"]
     pub bass: FloatParam,
@@ -23,6 +147,22 @@ pub struct GuitarFxParams {
     #[id = "treble"] 
     pub treble: FloatParam,
     
+    /// Power amp master volume - drives the output stage into saturation
+    #[id = "master"]
+    pub master: FloatParam,
+    
+    /// Power amp presence: high-frequency boost or cut ahead of the output stage
+    #[id = "presence"]
+    pub presence: FloatParam,
+    
+    /// Power amp resonance: low-end speaker resonance boost or cut
+    #[id = "resonance"]
+    pub resonance: FloatParam,
+    
+    /// Power supply sag: how much the output stage compresses under heavy load
+    #[id = "sag"]
+    pub sag: FloatParam,
+    
     /// Output gain with smooth parameter changes
     #[id = "output_gain"]
     pub output_gain: FloatParam,
@@ -34,6 +174,129 @@ pub struct GuitarFxParams {
     /// Cabinet wet/dry mix for blending direct and cabinet-processed signal
     #[id = "cabinet_mix"]
     pub cabinet_mix: FloatParam,
+    
+    /// Main cabinet level in the IR blend
+    #[id = "cabinet_a_level"]
+    pub cabinet_a_level: FloatParam,
+    
+    /// Main cabinet position in the stereo field
+    #[id = "cabinet_a_pan"]
+    pub cabinet_a_pan: FloatParam,
+    
+    /// Main cabinet polarity invert
+    #[id = "cabinet_a_invert"]
+    pub cabinet_a_invert: BoolParam,
+    
+    /// Main cabinet time alignment against the blend cabinet
+    #[id = "cabinet_a_delay"]
+    pub cabinet_a_delay: FloatParam,
+    
+    /// Turns the second IR of the cabinet blend on
+    #[id = "cabinet_b_enabled"]
+    pub cabinet_b_enabled: BoolParam,
+    
+    /// Second cabinet IR, blended with the main cabinet like a second mic
+    #[id = "cabinet_b_type"]
+    pub cabinet_b_type: EnumParam<CabinetType>,
+    
+    /// Blend cabinet level
+    #[id = "cabinet_b_level"]
+    pub cabinet_b_level: FloatParam,
+    
+    /// Blend cabinet position in the stereo field
+    #[id = "cabinet_b_pan"]
+    pub cabinet_b_pan: FloatParam,
+    
+    /// Blend cabinet polarity invert
+    #[id = "cabinet_b_invert"]
+    pub cabinet_b_invert: BoolParam,
+    
+    /// Blend cabinet time alignment against the main cabinet
+    #[id = "cabinet_b_delay"]
+    pub cabinet_b_delay: FloatParam,
+    
+    /// Crossfade time when switching cabinets
+    #[id = "cabinet_crossfade"]
+    pub cabinet_crossfade: FloatParam,
+    
+    /// Cabinet convolution engine: low CPU with block latency, or zero latency for live monitoring
+    #[id = "convolution_mode"]
+    pub convolution_mode: EnumParam<ConvolutionMode>,
+    
+    /// Cut silence before and after the user IR when it is loaded
+    #[id = "ir_trim"]
+    pub ir_trim: BoolParam,
+    
+    /// Maximum user IR length - longer IRs are cut with a short fade-out
+    #[id = "ir_length"]
+    pub ir_length: FloatParam,
+    
+    /// Convert the user IR to minimum phase for a tighter attack
+    #[id = "ir_minimum_phase"]
+    pub ir_minimum_phase: BoolParam,
+    
+    /// How the user IR level is matched: by peak, RMS or loudness
+    #[id = "ir_normalization"]
+    pub ir_normalization: EnumParam<IrNormalization>,
+    
+    /// Stereo handling: independent dual-mono chains or linked stereo
+    #[id = "channel_mode"]
+    pub channel_mode: EnumParam<ChannelMode>,
+    
+    /// Stereo width of the output stage in mono-in / stereo-out layouts
+    #[id = "stereo_width"]
+    pub stereo_width: FloatParam,
+    
+    /// Stereo delay after the cabinets
+    #[id = "delay_enabled"]
+    pub delay_enabled: BoolParam,
+    
+    /// Clean digital repeats or tape with wow, flutter and saturation
+    #[id = "delay_mode"]
+    pub delay_mode: EnumParam<DelayMode>,
+    
+    /// Delay time when not synced to the host tempo
+    #[id = "delay_time"]
+    pub delay_time: FloatParam,
+    
+    /// Note value the delay time follows, or free-running
+    #[id = "delay_sync"]
+    pub delay_sync: EnumParam<DelaySync>,
+    
+    /// Amount of each repeat fed back into the delay
+    #[id = "delay_feedback"]
+    pub delay_feedback: FloatParam,
+    
+    /// Low and high cut filters in the feedback path - repeats get thinner and darker
+    #[id = "delay_low_cut"]
+    pub delay_low_cut: FloatParam,
+    
+    #[id = "delay_high_cut"]
+    pub delay_high_cut: FloatParam,
+    
+    /// Bounce the repeats between left and right
+    #[id = "delay_ping_pong"]
+    pub delay_ping_pong: BoolParam,
+    
+    /// Tape transport wobble depth, tape mode only
+    #[id = "delay_wow_flutter"]
+    pub delay_wow_flutter: FloatParam,
+    
+    /// Lowers the repeats while playing so they fill the gaps instead
+    #[id = "delay_ducking"]
+    pub delay_ducking: FloatParam,
+    
+    /// Level of the repeats over the dry signal
+    #[id = "delay_mix"]
+    pub delay_mix: FloatParam,
+    
+    /// Path of the user impulse response file, saved with the plugin state
+    /// and loaded on a background task when the plugin is initialized
+    #[persist = "user_ir_path"]
+    pub user_ir_path: Arc<RwLock<Option<PathBuf>>>,
+    
+    /// Set by `request_user_ir` until the audio thread has queued the load
+    pub user_ir_requested: AtomicBool,
 }
 
 impl Default for GuitarFxParams {
@@ -60,6 +323,192 @@ impl Default for GuitarFxParams {
             )
             .with_smoother(SmoothingStyle::Linear(50.0)),
             
+            gate_enabled: BoolParam::new("Noise Gate", false),
+            
+            gate_threshold: FloatParam::new(
+                "Gate Threshold",
+                NoiseGateSettings::default().threshold_db,
+                FloatRange::Linear { min: -96.0, max: 0.0 }
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1),
+            
+            gate_hysteresis: FloatParam::new(
+                "Gate Hysteresis",
+                NoiseGateSettings::default().hysteresis_db,
+                FloatRange::Linear { min: 0.0, max: 20.0 }
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1),
+            
+            gate_attack: FloatParam::new(
+                "Gate Attack",
+                NoiseGateSettings::default().attack_ms,
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 50.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            
+            gate_hold: FloatParam::new(
+                "Gate Hold",
+                NoiseGateSettings::default().hold_ms,
+                FloatRange::Linear { min: 0.0, max: 500.0 }
+            )
+            .with_unit(" ms")
+            .with_step_size(1.0),
+            
+            gate_release: FloatParam::new(
+                "Gate Release",
+                NoiseGateSettings::default().release_ms,
+                FloatRange::Skewed {
+                    min: 5.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(1.0),
+            
+            gate_lookahead: FloatParam::new(
+                "Gate Lookahead",
+                NoiseGateSettings::default().lookahead_ms,
+                FloatRange::Linear { min: 0.0, max: MAX_GATE_LOOKAHEAD_MS }
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            
+            gate_sidechain: BoolParam::new("Gate Sidechain", false),
+            
+            comp_enabled: BoolParam::new("Compressor", false),
+            
+            comp_sustain: FloatParam::new(
+                "Comp Sustain",
+                CompressorSettings::default().sustain,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            comp_attack: FloatParam::new(
+                "Comp Attack",
+                CompressorSettings::default().attack_ms,
+                FloatRange::Skewed {
+                    min: 0.5,
+                    max: 100.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            
+            comp_level: FloatParam::new(
+                "Comp Level",
+                CompressorSettings::default().level_db,
+                FloatRange::Linear { min: -12.0, max: 12.0 }
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1),
+            
+            comp_blend: FloatParam::new(
+                "Comp Blend",
+                CompressorSettings::default().blend,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            fuzz_enabled: BoolParam::new("Fuzz Pedal", false),
+            
+            fuzz_model: EnumParam::new("Fuzz Model", FuzzModel::FuzzFace),
+            
+            fuzz_amount: FloatParam::new(
+                "Fuzz",
+                FuzzSettings::default().fuzz,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            fuzz_tone: FloatParam::new(
+                "Fuzz Tone",
+                FuzzSettings::default().tone,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            fuzz_bias: FloatParam::new(
+                "Fuzz Bias",
+                FuzzSettings::default().bias,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            fuzz_gate: FloatParam::new(
+                "Fuzz Gate",
+                FuzzSettings::default().gate,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            fuzz_level: FloatParam::new(
+                "Fuzz Level",
+                FuzzSettings::default().level_db,
+                FloatRange::Linear { min: -24.0, max: 12.0 }
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1),
+            
+            pedal_enabled: BoolParam::new("Pedal", false),
+            
+            pedal_model: EnumParam::new("Pedal Model", PedalModel::TubeScreamer),
+            
+            pedal_drive: FloatParam::new(
+                "Pedal Drive",
+                PedalSettings::default().drive,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            pedal_tone: FloatParam::new(
+                "Pedal Tone",
+                PedalSettings::default().tone,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            pedal_level: FloatParam::new(
+                "Pedal Level",
+                PedalSettings::default().level_db,
+                FloatRange::Linear { min: -24.0, max: 12.0 }
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1),
+            
+            oversampling: EnumParam::new("Oversampling", OversamplingFactor::X2),
+            
+            oversampling_phase: EnumParam::new("Oversampling Phase", OversamplingPhase::Linear),
+            
+            waveshaper_mode: EnumParam::new("Anti-Aliasing", WaveshaperMode::Plain),
+            
+            tone_stack_model: EnumParam::new("Tone Stack", ToneStackModel::Modern),
+            
             bass: FloatParam::new(
                 "Bass",
                 0.0,
@@ -84,6 +533,46 @@ impl Default for GuitarFxParams {
             .with_smoother(SmoothingStyle::Linear(50.0))
             .with_unit(" dB"),
             
+            master: FloatParam::new(
+                "Master",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 24.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            
+            presence: FloatParam::new(
+                "Presence",
+                0.0,
+                FloatRange::Linear { min: -12.0, max: 12.0 }
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit(" dB"),
+            
+            resonance: FloatParam::new(
+                "Resonance",
+                0.0,
+                FloatRange::Linear { min: -12.0, max: 12.0 }
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit(" dB"),
+            
+            sag: FloatParam::new(
+                "Sag",
+                DEFAULT_SAG,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
             output_gain: FloatParam::new(
                 "Output Gain",
                 util::db_to_gain(0.0),
@@ -112,6 +601,290 @@ impl Default for GuitarFxParams {
             .with_unit("%")
             .with_value_to_string(formatters::v2s_f32_percentage(1))
             .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            cabinet_a_level: cabinet_level_param("Cab A Level"),
+            cabinet_a_pan: cabinet_pan_param("Cab A Pan"),
+            cabinet_a_invert: BoolParam::new("Cab A Invert", false),
+            cabinet_a_delay: cabinet_delay_param("Cab A Delay"),
+            
+            cabinet_b_enabled: BoolParam::new("Cab B", false),
+            
+            cabinet_b_type: EnumParam::new(
+                "Cab B Type",
+                CabinetType::FenderTwin2x12
+            ),
+            
+            cabinet_b_level: cabinet_level_param("Cab B Level"),
+            cabinet_b_pan: cabinet_pan_param("Cab B Pan"),
+            cabinet_b_invert: BoolParam::new("Cab B Invert", false),
+            cabinet_b_delay: cabinet_delay_param("Cab B Delay"),
+            
+            cabinet_crossfade: FloatParam::new(
+                "Cab Crossfade",
+                50.0,
+                FloatRange::Linear { min: 0.0, max: 500.0 }
+            )
+            .with_unit(" ms")
+            .with_step_size(1.0),
+            
+            convolution_mode: EnumParam::new(
+                "Cabinet Latency",
+                ConvolutionMode::LowCpu
+            ),
+            
+            ir_trim: BoolParam::new("IR Trim", true),
+            
+            ir_length: FloatParam::new(
+                "IR Length",
+                IrPrepSettings::default().length_ms,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 4000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(1.0),
+            
+            ir_minimum_phase: BoolParam::new("IR Minimum Phase", false),
+            
+            ir_normalization: EnumParam::new(
+                "IR Normalization",
+                IrNormalization::Peak
+            ),
+            
+            channel_mode: EnumParam::new(
+                "Channel Mode",
+                ChannelMode::DualMono
+            ),
+            
+            stereo_width: FloatParam::new(
+                "Stereo Width",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            delay_enabled: BoolParam::new("Delay", false),
+            
+            delay_mode: EnumParam::new("Delay Mode", DelayMode::Digital),
+            
+            delay_time: FloatParam::new(
+                "Delay Time",
+                DelaySettings::default().time_ms,
+                FloatRange::Skewed {
+                    min: 1.0,
+                    max: MAX_DELAY_MS,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(1.0),
+            
+            delay_sync: EnumParam::new("Delay Sync", DelaySync::Free),
+            
+            delay_feedback: FloatParam::new(
+                "Delay Feedback",
+                DelaySettings::default().feedback,
+                FloatRange::Linear { min: 0.0, max: 0.95 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            delay_low_cut: FloatParam::new(
+                "Delay Low Cut",
+                DelaySettings::default().low_cut_hz,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_step_size(1.0),
+            
+            delay_high_cut: FloatParam::new(
+                "Delay High Cut",
+                DelaySettings::default().high_cut_hz,
+                FloatRange::Skewed {
+                    min: 1000.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_step_size(1.0),
+            
+            delay_ping_pong: BoolParam::new("Delay Ping-Pong", false),
+            
+            delay_wow_flutter: FloatParam::new(
+                "Delay Wow/Flutter",
+                DelaySettings::default().wow_flutter,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            delay_ducking: FloatParam::new(
+                "Delay Ducking",
+                DelaySettings::default().ducking,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            delay_mix: FloatParam::new(
+                "Delay Mix",
+                DelaySettings::default().mix,
+                FloatRange::Linear { min: 0.0, max: 1.0 }
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            
+            user_ir_path: Arc::new(RwLock::new(None)),
+            user_ir_requested: AtomicBool::new(false),
+        }
+    }
+}
+
+impl GuitarFxParams {
+    /// Pick a new user IR file - for `GuitarFx::load_user_ir` and editors, never the audio thread
+    /// Saves the path with the plugin state; the next `process` call queues the load
+    /// on the background thread
+    pub fn request_user_ir(&self, path: PathBuf) {
+        if let Ok(mut persisted) = self.user_ir_path.write() {
+            *persisted = Some(path);
+        }
+        self.user_ir_requested.store(true, Ordering::Release);
+    }
+    
+    /// Take a pending `request_user_ir` - lock-free, for the audio thread
+    pub fn take_user_ir_request(&self) -> bool {
+        self.user_ir_requested.swap(false, Ordering::AcqRel)
+    }
+    
+    /// User IR preparation chosen by the IR parameters - readable from any thread
+    pub fn ir_prep_settings(&self) -> IrPrepSettings {
+        IrPrepSettings {
+            trim_threshold_db: self.ir_trim.value().then_some(-60.0),
+            length_ms: self.ir_length.value(),
+            minimum_phase: self.ir_minimum_phase.value(),
+            normalization: self.ir_normalization.value(),
+            ..IrPrepSettings::default()
+        }
+    }
+    
+    /// Noise gate settings chosen by the gate parameters
+    pub fn noise_gate_settings(&self) -> NoiseGateSettings {
+        NoiseGateSettings {
+            enabled: self.gate_enabled.value(),
+            threshold_db: self.gate_threshold.value(),
+            hysteresis_db: self.gate_hysteresis.value(),
+            attack_ms: self.gate_attack.value(),
+            hold_ms: self.gate_hold.value(),
+            release_ms: self.gate_release.value(),
+            lookahead_ms: self.gate_lookahead.value(),
+        }
+    }
+    
+    /// Compressor settings chosen by the compressor parameters
+    pub fn compressor_settings(&self) -> CompressorSettings {
+        CompressorSettings {
+            enabled: self.comp_enabled.value(),
+            sustain: self.comp_sustain.value(),
+            attack_ms: self.comp_attack.value(),
+            level_db: self.comp_level.value(),
+            blend: self.comp_blend.value(),
         }
     }
-}
\ No newline at end of file
+    
+    /// Fuzz pedal settings chosen by the fuzz parameters
+    pub fn fuzz_settings(&self) -> FuzzSettings {
+        FuzzSettings {
+            enabled: self.fuzz_enabled.value(),
+            model: self.fuzz_model.value(),
+            fuzz: self.fuzz_amount.value(),
+            tone: self.fuzz_tone.value(),
+            bias: self.fuzz_bias.value(),
+            gate: self.fuzz_gate.value(),
+            level_db: self.fuzz_level.value(),
+        }
+    }
+    
+    /// Drive pedal settings chosen by the pedal parameters
+    pub fn pedal_settings(&self) -> PedalSettings {
+        PedalSettings {
+            enabled: self.pedal_enabled.value(),
+            model: self.pedal_model.value(),
+            drive: self.pedal_drive.value(),
+            tone: self.pedal_tone.value(),
+            level_db: self.pedal_level.value(),
+        }
+    }
+    
+    /// Delay settings chosen by the delay parameters, synced notes following `tempo_bpm`
+    pub fn delay_settings(&self, tempo_bpm: f32) -> DelaySettings {
+        DelaySettings {
+            enabled: self.delay_enabled.value(),
+            mode: self.delay_mode.value(),
+            time_ms: self.delay_time.value(),
+            sync: self.delay_sync.value(),
+            tempo_bpm,
+            feedback: self.delay_feedback.value(),
+            low_cut_hz: self.delay_low_cut.value(),
+            high_cut_hz: self.delay_high_cut.value(),
+            ping_pong: self.delay_ping_pong.value(),
+            wow_flutter: self.delay_wow_flutter.value(),
+            ducking: self.delay_ducking.value(),
+            mix: self.delay_mix.value(),
+        }
+    }
+}
+
+/// Level of one cabinet blend slot, -30 to +6 dB
+fn cabinet_level_param(name: &str) -> FloatParam {
+    FloatParam::new(
+        name,
+        util::db_to_gain(0.0),
+        FloatRange::Skewed {
+            min: util::db_to_gain(-30.0),
+            max: util::db_to_gain(6.0),
+            factor: FloatRange::gain_skew_factor(-30.0, 6.0),
+        },
+    )
+    .with_smoother(SmoothingStyle::Logarithmic(50.0))
+    .with_unit(" dB")
+    .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+    .with_string_to_value(formatters::s2v_f32_gain_to_db())
+}
+
+/// Pan of one cabinet blend slot, -1 (left) to 1 (right)
+fn cabinet_pan_param(name: &str) -> FloatParam {
+    FloatParam::new(
+        name,
+        0.0,
+        FloatRange::Linear { min: -1.0, max: 1.0 }
+    )
+    .with_smoother(SmoothingStyle::Linear(50.0))
+    .with_value_to_string(formatters::v2s_f32_panning())
+    .with_string_to_value(formatters::s2v_f32_panning())
+}
+
+/// Time-alignment delay of one cabinet blend slot in milliseconds
+fn cabinet_delay_param(name: &str) -> FloatParam {
+    FloatParam::new(
+        name,
+        0.0,
+        FloatRange::Linear { min: 0.0, max: MAX_SLOT_DELAY_MS }
+    )
+    .with_smoother(SmoothingStyle::Linear(20.0))
+    .with_unit(" ms")
+    .with_step_size(0.001)
+}
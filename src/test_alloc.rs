@@ -0,0 +1,294 @@
+// Real-time safety checks: the audio path must never touch the heap
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    use crate::dsp::{
+        BlockParams, CabinetLoader, CabinetType, ChannelLayout, ChannelMode, CompressorSettings, ConvolutionMode,
+        DelayMode, DelaySettings, DelaySync, FuzzModel, FuzzSettings, GuitarFxProcessor, NoiseGateSettings,
+        OversamplingFactor, OversamplingPhase, PedalModel, PedalSettings, StereoProcessor, ToneStackModel,
+        WaveshaperMode, MAX_BLOCK_SIZE,
+    };
+
+    /// System allocator wrapper that counts heap operations on threads that opted in
+    struct CountingAllocator;
+
+    thread_local! {
+        static COUNTING: Cell<bool> = const { Cell::new(false) };
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn record_allocation() {
+        let _ = COUNTING.try_with(|counting| {
+            if counting.get() {
+                ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            }
+        });
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            record_allocation();
+            System.alloc(layout)
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            record_allocation();
+            System.alloc_zeroed(layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            record_allocation();
+            System.realloc(ptr, layout, new_size)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            record_allocation();
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Run `f` and fail if it allocated, reallocated or freed heap memory on this thread
+    /// `context` names the case in the failure message
+    pub(crate) fn assert_no_alloc<F: FnOnce()>(context: &str, f: F) {
+        ALLOCATIONS.with(|count| count.set(0));
+        COUNTING.with(|counting| counting.set(true));
+        f();
+        COUNTING.with(|counting| counting.set(false));
+
+        let allocations = ALLOCATIONS.with(|count| count.get());
+        assert_eq!(allocations, 0, "{}: real-time path performed {} heap operations", context, allocations);
+    }
+
+    fn test_block(offset: usize) -> [f32; MAX_BLOCK_SIZE] {
+        let mut block = [0.0; MAX_BLOCK_SIZE];
+        for (i, sample) in block.iter_mut().enumerate() {
+            *sample = ((offset + i) as f32 * 0.05).sin() * 0.5;
+        }
+        block
+    }
+
+    #[test]
+    fn test_processor_blocks_do_not_allocate() {
+        for mode in [ConvolutionMode::LowCpu, ConvolutionMode::ZeroLatency] {
+            let mut processor = GuitarFxProcessor::new();
+            processor.initialize(48000.0);
+            processor.set_convolution_mode(mode);
+            processor.load_cabinets([CabinetType::Marshall4x12V30, CabinetType::Direct]);
+            let params = BlockParams::constant(2.0, 10.0, 0.8, 2.0, -3.0, 1.0);
+
+            // Several thousand samples cover every FFT block boundary of every stage
+            assert_no_alloc(&format!("{:?} blocks", mode), || {
+                for n in 0..200 {
+                    let mut block = test_block(n * MAX_BLOCK_SIZE);
+                    processor.process_block(&mut block, &params);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_processor_samples_do_not_allocate() {
+        let mut processor = GuitarFxProcessor::new();
+        processor.initialize(44100.0);
+
+        assert_no_alloc("single samples", || {
+            for n in 0..4096 {
+                let input = (n as f32 * 0.01).sin();
+                processor.update_tone_controls(1.0, 0.0, -1.0);
+                processor.process_sample(input, 1.5, 5.0, 1.0);
+            }
+        });
+    }
+
+    #[test]
+    fn test_oversampling_and_adaa_switches_do_not_allocate() {
+        let mut processor = GuitarFxProcessor::new();
+        processor.initialize(44100.0);
+        let params = BlockParams::constant(1.0, 12.0, 1.0, 0.0, 0.0, 0.0);
+        let factors = [OversamplingFactor::X2, OversamplingFactor::X8, OversamplingFactor::Off, OversamplingFactor::X4];
+        let phases = [OversamplingPhase::Linear, OversamplingPhase::Minimum];
+        let modes = [WaveshaperMode::Plain, WaveshaperMode::Adaa1, WaveshaperMode::Adaa2];
+
+        assert_no_alloc("oversampling and ADAA switches", || {
+            for n in 0..64 {
+                processor.set_oversampling(factors[n / 8 % 4], phases[n / 4 % 2]);
+                processor.set_waveshaper_mode(modes[n % 3]);
+                let mut block = test_block(n * MAX_BLOCK_SIZE);
+                processor.process_block(&mut block, &params);
+            }
+        });
+    }
+
+    /// Changes one stage's settings before block `n` - one row per switchable stage
+    type StageSwitch = fn(&mut StereoProcessor, &mut BlockParams, usize);
+
+    #[test]
+    fn test_stage_switches_do_not_allocate() {
+        let stages: [(&str, StageSwitch); 6] = [
+            ("tone stack", |processor, params, n| {
+                let models = [
+                    ToneStackModel::Fender,
+                    ToneStackModel::Marshall,
+                    ToneStackModel::Vox,
+                    ToneStackModel::James,
+                    ToneStackModel::Modern,
+                ];
+                processor.set_tone_stack_model(models[n / 8 % models.len()]);
+                params.bass.fill((n % 8) as f32 * 3.0 - 12.0);
+            }),
+            ("noise gate", |processor, params, n| {
+                processor.set_noise_gate(NoiseGateSettings {
+                    enabled: n % 16 != 0,
+                    lookahead_ms: (n % 4) as f32 * 3.0,
+                    ..NoiseGateSettings::default()
+                });
+                params.gate_sidechain = n >= 32;
+                params.gate_key.fill(if n % 8 < 4 { 0.5 } else { 0.0 });
+            }),
+            ("compressor", |processor, _, n| {
+                processor.set_compressor(CompressorSettings {
+                    enabled: n % 16 != 0,
+                    sustain: (n % 5) as f32 * 0.25,
+                    blend: if n % 2 == 0 { 1.0 } else { 0.5 },
+                    ..CompressorSettings::default()
+                });
+            }),
+            ("pedal", |processor, _, n| {
+                let models = [PedalModel::TubeScreamer, PedalModel::TrebleBooster, PedalModel::CleanBoost];
+                processor.set_pedal(PedalSettings {
+                    enabled: n % 16 != 0,
+                    model: models[n / 4 % models.len()],
+                    drive: (n % 4) as f32 / 3.0,
+                    ..PedalSettings::default()
+                });
+                processor.set_oversampling(
+                    if n < 32 { OversamplingFactor::X2 } else { OversamplingFactor::X4 },
+                    OversamplingPhase::Minimum,
+                );
+            }),
+            ("fuzz", |processor, _, n| {
+                processor.set_fuzz(FuzzSettings {
+                    enabled: n % 16 != 0,
+                    model: if n < 32 { FuzzModel::FuzzFace } else { FuzzModel::BigMuff },
+                    bias: 1.0 - (n % 4) as f32 * 0.3,
+                    gate: (n % 3) as f32 * 0.5,
+                    ..FuzzSettings::default()
+                });
+            }),
+            ("delay", |processor, _, n| {
+                processor.set_delay(DelaySettings {
+                    enabled: n % 24 != 0,
+                    mode: if n < 32 { DelayMode::Digital } else { DelayMode::Tape },
+                    time_ms: 50.0 + n as f32 * 10.0,
+                    sync: if n % 16 < 8 { DelaySync::Free } else { DelaySync::DottedEighth },
+                    ping_pong: n % 2 == 0,
+                    ducking: 0.5,
+                    ..DelaySettings::default()
+                });
+            }),
+        ];
+
+        let layouts = [ChannelLayout::Mono, ChannelLayout::MonoToStereo, ChannelLayout::Stereo];
+        for (stage, switch) in stages {
+            for layout in layouts {
+                let mut processor = StereoProcessor::new();
+                processor.initialize(44100.0, layout);
+                let mut params = BlockParams::constant(1.0, 6.0, 1.0, 0.0, 0.0, 0.0);
+
+                assert_no_alloc(&format!("{} in {:?}", stage, layout), || {
+                    for n in 0..64 {
+                        switch(&mut processor, &mut params, n);
+                        let mut left = test_block(n * MAX_BLOCK_SIZE);
+                        let mut right = test_block(n * MAX_BLOCK_SIZE + 7);
+                        if layout == ChannelLayout::Mono {
+                            processor.process_mono_block(&mut left, &params);
+                        } else {
+                            processor.process_block(&mut left, &mut right, &params);
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn test_stereo_layouts_do_not_allocate() {
+        let layouts = [ChannelLayout::Mono, ChannelLayout::MonoToStereo, ChannelLayout::Stereo];
+        for layout in layouts {
+            let mut processor = StereoProcessor::new();
+            processor.initialize(44100.0, layout);
+            processor.set_channel_mode(ChannelMode::Stereo);
+            let mut params = BlockParams::constant(1.0, 6.0, 1.0, 0.0, 0.0, 0.0);
+            params.stereo_width = [0.7; MAX_BLOCK_SIZE];
+
+            assert_no_alloc(&format!("{:?}", layout), || {
+                for n in 0..100 {
+                    let mut left = test_block(n * MAX_BLOCK_SIZE);
+                    let mut right = test_block(n * MAX_BLOCK_SIZE + 7);
+                    processor.process_block(&mut left, &mut right, &params);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_cabinet_crossfade_does_not_allocate() {
+        let loader = CabinetLoader::new();
+        let mut processor = StereoProcessor::new();
+        processor.initialize(44100.0, ChannelLayout::Stereo);
+        processor.set_cabinet_crossfade(20.0);
+        let mut params = BlockParams::constant(1.0, 4.0, 1.0, 0.0, 0.0, 0.0);
+        params.blend_enabled = true;
+
+        for (slot, cabinet_type) in [(0, CabinetType::Mesa4x12Recto), (1, CabinetType::VoxAC30Blue)] {
+            loader.prepare(slot, cabinet_type, None).unwrap();
+            assert_no_alloc(&format!("{:?} into slot {}", cabinet_type, slot), || {
+                assert!(processor.install_cabinets(&loader));
+                for n in 0..40 {
+                    let mut left = test_block(n * MAX_BLOCK_SIZE);
+                    let mut right = test_block(n * MAX_BLOCK_SIZE + 3);
+                    processor.process_block(&mut left, &mut right, &params);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_convolution_mode_switch_does_not_allocate() {
+        let loader = CabinetLoader::new();
+        let mut processor = StereoProcessor::new();
+        processor.initialize(44100.0, ChannelLayout::Stereo);
+        processor.set_cabinet_crossfade(5.0);
+        let params = BlockParams::constant(1.0, 4.0, 1.0, 0.0, 0.0, 0.0);
+
+        let mut latencies = Vec::new();
+        for mode in [ConvolutionMode::ZeroLatency, ConvolutionMode::LowCpu, ConvolutionMode::ZeroLatency] {
+            // A cabinet still prepared for the previous mode is refused, not rebuilt in place
+            loader.prepare(0, CabinetType::Mesa4x12Recto, None).unwrap();
+            assert_no_alloc(&format!("refusing stale engines for {:?}", mode), || {
+                processor.set_convolution_mode(mode);
+                assert!(!processor.install_cabinets(&loader));
+            });
+            assert!(loader.take_stale(0));
+
+            // Engines for the new mode come from the background thread and crossfade in
+            loader.set_convolution_mode(mode);
+            loader.prepare(0, CabinetType::Mesa4x12Recto, None).unwrap();
+            assert_no_alloc(&format!("installing {:?} engines", mode), || {
+                assert!(processor.install_cabinets(&loader));
+                for n in 0..40 {
+                    let mut left = test_block(n * MAX_BLOCK_SIZE);
+                    let mut right = test_block(n * MAX_BLOCK_SIZE + 3);
+                    processor.process_block(&mut left, &mut right, &params);
+                }
+            });
+            latencies.push(processor.get_latency());
+        }
+        assert!(latencies[0] < latencies[1] && latencies[2] == latencies[0]);
+    }
+}